@@ -1,3 +1,5 @@
 fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+    #[cfg(feature = "gui")]
     tauri_build::build()
 }
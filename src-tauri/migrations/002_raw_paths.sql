@@ -0,0 +1,26 @@
+-- Store the raw OS bytes of each path next to the lossy display string and
+-- make them the location identity, so non-UTF-8 names can't collide.
+CREATE TABLE file_locations_new (
+    id            INTEGER PRIMARY KEY AUTOINCREMENT,
+    blake3_hash   TEXT NOT NULL REFERENCES files(blake3_hash),
+    device_id     TEXT NOT NULL REFERENCES storage_devices(id),
+    file_path     TEXT NOT NULL,  -- relative to mount point, lossy UTF-8 for display
+    path_raw      BLOB NOT NULL,  -- relative to mount point, raw OS bytes
+    file_name     TEXT NOT NULL,
+    file_size     INTEGER NOT NULL,
+    modified_at   TEXT,
+    last_verified TEXT NOT NULL DEFAULT (datetime('now')),
+    scan_mode     TEXT NOT NULL DEFAULT 'full',  -- quick/full/deferred
+    UNIQUE(device_id, path_raw)
+);
+
+INSERT INTO file_locations_new
+    (id, blake3_hash, device_id, file_path, path_raw, file_name, file_size, modified_at, last_verified, scan_mode)
+SELECT id, blake3_hash, device_id, file_path, CAST(file_path AS BLOB), file_name, file_size, modified_at, last_verified, scan_mode
+FROM file_locations;
+
+DROP TABLE file_locations;
+ALTER TABLE file_locations_new RENAME TO file_locations;
+
+CREATE INDEX IF NOT EXISTS idx_locations_hash ON file_locations(blake3_hash);
+CREATE INDEX IF NOT EXISTS idx_locations_device ON file_locations(device_id);
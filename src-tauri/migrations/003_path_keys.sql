@@ -0,0 +1,6 @@
+-- Normalized comparison key for each location path: NFC + lowercase, so
+-- names that differ only in Unicode normalization or case compare equal.
+-- Keys are backfilled by the application after migrations run.
+ALTER TABLE file_locations ADD COLUMN path_key TEXT NOT NULL DEFAULT '';
+
+CREATE INDEX IF NOT EXISTS idx_locations_device_key ON file_locations(device_id, path_key);
@@ -0,0 +1,58 @@
+-- Give files an integer key and store the BLAKE3 digest as a 32-byte BLOB
+-- instead of repeating 64-char hex in every location row. Deferred
+-- placeholders keep their "deferred:size:mtime" text as the BLOB.
+-- blake3_hash stays available as a virtual column for readers.
+CREATE TABLE files_new (
+    id                  INTEGER PRIMARY KEY,
+    hash                BLOB NOT NULL UNIQUE,
+    blake3_hash         TEXT GENERATED ALWAYS AS (
+                            CASE WHEN substr(hash, 1, 9) = CAST('deferred:' AS BLOB)
+                                 THEN CAST(hash AS TEXT)
+                                 ELSE lower(hex(hash)) END
+                        ) VIRTUAL,
+    file_size           INTEGER NOT NULL,
+    representative_name TEXT NOT NULL,
+    extension           TEXT NOT NULL DEFAULT '',
+    created_at          TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+INSERT INTO files_new (hash, file_size, representative_name, extension, created_at)
+SELECT CASE WHEN blake3_hash LIKE 'deferred:%' THEN CAST(blake3_hash AS BLOB)
+            ELSE COALESCE(unhex(blake3_hash), CAST(blake3_hash AS BLOB)) END,
+       file_size, representative_name, extension, created_at
+FROM files;
+
+CREATE TABLE file_locations_new (
+    id            INTEGER PRIMARY KEY AUTOINCREMENT,
+    file_id       INTEGER NOT NULL REFERENCES files_new(id),
+    device_id     TEXT NOT NULL REFERENCES storage_devices(id),
+    file_path     TEXT NOT NULL,  -- relative to mount point, lossy UTF-8 for display
+    path_raw      BLOB NOT NULL,  -- relative to mount point, raw OS bytes
+    path_key      TEXT NOT NULL DEFAULT '',
+    file_name     TEXT NOT NULL,
+    file_size     INTEGER NOT NULL,
+    modified_at   TEXT,
+    last_verified TEXT NOT NULL DEFAULT (datetime('now')),
+    scan_mode     TEXT NOT NULL DEFAULT 'full',  -- quick/full/deferred
+    UNIQUE(device_id, path_raw)
+);
+
+INSERT INTO file_locations_new
+    (id, file_id, device_id, file_path, path_raw, path_key, file_name, file_size, modified_at, last_verified, scan_mode)
+SELECT fl.id, f.id, fl.device_id, fl.file_path, fl.path_raw, fl.path_key, fl.file_name, fl.file_size,
+       fl.modified_at, fl.last_verified, fl.scan_mode
+FROM file_locations fl
+JOIN files_new f ON f.hash = CASE WHEN fl.blake3_hash LIKE 'deferred:%'
+                                  THEN CAST(fl.blake3_hash AS BLOB)
+                                  ELSE COALESCE(unhex(fl.blake3_hash), CAST(fl.blake3_hash AS BLOB)) END;
+
+DROP TABLE file_locations;
+DROP TABLE files;
+ALTER TABLE files_new RENAME TO files;
+ALTER TABLE file_locations_new RENAME TO file_locations;
+
+CREATE INDEX IF NOT EXISTS idx_locations_file ON file_locations(file_id);
+CREATE INDEX IF NOT EXISTS idx_locations_device ON file_locations(device_id);
+CREATE INDEX IF NOT EXISTS idx_locations_device_key ON file_locations(device_id, path_key);
+CREATE INDEX IF NOT EXISTS idx_files_extension ON files(extension);
+CREATE INDEX IF NOT EXISTS idx_files_size ON files(file_size);
@@ -0,0 +1,49 @@
+-- Scans become first-class records, and locations that disappear are
+-- tombstoned (removed_at + the scan that noticed) instead of deleted.
+-- Only live rows need a unique path, so the constraint moves to a partial
+-- index, which means rebuilding file_locations once more.
+CREATE TABLE IF NOT EXISTS scans (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    device_id   TEXT NOT NULL REFERENCES storage_devices(id),
+    scan_prefix TEXT NOT NULL,  -- relative to mount point, lossy UTF-8 for display
+    prefix_raw  BLOB NOT NULL,  -- relative to mount point, raw OS bytes
+    scan_mode   TEXT NOT NULL,
+    status      TEXT NOT NULL DEFAULT 'running',  -- running/finished/cancelled/failed
+    started_at  TEXT NOT NULL DEFAULT (datetime('now')),
+    finished_at TEXT,
+    scanned     INTEGER NOT NULL DEFAULT 0,
+    hashed      INTEGER NOT NULL DEFAULT 0,
+    added       INTEGER NOT NULL DEFAULT 0,
+    removed     INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE INDEX IF NOT EXISTS idx_scans_device ON scans(device_id);
+
+CREATE TABLE file_locations_new (
+    id              INTEGER PRIMARY KEY AUTOINCREMENT,
+    file_id         INTEGER NOT NULL REFERENCES files(id),
+    device_id       TEXT NOT NULL REFERENCES storage_devices(id),
+    file_path       TEXT NOT NULL,  -- relative to mount point, lossy UTF-8 for display
+    path_raw        BLOB NOT NULL,  -- relative to mount point, raw OS bytes
+    path_key        TEXT NOT NULL DEFAULT '',
+    file_name       TEXT NOT NULL,
+    file_size       INTEGER NOT NULL,
+    modified_at     TEXT,
+    last_verified   TEXT NOT NULL DEFAULT (datetime('now')),
+    scan_mode       TEXT NOT NULL DEFAULT 'full',  -- quick/full/deferred
+    removed_at      TEXT,  -- NULL while the file is still there
+    removed_by_scan INTEGER REFERENCES scans(id)
+);
+
+INSERT INTO file_locations_new
+    (id, file_id, device_id, file_path, path_raw, path_key, file_name, file_size, modified_at, last_verified, scan_mode)
+SELECT id, file_id, device_id, file_path, path_raw, path_key, file_name, file_size, modified_at, last_verified, scan_mode
+FROM file_locations;
+
+DROP TABLE file_locations;
+ALTER TABLE file_locations_new RENAME TO file_locations;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_locations_live_path ON file_locations(device_id, path_raw) WHERE removed_at IS NULL;
+CREATE INDEX IF NOT EXISTS idx_locations_file ON file_locations(file_id);
+CREATE INDEX IF NOT EXISTS idx_locations_device ON file_locations(device_id);
+CREATE INDEX IF NOT EXISTS idx_locations_device_key ON file_locations(device_id, path_key);
@@ -0,0 +1,12 @@
+-- One snapshot per finished scan: the live (path, hash, size) entries under
+-- the scan prefix, front-coded into a single BLOB (see snapshot.rs).
+CREATE TABLE IF NOT EXISTS scan_snapshots (
+    scan_id     INTEGER PRIMARY KEY REFERENCES scans(id),
+    device_id   TEXT NOT NULL REFERENCES storage_devices(id),
+    entry_count INTEGER NOT NULL,
+    total_bytes INTEGER NOT NULL,
+    data        BLOB NOT NULL,
+    created_at  TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_snapshots_device ON scan_snapshots(device_id);
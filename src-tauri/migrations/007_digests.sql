@@ -0,0 +1,13 @@
+-- Digests other than BLAKE3, per file. They come from checksum files other
+-- tools wrote (sha256sum, md5sum, hashdeep) or are computed during a scan.
+-- `source` names the checksum file, or 'scan'; `verified_at` is set once the
+-- digest has been checked against the file's contents.
+CREATE TABLE IF NOT EXISTS digests (
+    file_id     INTEGER NOT NULL REFERENCES files(id),
+    algorithm   TEXT NOT NULL,  -- sha256/sha1/md5
+    digest      TEXT NOT NULL,  -- lowercase hex
+    source      TEXT NOT NULL,
+    verified_at TEXT,
+    created_at  TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (file_id, algorithm)
+);
@@ -0,0 +1,4 @@
+-- Where a device physically lives (e.g. 'home', 'office'), so a copy in the
+-- same building as the original can be told apart from an off-site one.
+-- NULL means not set.
+ALTER TABLE storage_devices ADD COLUMN site TEXT;
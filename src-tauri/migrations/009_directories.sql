@@ -0,0 +1,20 @@
+-- One row per folder that holds catalogued files, rebuilt per device from its
+-- live locations. `content_hash` is a Merkle hash over the folder's sorted
+-- (name, child hash) entries, so two folders with the same tree and contents
+-- share it wherever they live; it's NULL while anything below is unhashed.
+-- Counts and bytes are recursive.
+CREATE TABLE IF NOT EXISTS directories (
+    id           INTEGER PRIMARY KEY,
+    device_id    TEXT NOT NULL REFERENCES storage_devices(id),
+    path_raw     BLOB NOT NULL,  -- relative to mount point, empty for the device root
+    path         TEXT NOT NULL,  -- lossy UTF-8 for display
+    parent_raw   BLOB,           -- NULL for the device root
+    content_hash BLOB,
+    file_count   INTEGER NOT NULL,
+    total_bytes  INTEGER NOT NULL,
+    built_at     TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_directories_path ON directories(device_id, path_raw);
+CREATE INDEX IF NOT EXISTS idx_directories_parent ON directories(device_id, parent_raw);
+CREATE INDEX IF NOT EXISTS idx_directories_hash ON directories(content_hash);
@@ -0,0 +1,5 @@
+-- Recursive safety aggregates per folder. `unsafe_count` counts files with
+-- no cold copy or fewer than two copies; `duplicate_bytes` is the size of
+-- files whose content is stored more than once on the same device.
+ALTER TABLE directories ADD COLUMN unsafe_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE directories ADD COLUMN duplicate_bytes INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,35 @@
+-- Full-text index over the names and paths of live locations. The default
+-- tokenizer splits on '/', '_', '.', '-' and spaces, so every path segment
+-- and every word of a name is searchable. Triggers keep it in step with
+-- file_locations, whichever of scans, ingests or imports writes there.
+CREATE VIRTUAL TABLE IF NOT EXISTS location_search USING fts5(
+    file_name,
+    file_path,
+    content = 'file_locations',
+    content_rowid = 'id'
+);
+
+INSERT INTO location_search (rowid, file_name, file_path)
+SELECT id, file_name, file_path FROM file_locations WHERE removed_at IS NULL;
+
+CREATE TRIGGER IF NOT EXISTS location_search_insert AFTER INSERT ON file_locations
+WHEN new.removed_at IS NULL
+BEGIN
+    INSERT INTO location_search (rowid, file_name, file_path) VALUES (new.id, new.file_name, new.file_path);
+END;
+
+CREATE TRIGGER IF NOT EXISTS location_search_delete AFTER DELETE ON file_locations
+WHEN old.removed_at IS NULL
+BEGIN
+    INSERT INTO location_search (location_search, rowid, file_name, file_path)
+    VALUES ('delete', old.id, old.file_name, old.file_path);
+END;
+
+-- Tombstoning a location drops it from the index; reviving it adds it back
+CREATE TRIGGER IF NOT EXISTS location_search_update AFTER UPDATE OF file_name, file_path, removed_at ON file_locations
+BEGIN
+    INSERT INTO location_search (location_search, rowid, file_name, file_path)
+    SELECT 'delete', old.id, old.file_name, old.file_path WHERE old.removed_at IS NULL;
+    INSERT INTO location_search (rowid, file_name, file_path)
+    SELECT new.id, new.file_name, new.file_path WHERE new.removed_at IS NULL;
+END;
@@ -0,0 +1,8 @@
+-- Named catalog queries, e.g. `ext:cr3 size>20MB device:cold copies<3`,
+-- kept as typed and parsed again on each run.
+CREATE TABLE IF NOT EXISTS saved_queries (
+    name       TEXT PRIMARY KEY,
+    query      TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
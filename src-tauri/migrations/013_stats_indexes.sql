@@ -0,0 +1,4 @@
+-- Covers the per-file aggregate behind the statistics breakdowns (copies
+-- per device and earliest modified time) without touching location rows.
+CREATE INDEX IF NOT EXISTS idx_locations_live_file
+    ON file_locations(file_id, device_id, modified_at) WHERE removed_at IS NULL;
@@ -0,0 +1,19 @@
+-- Daily capacity history per device: what the volume reported and how much
+-- live content the catalog holds on it. Later samples on the same day
+-- replace earlier ones, so frequent detection doesn't grow the table.
+CREATE TABLE IF NOT EXISTS capacity_samples (
+    device_id        TEXT NOT NULL REFERENCES storage_devices(id),
+    sampled_on       TEXT NOT NULL,  -- date('now') of the sample
+    sampled_at       TEXT NOT NULL,
+    total_bytes      INTEGER NOT NULL,
+    available_bytes  INTEGER NOT NULL,
+    catalogued_bytes INTEGER NOT NULL,
+    PRIMARY KEY (device_id, sampled_on)
+);
+
+-- Start each known device's history from what it last reported
+INSERT INTO capacity_samples (device_id, sampled_on, sampled_at, total_bytes, available_bytes, catalogued_bytes)
+SELECT d.id, date(d.last_seen), d.last_seen, d.total_bytes, d.available_bytes,
+       (SELECT COALESCE(SUM(fl.file_size), 0) FROM file_locations fl
+        WHERE fl.device_id = d.id AND fl.removed_at IS NULL)
+FROM storage_devices d;
@@ -0,0 +1,4 @@
+-- A snapshot can be stored as the changes since an earlier one of the same
+-- device and folder instead of the whole listing (see snapshot.rs).
+-- NULL means the data holds every entry.
+ALTER TABLE scan_snapshots ADD COLUMN base_scan_id INTEGER REFERENCES scan_snapshots(scan_id);
@@ -0,0 +1,258 @@
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+
+use crate::capacity;
+use crate::db;
+use crate::error::AppError;
+use crate::models::{CapacityReport, DashboardStats, FileLocation, FileSafety, ListQuery, Page, ScanEvent, StatsBreakdown};
+use crate::progress::LogSink;
+use crate::scanner::ScanOptions;
+use crate::state::AppState;
+
+pub const DEFAULT_PORT: u16 = 7878;
+
+/// Settings for the local API. It only ever listens on 127.0.0.1, and every
+/// request must carry `Authorization: Bearer <token>`.
+pub struct ApiConfig {
+    pub port: u16,
+    pub token: String,
+}
+
+impl ApiConfig {
+    /// The API is off unless `OFM_API_TOKEN` is set; `OFM_API_PORT`
+    /// overrides the default port.
+    pub fn from_env() -> Option<Self> {
+        let token = std::env::var("OFM_API_TOKEN").ok().filter(|t| !t.is_empty())?;
+        let port = std::env::var("OFM_API_PORT")
+            .ok()
+            .and_then(|p| p.parse().ok())
+            .unwrap_or(DEFAULT_PORT);
+        Some(ApiConfig { port, token })
+    }
+}
+
+#[derive(Clone)]
+struct ApiState {
+    app: AppState,
+    token: Arc<str>,
+}
+
+#[derive(Serialize)]
+struct ScanStatusResponse {
+    running: bool,
+    last_event: Option<ScanEvent>,
+}
+
+#[derive(Deserialize)]
+struct StartScanRequest {
+    target: String,
+    #[serde(default = "default_mode")]
+    mode: String,
+    #[serde(default)]
+    write_manifest: bool,
+    #[serde(default)]
+    digests: Vec<String>,
+}
+
+fn default_mode() -> String {
+    "quick".to_string()
+}
+
+/// Errors as `{"error": "..."}` with a status code scripts can branch on.
+struct ApiError(StatusCode, String);
+
+impl From<AppError> for ApiError {
+    fn from(e: AppError) -> Self {
+        let status = match e {
+            AppError::General(_) => StatusCode::BAD_REQUEST,
+            AppError::ScanRunning => StatusCode::CONFLICT,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        ApiError(status, e.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response()
+    }
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+/// Routes for the catalog queries and scan control, behind token auth.
+pub fn router(app: AppState, token: &str) -> Router {
+    let state = ApiState {
+        app,
+        token: Arc::from(token),
+    };
+    Router::new()
+        .route("/api/stats", get(dashboard_stats))
+        .route("/api/stats/breakdown", get(stats_breakdown))
+        .route("/api/capacity", get(capacity_report))
+        .route("/api/unsafe", get(unsafe_files))
+        .route("/api/files/{hash}/safety", get(file_safety))
+        .route("/api/files/{hash}/locations", get(file_locations))
+        .route("/api/scan", get(scan_status).post(start_scan))
+        .route("/api/scan/cancel", post(cancel_scan))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_token))
+        .with_state(state)
+}
+
+/// Serves the API on localhost until the process exits.
+pub async fn serve(app: AppState, config: ApiConfig) -> Result<(), AppError> {
+    if config.token.is_empty() {
+        return Err(AppError::General("The API needs a non-empty token".to_string()));
+    }
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, config.port));
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    log::info!("API listening on http://{}", addr);
+    axum::serve(listener, router(app, &config.token)).await?;
+    Ok(())
+}
+
+async fn require_token(State(state): State<ApiState>, request: Request, next: Next) -> Response {
+    let presented = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    match presented {
+        Some(token) if tokens_match(token, &state.token) => next.run(request).await,
+        _ => ApiError(StatusCode::UNAUTHORIZED, "Missing or invalid token".to_string()).into_response(),
+    }
+}
+
+/// Compares without stopping at the first differing byte, so response
+/// timing doesn't reveal how much of a guess was right.
+fn tokens_match(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+async fn dashboard_stats(State(state): State<ApiState>) -> ApiResult<DashboardStats> {
+    Ok(Json(db::get_dashboard_stats(&state.app.pool).await?))
+}
+
+async fn stats_breakdown(State(state): State<ApiState>) -> ApiResult<StatsBreakdown> {
+    Ok(Json(db::get_stats_breakdown(&state.app.pool).await?))
+}
+
+async fn capacity_report(State(state): State<ApiState>) -> ApiResult<CapacityReport> {
+    Ok(Json(capacity::capacity_report(&state.app.pool).await?))
+}
+
+async fn unsafe_files(State(state): State<ApiState>, Query(query): Query<ListQuery>) -> ApiResult<Page<FileSafety>> {
+    Ok(Json(db::list_unsafe_files(&state.app.pool, &query).await?))
+}
+
+/// 404 when the hash isn't catalogued, so "unknown" never reads as "safe".
+async fn file_safety(State(state): State<ApiState>, Path(hash): Path<String>) -> ApiResult<FileSafety> {
+    db::get_file_safety(&state.app.pool, &hash)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError(StatusCode::NOT_FOUND, format!("No file with hash {}", hash)))
+}
+
+async fn file_locations(State(state): State<ApiState>, Path(hash): Path<String>) -> ApiResult<Vec<FileLocation>> {
+    Ok(Json(db::get_file_locations(&state.app.pool, &hash).await?))
+}
+
+async fn scan_status(State(state): State<ApiState>) -> ApiResult<ScanStatusResponse> {
+    let (running, last_event) = state.app.scan_status().await;
+    Ok(Json(ScanStatusResponse { running, last_event }))
+}
+
+/// 409 while another scan (from here or the app) is still running.
+async fn start_scan(State(state): State<ApiState>, Json(req): Json<StartScanRequest>) -> Result<StatusCode, ApiError> {
+    let options = ScanOptions {
+        mode: req.mode,
+        write_manifest: req.write_manifest,
+        extra_digests: req.digests,
+    };
+    state.app.start_scan(PathBuf::from(req.target), options, LogSink).await?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn cancel_scan(State(state): State<ApiState>) -> StatusCode {
+    state.app.cancel_scan().await;
+    StatusCode::NO_CONTENT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    use crate::db::tests::temp_pool;
+
+    fn post_scan(token: &str, target: &str) -> Request<Body> {
+        Request::post("/api/scan")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(serde_json::json!({ "target": target }).to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn requests_without_the_token_are_rejected() {
+        let (_dir, pool) = temp_pool().await;
+        let app = router(AppState::new(pool), "secret");
+
+        let missing = Request::get("/api/stats").body(Body::empty()).unwrap();
+        assert_eq!(app.clone().oneshot(missing).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+
+        let wrong = Request::get("/api/stats")
+            .header(header::AUTHORIZATION, "Bearer secreT")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(app.clone().oneshot(wrong).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+
+        let right = Request::get("/api/stats")
+            .header(header::AUTHORIZATION, "Bearer secret")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(app.oneshot(right).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn scan_starts_in_the_background() {
+        let (dir, pool) = temp_pool().await;
+        let app = router(AppState::new(pool), "secret");
+
+        let response = app.oneshot(post_scan("secret", &dir.path().to_string_lossy())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+    }
+
+    #[tokio::test]
+    async fn second_scan_conflicts_with_a_running_one() {
+        let (dir, pool) = temp_pool().await;
+        let state = AppState::new(pool);
+        let app = router(state.clone(), "secret");
+
+        let long_scan = tokio::spawn(std::future::pending::<()>());
+        state.occupy_scan_slot(long_scan.abort_handle()).await;
+
+        let response = app.clone().oneshot(post_scan("secret", &dir.path().to_string_lossy())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let status = Request::get("/api/scan")
+            .header(header::AUTHORIZATION, "Bearer secret")
+            .body(Body::empty())
+            .unwrap();
+        let body = axum::body::to_bytes(app.oneshot(status).await.unwrap().into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["running"], true);
+        long_scan.abort();
+    }
+}
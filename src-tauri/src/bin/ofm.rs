@@ -0,0 +1,791 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+
+#[cfg(feature = "api")]
+use file_manager_rust_lib::api;
+use file_manager_rust_lib::capacity;
+use file_manager_rust_lib::compare;
+use file_manager_rust_lib::db::{self, DbPool};
+use file_manager_rust_lib::devices;
+use file_manager_rust_lib::directories;
+use file_manager_rust_lib::error::AppError;
+use file_manager_rust_lib::ingest::{self, IngestOptions};
+use file_manager_rust_lib::models::{ListQuery, OverlapMatrix};
+use file_manager_rust_lib::progress::StdoutSink;
+use file_manager_rust_lib::query;
+use file_manager_rust_lib::scanner::ScanOptions;
+use file_manager_rust_lib::search;
+use file_manager_rust_lib::state::AppState;
+use file_manager_rust_lib::wipe;
+
+/// Headless access to the FileManager catalog, for cron jobs and SSH sessions.
+#[derive(Parser)]
+#[command(name = "ofm", version, about)]
+struct Cli {
+    /// Catalog database; defaults to the desktop app's
+    #[arg(long, global = true, env = "OFM_DB")]
+    db: Option<PathBuf>,
+
+    /// Print JSON instead of text
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Detect connected volumes and list every known device
+    Devices,
+    /// Scan a folder or volume into the catalog
+    Scan {
+        path: PathBuf,
+        #[arg(long, default_value = "quick", value_parser = ["quick", "full"])]
+        mode: String,
+        /// Write the device manifest after a successful scan
+        #[arg(long)]
+        manifest: bool,
+        /// Extra digests to compute while hashing (sha256, sha1, md5, xxh3)
+        #[arg(long = "digest", value_delimiter = ',')]
+        digests: Vec<String>,
+    },
+    /// Copy a camera card onto a hot device, skipping content already catalogued
+    Ingest {
+        /// The card, or its DCIM folder
+        source: PathBuf,
+        /// Folder on a hot device to copy into
+        destination: PathBuf,
+        #[arg(long, default_value = ingest::DEFAULT_TEMPLATE)]
+        template: String,
+    },
+    /// Files with no cold copy or fewer than two copies
+    Unsafe,
+    /// Files stored more times than the threshold
+    Waste {
+        #[arg(long, default_value_t = 2)]
+        threshold: i64,
+    },
+    /// Files and bytes by media category, extension, year or device type
+    Stats {
+        #[arg(long, default_value = "category", value_parser = clap::builder::PossibleValuesParser::new(db::STATS_DIMENSIONS))]
+        by: String,
+    },
+    /// When each device fills up at its recent growth, and whether cold devices have room for the backlog
+    Capacity {
+        /// Show this device's daily capacity history instead
+        #[arg(long)]
+        device: Option<String>,
+    },
+    /// Sizes and safety of the folders inside a folder on a device
+    Tree {
+        device_id: String,
+        /// Folder relative to the device's root; the root by default
+        #[arg(default_value = "")]
+        path: String,
+    },
+    /// Folders that duplicate each other, within or across devices
+    DupFolders {
+        /// Ignore folders smaller than this many bytes
+        #[arg(long, default_value_t = 1024 * 1024)]
+        min_bytes: i64,
+        /// Also list near-identical folders sharing at least this fraction (0.0-1.0)
+        #[arg(long)]
+        similarity: Option<f64>,
+    },
+    /// Find files by name or path on every device, e.g. `wedding_final*` or `"final v3"`
+    Search {
+        query: String,
+        #[arg(long, default_value_t = search::DEFAULT_LIMIT)]
+        limit: i64,
+    },
+    /// Files matching a query like `ext:cr3 size>20MB device:cold copies<3 modified:2023`
+    Query {
+        /// The query; omit it to run a saved one with --saved
+        #[arg(required_unless_present = "saved")]
+        query: Option<String>,
+        /// Run the saved query with this name
+        #[arg(long, conflicts_with = "query")]
+        saved: Option<String>,
+        /// Also save the query under this name
+        #[arg(long, requires = "query")]
+        save_as: Option<String>,
+        /// Sort by size, name or copies
+        #[arg(long)]
+        sort: Option<String>,
+        #[arg(long, default_value_t = 100)]
+        limit: i64,
+    },
+    /// List saved queries, or delete one
+    Queries {
+        #[arg(long)]
+        delete: Option<String>,
+    },
+    /// Every place a file is stored, by BLAKE3 hash
+    Locate { hash: String },
+    /// Compare a folder with its copy, by content, from the catalog alone
+    Compare {
+        left_device: String,
+        /// Folder relative to the left device's root
+        left_prefix: String,
+        right_device: String,
+        right_prefix: String,
+    },
+    /// How much content each pair of devices has in common
+    Overlap,
+    /// Check that everything on a device (or a folder on it) exists elsewhere
+    WipeCheck {
+        device_id: String,
+        /// Folder relative to the device root
+        #[arg(long, default_value = "")]
+        prefix: String,
+        /// Copies not verified in this many days don't count
+        #[arg(long)]
+        stale_after_days: Option<i64>,
+    },
+    /// Serve the local HTTP API until interrupted
+    #[cfg(feature = "api")]
+    Serve {
+        #[arg(long, env = "OFM_API_PORT", default_value_t = api::DEFAULT_PORT)]
+        port: u16,
+        /// Bearer token clients must send
+        #[arg(long, env = "OFM_API_TOKEN", hide_env_values = true)]
+        token: String,
+    },
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), AppError> {
+    let db_path = match cli.db {
+        Some(path) => path,
+        None => default_db_path()?,
+    };
+    let pool = db::init_pool(&db_path).await?;
+    db::run_migrations(&pool, &db_path).await?;
+
+    match cli.command {
+        Command::Devices => devices_cmd(&pool, cli.json).await,
+        Command::Scan { path, mode, manifest, digests } => {
+            let options = ScanOptions {
+                mode,
+                write_manifest: manifest,
+                extra_digests: digests,
+            };
+            scan_cmd(pool, path, options, cli.json).await
+        }
+        Command::Ingest { source, destination, template } => {
+            let options = IngestOptions {
+                source,
+                destination,
+                template,
+            };
+            ingest_cmd(pool, options, cli.json).await
+        }
+        Command::Unsafe => {
+            let mut query = ListQuery {
+                limit: Some(db::MAX_PAGE_SIZE),
+                ..Default::default()
+            };
+            let mut files = Vec::new();
+            loop {
+                let page = db::list_unsafe_files(&pool, &query).await?;
+                files.extend(page.items);
+                match page.next_cursor {
+                    Some(cursor) => query.cursor = Some(cursor),
+                    None => break,
+                }
+            }
+            if cli.json {
+                return print_json(&files);
+            }
+            for f in &files {
+                println!(
+                    "{}  {:>10}  {} copies ({} hot, {} cold)  {}",
+                    short_hash(&f.blake3_hash),
+                    format_bytes(f.file_size),
+                    f.total_copies,
+                    f.hot_copies,
+                    f.cold_copies,
+                    f.representative_name
+                );
+            }
+            println!("{} unsafe files", files.len());
+            Ok(())
+        }
+        Command::Waste { threshold } => {
+            let mut query = ListQuery {
+                limit: Some(db::MAX_PAGE_SIZE),
+                ..Default::default()
+            };
+            let mut candidates = Vec::new();
+            loop {
+                let page = db::list_waste_candidates(&pool, threshold, &query).await?;
+                candidates.extend(page.items);
+                match page.next_cursor {
+                    Some(cursor) => query.cursor = Some(cursor),
+                    None => break,
+                }
+            }
+            if cli.json {
+                return print_json(&candidates);
+            }
+            for c in &candidates {
+                println!(
+                    "{}  {:>10}  {} copies  {:>10} wasted  {}",
+                    short_hash(&c.blake3_hash),
+                    format_bytes(c.file_size),
+                    c.total_copies,
+                    format_bytes(c.wasted_bytes),
+                    c.representative_name
+                );
+            }
+            let total: i64 = candidates.iter().map(|c| c.wasted_bytes).sum();
+            println!("{} files, {} reclaimable", candidates.len(), format_bytes(total));
+            Ok(())
+        }
+        Command::Stats { by } => {
+            let buckets = db::get_stat_buckets(&pool, &by).await?;
+            if cli.json {
+                return print_json(&buckets);
+            }
+            println!(
+                "{:<12}  {:>8}  {:>10}  {:>10}  {:>10}  {:>10}",
+                by, "files", "unique", "duplicate", "unsafe", "not hashed"
+            );
+            for b in &buckets {
+                println!(
+                    "{:<12}  {:>8}  {:>10}  {:>10}  {:>10}  {:>10}",
+                    if b.key.is_empty() { "(none)" } else { &b.key },
+                    b.files,
+                    format_bytes(b.unique_bytes),
+                    format_bytes(b.duplicate_bytes),
+                    format_bytes(b.unsafe_bytes),
+                    format_bytes(b.not_hashed_bytes)
+                );
+            }
+            Ok(())
+        }
+        Command::Capacity { device: Some(device_id) } => {
+            let samples = db::get_capacity_samples(&pool, Some(&device_id), None).await?;
+            if cli.json {
+                return print_json(&samples);
+            }
+            for s in &samples {
+                println!(
+                    "{}  {:>10} used of {:>10}  {:>10} catalogued",
+                    s.sampled_at,
+                    format_bytes(s.total_bytes - s.available_bytes),
+                    format_bytes(s.total_bytes),
+                    format_bytes(s.catalogued_bytes)
+                );
+            }
+            Ok(())
+        }
+        Command::Capacity { device: None } => {
+            let report = capacity::capacity_report(&pool).await?;
+            if cli.json {
+                return print_json(&report);
+            }
+            for d in &report.devices {
+                let growth = match d.growth_bytes_per_day {
+                    Some(g) if g < 0.0 => format!("-{}/day", format_bytes(-g as i64)),
+                    Some(g) => format!("+{}/day", format_bytes(g as i64)),
+                    None => "no trend yet".to_string(),
+                };
+                println!(
+                    "{:<20}  {:<7}  {:>10} free of {:>10}  {:>14}  {}",
+                    d.label,
+                    d.device_type,
+                    format_bytes(d.available_bytes),
+                    format_bytes(d.total_bytes),
+                    growth,
+                    d.full_on.as_deref().map(|day| format!("full by {}", day)).unwrap_or_default()
+                );
+            }
+            let c = &report.cold;
+            println!(
+                "Not on a cold device: {} files, {}. Free on {} cold devices: {}.",
+                c.backlog_files,
+                format_bytes(c.backlog_bytes),
+                c.cold_devices,
+                format_bytes(c.available_bytes)
+            );
+            if c.cold_devices == 0 {
+                println!("No device is marked cold");
+            } else if c.enough_room {
+                println!("Cold devices have room for the backlog");
+            } else {
+                println!("Cold devices are short by {}", format_bytes(c.shortfall_bytes));
+            }
+            Ok(())
+        }
+        Command::Tree { device_id, path } => {
+            let listing = directories::folder_listing(&pool, &device_id, &path).await?;
+            if cli.json {
+                return print_json(&listing);
+            }
+            let Some(folder) = &listing.folder else {
+                println!("Nothing catalogued under {}", path);
+                return Ok(());
+            };
+            for child in &listing.children {
+                println!(
+                    "{:>10}  {:>8} files  {:>6} unsafe  {:>10} duplicated  {}/",
+                    format_bytes(child.total_bytes),
+                    child.file_count,
+                    child.unsafe_count,
+                    format_bytes(child.duplicate_bytes),
+                    child.name
+                );
+            }
+            println!(
+                "{} in {} files, {} unsafe, {} duplicated on the device",
+                format_bytes(folder.total_bytes),
+                folder.file_count,
+                folder.unsafe_count,
+                format_bytes(folder.duplicate_bytes)
+            );
+            Ok(())
+        }
+        Command::DupFolders { min_bytes, similarity } => {
+            let report = directories::find_duplicate_folders(&pool, min_bytes, similarity).await?;
+            if cli.json {
+                return print_json(&report);
+            }
+            for group in &report.exact {
+                println!(
+                    "{}  {} files  {:>10} each  {:>10} reclaimable",
+                    short_hash(&group.content_hash),
+                    group.file_count,
+                    format_bytes(group.total_bytes),
+                    format_bytes(group.reclaimable_bytes)
+                );
+                for f in &group.folders {
+                    println!("    {}  {}", f.device_id, f.path);
+                }
+            }
+            for pair in &report.similar {
+                println!(
+                    "~{:.0}%  {:>10} shared  {}:{}  {}:{}",
+                    pair.similarity * 100.0,
+                    format_bytes(pair.shared_bytes),
+                    pair.left.device_id,
+                    pair.left.path,
+                    pair.right.device_id,
+                    pair.right.path
+                );
+            }
+            println!(
+                "{} duplicate groups, {} reclaimable",
+                report.exact.len(),
+                format_bytes(report.reclaimable_bytes)
+            );
+            Ok(())
+        }
+        Command::Search { query, limit } => {
+            let results = search::search_files(&pool, &query, limit).await?;
+            if cli.json {
+                return print_json(&results);
+            }
+            for r in &results {
+                println!(
+                    "{}  {:>10}  {} copies ({} cold){}  {}",
+                    short_hash(&r.blake3_hash),
+                    format_bytes(r.file_size),
+                    r.total_copies,
+                    r.cold_copies,
+                    if r.is_safe { "" } else { "  UNSAFE" },
+                    r.representative_name
+                );
+                for l in &r.locations {
+                    println!(
+                        "    {} {} [{}]  {}",
+                        if l.connected { "*" } else { " " },
+                        l.device_label,
+                        l.device_type,
+                        l.location.file_path
+                    );
+                }
+            }
+            println!("{} files (* = connected)", results.len());
+            Ok(())
+        }
+        Command::Query { query: text, saved, save_as, sort, limit } => {
+            let page = ListQuery {
+                limit: Some(limit),
+                sort,
+                ..Default::default()
+            };
+            let results = match (&text, &saved) {
+                (_, Some(name)) => query::run_saved(&pool, name, &page).await?,
+                (Some(text), None) => {
+                    if let Some(name) = &save_as {
+                        query::save(&pool, name, text).await?;
+                    }
+                    query::run(&pool, text, &page).await?
+                }
+                (None, None) => return Err(AppError::General("Give a query or --saved".to_string())),
+            };
+            if cli.json {
+                return print_json(&results);
+            }
+            for f in &results.items {
+                println!(
+                    "{}  {:>10}  {} copies ({} hot, {} cold)  {}",
+                    short_hash(&f.blake3_hash),
+                    format_bytes(f.file_size),
+                    f.total_copies,
+                    f.hot_copies,
+                    f.cold_copies,
+                    f.representative_name
+                );
+            }
+            println!("{} of {} matching files", results.items.len(), results.total);
+            Ok(())
+        }
+        Command::Queries { delete } => {
+            if let Some(name) = delete {
+                if !db::delete_saved_query(&pool, &name).await? {
+                    return Err(AppError::General(format!("No saved query named {}", name)));
+                }
+                return Ok(());
+            }
+            let saved = db::get_saved_queries(&pool).await?;
+            if cli.json {
+                return print_json(&saved);
+            }
+            for q in &saved {
+                println!("{}  {}", q.name, q.query);
+            }
+            Ok(())
+        }
+        Command::Locate { hash } => {
+            let locations = db::get_file_locations(&pool, &hash).await?;
+            // An error in either mode, so scripts can tell from the exit code
+            if locations.is_empty() {
+                return Err(AppError::General(format!("No live locations for {}", hash)));
+            }
+            if cli.json {
+                return print_json(&locations);
+            }
+            let labels: HashMap<String, String> = db::get_all_devices(&pool)
+                .await?
+                .into_iter()
+                .map(|d| (d.id, d.label))
+                .collect();
+            for loc in &locations {
+                let label = labels.get(&loc.device_id).map(String::as_str).unwrap_or("?");
+                println!("{}:{}  (verified {})", label, loc.file_path, loc.last_verified);
+            }
+            Ok(())
+        }
+        Command::Compare { left_device, left_prefix, right_device, right_prefix } => {
+            let report =
+                compare::compare_folders(&pool, &left_device, &left_prefix, &right_device, &right_prefix).await?;
+            if cli.json {
+                return print_json(&report);
+            }
+            for e in &report.only_left {
+                println!("- {}", e.path);
+            }
+            for e in &report.only_right {
+                println!("+ {}", e.path);
+            }
+            for m in &report.moved {
+                println!("> {} -> {}", m.left_path, m.right_path);
+            }
+            for d in &report.different {
+                println!("! {}", d.path);
+            }
+            for p in &report.not_hashed {
+                println!("? {}", p);
+            }
+            let s = &report.summary;
+            println!(
+                "{} identical, {} moved, {} different, {} only left ({}), {} only right ({}), {} not hashed",
+                s.identical.files,
+                s.moved.files,
+                s.different.files,
+                s.only_left.files,
+                format_bytes(s.only_left.bytes),
+                s.only_right.files,
+                format_bytes(s.only_right.bytes),
+                report.not_hashed.len()
+            );
+            println!("{}", if report.in_sync { "In sync" } else { "Not in sync" });
+            Ok(())
+        }
+        Command::Overlap => {
+            let matrix = OverlapMatrix {
+                devices: db::get_device_content_totals(&pool).await?,
+                pairs: db::get_device_overlaps(&pool).await?,
+            };
+            if cli.json {
+                return print_json(&matrix);
+            }
+            let labels: HashMap<&str, &str> =
+                matrix.devices.iter().map(|d| (d.device_id.as_str(), d.label.as_str())).collect();
+            for d in &matrix.devices {
+                println!(
+                    "{:<20}  {:>8} files  {:>10}  {:>10} only here",
+                    d.label,
+                    d.files,
+                    format_bytes(d.bytes),
+                    format_bytes(d.unique_bytes)
+                );
+            }
+            for p in &matrix.pairs {
+                println!(
+                    "{} <-> {}: {} files, {} shared",
+                    labels.get(p.device_a.as_str()).unwrap_or(&"?"),
+                    labels.get(p.device_b.as_str()).unwrap_or(&"?"),
+                    p.shared_files,
+                    format_bytes(p.shared_bytes)
+                );
+            }
+            Ok(())
+        }
+        Command::WipeCheck { device_id, prefix, stale_after_days } => {
+            let report = wipe::wipe_report(&pool, &device_id, &prefix, stale_after_days).await?;
+            if cli.json {
+                return print_json(&report);
+            }
+            for f in &report.files {
+                println!(
+                    "{:<16}  {:>10}  {} other copies  {}",
+                    f.kind,
+                    format_bytes(f.file_size),
+                    f.other_copies,
+                    f.file_path
+                );
+            }
+            println!(
+                "{} files, {}: {} safe ({})",
+                report.total_files,
+                format_bytes(report.total_bytes),
+                report.safe.files,
+                format_bytes(report.safe.bytes)
+            );
+            for cat in report.at_risk.iter().filter(|c| c.files > 0) {
+                println!("  {}: {} ({})", cat.kind, cat.files, format_bytes(cat.bytes));
+            }
+            println!("Verdict: {}", report.verdict);
+            Ok(())
+        }
+        #[cfg(feature = "api")]
+        Command::Serve { port, token } => {
+            eprintln!("Serving the API on http://127.0.0.1:{}", port);
+            api::serve(AppState::new(pool), api::ApiConfig { port, token }).await
+        }
+    }
+}
+
+async fn devices_cmd(pool: &DbPool, json: bool) -> Result<(), AppError> {
+    let detected = devices::detect_volumes();
+    for disk in &detected {
+        db::upsert_device(pool, disk).await?;
+    }
+    let connected: HashSet<&str> = detected.iter().map(|d| d.id.as_str()).collect();
+    let mut all = db::get_all_devices(pool).await?;
+    for dev in &mut all {
+        dev.is_connected = connected.contains(dev.id.as_str());
+    }
+
+    if json {
+        return print_json(&all);
+    }
+    for dev in &all {
+        println!(
+            "{:<36}  {:<7}  {:<10}  {:<9}  {:>10}  {}  {}",
+            dev.id,
+            dev.device_type,
+            dev.site.as_deref().unwrap_or("-"),
+            if dev.is_connected { "connected" } else { "offline" },
+            format_bytes(dev.total_bytes),
+            dev.label,
+            dev.mount_point
+        );
+    }
+    Ok(())
+}
+
+async fn scan_cmd(pool: DbPool, path: PathBuf, options: ScanOptions, json: bool) -> Result<(), AppError> {
+    let path = std::fs::canonicalize(&path)?;
+    let state = AppState::new(pool);
+    let task = state.start_scan(path, options, StdoutSink { json }).await?;
+
+    // First Ctrl-C stops the scan after the current file; the catalog stays consistent
+    let cancel_state = state.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            cancel_state.cancel_scan().await;
+        }
+    });
+
+    match task.await {
+        // The sink has already printed the reason
+        Ok(Err(_)) => Err(AppError::General("Scan failed".to_string())),
+        Ok(Ok(())) => Ok(()),
+        Err(e) => Err(AppError::General(format!("Scan task failed: {}", e))),
+    }
+}
+
+async fn ingest_cmd(pool: DbPool, options: IngestOptions, json: bool) -> Result<(), AppError> {
+    let state = AppState::new(pool);
+    let task = state.start_ingest(options, StdoutSink { json }).await?;
+
+    let cancel_state = state.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            cancel_state.cancel_scan().await;
+        }
+    });
+
+    let report = task
+        .await
+        .map_err(|e| AppError::General(format!("Ingest task failed: {}", e)))??;
+    if json {
+        return print_json(&report);
+    }
+    eprintln!();
+    for issue in &report.issues {
+        println!("failed: {}: {}", issue.file_path, issue.message);
+    }
+    println!(
+        "{} files: copied {} ({}), {} already catalogued, {} already at the destination",
+        report.files,
+        report.copied,
+        format_bytes(report.bytes_copied as i64),
+        report.already_catalogued,
+        report.already_present
+    );
+    if report.cancelled {
+        println!("Ingest cancelled; the card is not safe to format");
+    } else if report.safe_to_format {
+        println!("Every file has a verified copy; the card is safe to format");
+    } else {
+        println!("Some files were not copied; do not format the card");
+    }
+    Ok(())
+}
+
+/// The desktop app keeps its catalog in Tauri's app data directory.
+fn default_db_path() -> Result<PathBuf, AppError> {
+    const IDENTIFIER: &str = "com.lars.filemanager";
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let base = if cfg!(target_os = "macos") {
+        home.map(|h| h.join("Library/Application Support"))
+    } else if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home.map(|h| h.join(".local/share")))
+    };
+    base.map(|b| b.join(IDENTIFIER).join("filemanager.db"))
+        .ok_or_else(|| AppError::General("Cannot locate the catalog; pass --db".to_string()))
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<(), AppError> {
+    let out = serde_json::to_string_pretty(value).map_err(|e| AppError::General(e.to_string()))?;
+    println!("{}", out);
+    Ok(())
+}
+
+fn short_hash(hash: &str) -> &str {
+    &hash[..hash.len().min(12)]
+}
+
+fn format_bytes(bytes: i64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Cli {
+        Cli::try_parse_from([&["ofm"], args].concat()).unwrap()
+    }
+
+    #[test]
+    fn subcommands_and_global_flags_parse() {
+        let cli = parse(&["devices"]);
+        assert!(matches!(cli.command, Command::Devices));
+        assert!(!cli.json && cli.db.is_none());
+
+        let cli = parse(&["scan", "/Volumes/A", "--mode", "full", "--digest", "sha256,md5", "--json"]);
+        assert!(cli.json);
+        let Command::Scan { path, mode, manifest, digests } = cli.command else {
+            panic!("expected scan");
+        };
+        assert_eq!((path, mode.as_str(), manifest), (PathBuf::from("/Volumes/A"), "full", false));
+        assert_eq!(digests, ["sha256", "md5"]);
+        let Command::Scan { mode, digests, .. } = parse(&["scan", "."]).command else {
+            panic!("expected scan");
+        };
+        assert_eq!((mode.as_str(), digests.len()), ("quick", 0));
+
+        assert!(matches!(parse(&["--db", "/tmp/c.db", "unsafe"]).command, Command::Unsafe));
+        assert!(matches!(parse(&["waste"]).command, Command::Waste { threshold: 2 }));
+        assert!(matches!(parse(&["waste", "--threshold", "4"]).command, Command::Waste { threshold: 4 }));
+        let cli = parse(&["locate", "ab12", "--json"]);
+        assert!(cli.json);
+        assert!(matches!(cli.command, Command::Locate { hash } if hash == "ab12"));
+    }
+
+    #[test]
+    fn bad_arguments_are_rejected() {
+        for args in [
+            &[][..],
+            &["scan"],
+            &["scan", ".", "--mode", "fast"],
+            &["waste", "--threshold", "many"],
+            &["locate"],
+            &["stats", "--by", "colour"],
+            &["query", "ext:jpg", "--saved", "raws"],
+        ] {
+            assert!(Cli::try_parse_from([&["ofm"], args].concat()).is_err(), "{:?}", args);
+        }
+    }
+
+    #[tokio::test]
+    async fn locating_an_unknown_hash_fails_in_json_mode_too() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("catalog.db");
+        let hash = "0".repeat(64);
+        for json in [false, true] {
+            let mut args = vec!["--db", db.to_str().unwrap(), "locate", &hash];
+            if json {
+                args.push("--json");
+            }
+            assert!(run(parse(&args)).await.is_err());
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+
+use crate::db::{self, DbPool};
+use crate::error::AppError;
+use crate::models::{CapacityReport, CapacitySample, ColdCapacity, DeviceForecast, StorageDevice};
+
+/// How far back growth is measured
+pub const FORECAST_WINDOW_DAYS: i64 = 90;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Forecasts, per device, when it fills up from the growth of its used space
+/// over the last `FORECAST_WINDOW_DAYS`, and checks the cold devices have
+/// room for everything not yet on one.
+pub async fn capacity_report(pool: &DbPool) -> Result<CapacityReport, AppError> {
+    let since = (Utc::now() - Duration::days(FORECAST_WINDOW_DAYS)).naive_utc();
+    let samples = db::get_capacity_samples(pool, None, None).await?;
+    let devices = db::get_all_devices(pool).await?;
+
+    let forecasts: Vec<DeviceForecast> = devices
+        .iter()
+        .map(|d| {
+            let history: Vec<&CapacitySample> = samples.iter().filter(|s| s.device_id == d.id).collect();
+            forecast(d, &history, since)
+        })
+        .collect();
+
+    let cold: Vec<_> = devices.iter().filter(|d| d.device_type == "cold").collect();
+    let available_bytes: i64 = cold.iter().map(|d| d.available_bytes).sum();
+    let (backlog_files, backlog_bytes) = db::get_cold_backlog(pool).await?;
+    Ok(CapacityReport {
+        devices: forecasts,
+        cold: ColdCapacity {
+            cold_devices: cold.len() as i64,
+            available_bytes,
+            backlog_files,
+            backlog_bytes,
+            shortfall_bytes: (backlog_bytes - available_bytes).max(0),
+            enough_room: !cold.is_empty() && available_bytes >= backlog_bytes,
+        },
+    })
+}
+
+fn forecast(device: &StorageDevice, history: &[&CapacitySample], since: NaiveDateTime) -> DeviceForecast {
+    let points: Vec<(NaiveDateTime, &CapacitySample)> = history
+        .iter()
+        .filter_map(|s| Some((NaiveDateTime::parse_from_str(&s.sampled_at, TIMESTAMP_FORMAT).ok()?, *s)))
+        .filter(|(at, _)| *at >= since)
+        .collect();
+    // A device that never reported a size (e.g. one only known from an
+    // imported catalog) has catalogued growth but no fill date
+    let growth = if device.total_bytes > 0 {
+        slope(&points, |s| (s.total_bytes - s.available_bytes) as f64)
+    } else {
+        None
+    };
+    let catalogued_growth = slope(&points, |s| s.catalogued_bytes as f64);
+
+    let days_until_full = growth.filter(|g| *g > 0.0).map(|g| device.available_bytes as f64 / g);
+    let full_on = days_until_full.zip(points.last()).map(|(days, (at, _))| {
+        // Capped so a barely growing device doesn't overflow the date
+        let seconds = (days.min(365.0 * 1000.0) * 86_400.0) as i64;
+        (*at + Duration::seconds(seconds)).format("%Y-%m-%d").to_string()
+    });
+
+    DeviceForecast {
+        device_id: device.id.clone(),
+        label: device.label.clone(),
+        device_type: device.device_type.clone(),
+        total_bytes: device.total_bytes,
+        available_bytes: device.available_bytes,
+        catalogued_bytes: history.last().map_or(0, |s| s.catalogued_bytes),
+        samples: points.len() as i64,
+        growth_bytes_per_day: growth,
+        catalogued_growth_bytes_per_day: catalogued_growth,
+        days_until_full,
+        full_on,
+    }
+}
+
+/// Least-squares slope of `value` per day, or `None` when the samples span
+/// less than a day.
+fn slope(points: &[(NaiveDateTime, &CapacitySample)], value: impl Fn(&CapacitySample) -> f64) -> Option<f64> {
+    let (first, last) = (points.first()?.0, points.last()?.0);
+    if last - first < Duration::days(1) {
+        return None;
+    }
+    let points: Vec<(f64, &CapacitySample)> = points
+        .iter()
+        .map(|(at, s)| ((*at - first).num_seconds() as f64 / 86_400.0, *s))
+        .collect();
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, s)| value(s)).sum::<f64>() / n;
+    let (mut covariance, mut variance) = (0.0, 0.0);
+    for (x, s) in &points {
+        covariance += (x - mean_x) * (value(s) - mean_y);
+        variance += (x - mean_x) * (x - mean_x);
+    }
+    Some(covariance / variance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::{add_device, temp_pool};
+
+    /// Records a sample `days_ago` at noon with `used` of 1000 bytes taken.
+    async fn sample(pool: &DbPool, device_id: &str, days_ago: i64, used: i64) -> NaiveDateTime {
+        let at = (Utc::now() - Duration::days(days_ago)).date_naive().and_hms_opt(12, 0, 0).unwrap();
+        sqlx::query(
+            "INSERT INTO capacity_samples (device_id, sampled_on, sampled_at, total_bytes, available_bytes, catalogued_bytes)
+             VALUES (?, ?, ?, 1000, ?, ?)",
+        )
+        .bind(device_id)
+        .bind(at.format("%Y-%m-%d").to_string())
+        .bind(at.format(TIMESTAMP_FORMAT).to_string())
+        .bind(1000 - used)
+        .bind(used / 2)
+        .execute(pool)
+        .await
+        .unwrap();
+        at
+    }
+
+    #[tokio::test]
+    async fn fill_date_follows_the_least_squares_growth() {
+        let (_dir, pool) = temp_pool().await;
+        for device in ["growing", "shrinking", "new", "stale"] {
+            add_device(&pool, device, "hot").await;
+        }
+        sqlx::query("UPDATE storage_devices SET total_bytes = 1000, available_bytes = 480")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // Not on a line: the fit through all four is 9.6 bytes a day
+        let mut last = None;
+        for (days_ago, used) in [(40, 300), (30, 420), (20, 480), (10, 600)] {
+            last = Some(sample(&pool, "growing", days_ago, used).await);
+        }
+        sample(&pool, "shrinking", 20, 600).await;
+        sample(&pool, "shrinking", 10, 500).await;
+        sample(&pool, "new", 0, 500).await;
+        // Outside the window, so it has no history to go on
+        sample(&pool, "stale", FORECAST_WINDOW_DAYS + 10, 100).await;
+        sample(&pool, "stale", FORECAST_WINDOW_DAYS + 5, 500).await;
+
+        let report = capacity_report(&pool).await.unwrap();
+        let forecast = |id: &str| report.devices.iter().find(|d| d.device_id == id).unwrap();
+
+        let growing = forecast("growing");
+        assert_eq!(growing.samples, 4);
+        assert!((growing.growth_bytes_per_day.unwrap() - 9.6).abs() < 1e-9);
+        assert!((growing.catalogued_growth_bytes_per_day.unwrap() - 4.8).abs() < 1e-9);
+        assert!((growing.days_until_full.unwrap() - 50.0).abs() < 1e-9);
+        let expected = (last.unwrap() + Duration::days(50)).format("%Y-%m-%d").to_string();
+        assert_eq!(growing.full_on.as_deref(), Some(expected.as_str()));
+
+        let shrinking = forecast("shrinking");
+        assert!(shrinking.growth_bytes_per_day.unwrap() < 0.0);
+        assert_eq!((shrinking.days_until_full, shrinking.full_on.as_deref()), (None, None));
+        // One sample spans no time at all
+        assert_eq!(forecast("new").growth_bytes_per_day, None);
+        assert_eq!((forecast("stale").samples, forecast("stale").growth_bytes_per_day), (0, None));
+    }
+}
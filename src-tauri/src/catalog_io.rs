@@ -0,0 +1,184 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
+
+use crate::db::{self, DbPool, LocationMerge};
+use crate::directories;
+use crate::error::AppError;
+use crate::models::{CatalogDigest, CatalogExportSummary, CatalogFile, CatalogImportSummary, CatalogLocation, StorageDevice};
+use crate::paths;
+
+const FORMAT_NAME: &str = "filemanager-catalog";
+const FORMAT_VERSION: u32 = 2;
+const PAGE_SIZE: i64 = 5000;
+
+/// One line of a catalog export. The first line is always a header; devices
+/// come before files, and files before the digests and locations that refer
+/// to them. Version 2 added digest records.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Record {
+    Header {
+        format: String,
+        version: u32,
+        exported_at: String,
+    },
+    Device(StorageDevice),
+    File(CatalogFile),
+    Digest(CatalogDigest),
+    Location(CatalogLocation),
+}
+
+/// Writes the whole catalog, tombstoned locations included, as JSON Lines.
+/// Scans and snapshots stay local to the catalog that recorded them. Every
+/// page is read in one transaction, so a scan running meanwhile can't leave
+/// locations pointing at files the export already went past.
+pub async fn export_catalog(pool: &DbPool, path: &Path) -> Result<CatalogExportSummary, AppError> {
+    let mut out = BufWriter::new(tokio::fs::File::create(path).await?);
+    let mut tx = pool.begin().await?;
+    let mut summary = CatalogExportSummary::default();
+
+    write_record(
+        &mut out,
+        &Record::Header {
+            format: FORMAT_NAME.to_string(),
+            version: FORMAT_VERSION,
+            exported_at: chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        },
+    )
+    .await?;
+
+    for device in db::get_catalog_devices(&mut tx).await? {
+        write_record(&mut out, &Record::Device(device)).await?;
+        summary.devices += 1;
+    }
+
+    let mut after_id = 0;
+    loop {
+        let page = db::get_catalog_files(&mut tx, after_id, PAGE_SIZE).await?;
+        let Some(last) = page.last() else { break };
+        after_id = last.id;
+        for file in page {
+            write_record(&mut out, &Record::File(file)).await?;
+            summary.files += 1;
+        }
+    }
+
+    let mut after_id = 0;
+    loop {
+        let page = db::get_catalog_digests(&mut tx, after_id, PAGE_SIZE).await?;
+        let Some(last) = page.last() else { break };
+        after_id = last.id;
+        for digest in page {
+            write_record(&mut out, &Record::Digest(digest)).await?;
+            summary.digests += 1;
+        }
+    }
+
+    let mut after_id = 0;
+    loop {
+        let page = db::get_catalog_locations(&mut tx, after_id, PAGE_SIZE).await?;
+        let Some(last) = page.last() else { break };
+        after_id = last.id;
+        for loc in page {
+            write_record(&mut out, &Record::Location(loc)).await?;
+            summary.locations += 1;
+        }
+    }
+
+    tx.commit().await?;
+    out.flush().await?;
+    Ok(summary)
+}
+
+/// Merges an exported catalog into this one in a single transaction, so a
+/// malformed file leaves the local catalog untouched.
+pub async fn import_catalog(pool: &DbPool, path: &Path) -> Result<CatalogImportSummary, AppError> {
+    let mut lines = BufReader::new(tokio::fs::File::open(path).await?).lines();
+    let mut summary = CatalogImportSummary::default();
+
+    match lines.next_line().await? {
+        Some(line) => match parse_record(&line, 1)? {
+            Record::Header { format, version, .. } if format == FORMAT_NAME => {
+                if version > FORMAT_VERSION {
+                    return Err(AppError::General(format!(
+                        "Catalog format version {} is newer than this app supports",
+                        version
+                    )));
+                }
+            }
+            _ => return Err(AppError::General("Not a catalog export".to_string())),
+        },
+        None => return Err(AppError::General("Catalog export is empty".to_string())),
+    }
+
+    let mut tx = pool.begin().await?;
+    let mut devices: HashSet<String> = HashSet::new();
+    let mut line_no = 1;
+    while let Some(line) = lines.next_line().await? {
+        line_no += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_record(&line, line_no)? {
+            Record::Header { .. } => {
+                return Err(AppError::General(format!("Line {}: unexpected header", line_no)));
+            }
+            Record::Device(device) => {
+                db::import_device(&mut tx, &device).await?;
+                devices.insert(device.id);
+                summary.devices += 1;
+            }
+            Record::File(file) => {
+                db::import_file(&mut tx, &file).await?;
+                summary.files += 1;
+            }
+            Record::Digest(digest) => {
+                let file_id = file_id_for(&mut tx, &digest.blake3_hash).await?;
+                db::import_digest(&mut tx, file_id, &digest).await?;
+                summary.digests += 1;
+            }
+            Record::Location(loc) => {
+                if !devices.contains(&loc.device_id) {
+                    return Err(AppError::General(format!(
+                        "Line {}: location on unknown device {}",
+                        line_no, loc.device_id
+                    )));
+                }
+                let path_raw = paths::from_hex(&loc.path_raw)
+                    .ok_or_else(|| AppError::General(format!("Line {}: invalid path_raw", line_no)))?;
+                let file_id = file_id_for(&mut tx, &loc.blake3_hash).await?;
+                match db::import_location(&mut tx, file_id, &path_raw, &loc).await? {
+                    LocationMerge::Added => summary.locations_added += 1,
+                    LocationMerge::Updated => summary.locations_updated += 1,
+                    LocationMerge::Skipped => summary.locations_skipped += 1,
+                }
+            }
+        }
+    }
+    tx.commit().await?;
+    let devices: Vec<&str> = devices.iter().map(String::as_str).collect();
+    directories::rebuild_affected(pool, &devices).await?;
+    Ok(summary)
+}
+
+/// Id of an already imported file; files precede the records that refer to them.
+async fn file_id_for(conn: &mut SqliteConnection, blake3_hash: &str) -> Result<i64, AppError> {
+    db::get_file_id(conn, blake3_hash)
+        .await?
+        .ok_or_else(|| AppError::General(format!("Catalog export refers to unknown file {}", blake3_hash)))
+}
+
+async fn write_record(out: &mut BufWriter<tokio::fs::File>, record: &Record) -> Result<(), AppError> {
+    let mut line = serde_json::to_vec(record).map_err(|e| AppError::General(e.to_string()))?;
+    line.push(b'\n');
+    out.write_all(&line).await?;
+    Ok(())
+}
+
+fn parse_record(line: &str, line_no: usize) -> Result<Record, AppError> {
+    serde_json::from_str(line).map_err(|e| AppError::General(format!("Line {}: {}", line_no, e)))
+}
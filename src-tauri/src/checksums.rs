@@ -0,0 +1,546 @@
+use std::path::{Path, PathBuf};
+
+use crate::db::{self, DbPool};
+use crate::devices::{detect_volumes, device_for_path};
+use crate::error::AppError;
+use crate::hasher;
+use crate::models::{ChecksumExportFlag, ChecksumExportReport, ChecksumImportReport, ChecksumIssue, FileLocation};
+use crate::paths;
+
+/// Algorithm given to 64-digit digests in a plain `*sum` file whose name
+/// doesn't say which tool wrote it: SHA-256 and BLAKE3 look alike, so import
+/// checks them against the catalog's BLAKE3 first.
+pub const SHA256_OR_BLAKE3: &str = "sha256|blake3";
+
+/// One file listed in a checksum file, with every digest given for it. The
+/// path keeps the file's raw bytes, so names that aren't UTF-8 still match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChecksumEntry {
+    pub path: Vec<u8>,
+    pub size: Option<i64>,
+    pub digests: Vec<(String, String)>,
+}
+
+/// Parses the checksum formats other tools write:
+/// - `sha256sum`/`md5sum`/`sha1sum`/`b3sum` lines (`<hex>  <path>`, `*` for
+///   binary mode, a leading `\` when the name is escaped)
+/// - BSD tag lines (`SHA256 (<path>) = <hex>`)
+/// - hashdeep files (`%%%% HASHDEEP-1.0` header, CSV rows)
+///
+/// Plain `*sum` lines don't name their algorithm; it is taken from
+/// `algorithm`, then from the checksum file's name, then from the digest length.
+pub fn parse(data: &[u8], file_name: &str, algorithm: Option<&str>) -> Result<Vec<ChecksumEntry>, AppError> {
+    if data.starts_with(b"%%%% HASHDEEP") {
+        return parse_hashdeep(data);
+    }
+
+    let mut entries = Vec::new();
+    for (i, line) in lines(data).enumerate() {
+        if line.is_empty() || line.starts_with(b"#") {
+            continue;
+        }
+        let entry = parse_bsd_line(line)
+            .or_else(|| parse_sum_line(line, file_name, algorithm))
+            .ok_or_else(|| AppError::General(format!("Line {}: unrecognized checksum line", i + 1)))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+fn lines(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let data = data.strip_suffix(b"\n").unwrap_or(data);
+    data.split(|&b| b == b'\n').map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+}
+
+fn split_once<'a>(line: &'a [u8], separator: &[u8]) -> Option<(&'a [u8], &'a [u8])> {
+    let i = line.windows(separator.len()).position(|w| w == separator)?;
+    Some((&line[..i], &line[i + separator.len()..]))
+}
+
+fn rsplit_once<'a>(line: &'a [u8], separator: &[u8]) -> Option<(&'a [u8], &'a [u8])> {
+    let i = line.windows(separator.len()).rposition(|w| w == separator)?;
+    Some((&line[..i], &line[i + separator.len()..]))
+}
+
+/// A hex digest, lowercased; `None` if `value` isn't one.
+fn hex_digest(value: &[u8]) -> Option<String> {
+    (!value.is_empty() && value.iter().all(u8::is_ascii_hexdigit))
+        .then(|| String::from_utf8_lossy(value).to_lowercase())
+}
+
+fn parse_sum_line(line: &[u8], file_name: &str, algorithm: Option<&str>) -> Option<ChecksumEntry> {
+    let (escaped, line) = match line.strip_prefix(b"\\") {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let (digest, rest) = split_once(line, b" ")?;
+    let digest = hex_digest(digest)?;
+    let path = rest.strip_prefix(b" ").or_else(|| rest.strip_prefix(b"*")).unwrap_or(rest);
+    let path = if escaped { unescape(path) } else { path.to_vec() };
+    let algorithm = match algorithm {
+        Some(a) => normalize_algorithm(a),
+        None => infer_algorithm(file_name, digest.len())?,
+    };
+    Some(ChecksumEntry {
+        path,
+        size: None,
+        digests: vec![(algorithm, digest)],
+    })
+}
+
+fn parse_bsd_line(line: &[u8]) -> Option<ChecksumEntry> {
+    let (tag, rest) = split_once(line, b" (")?;
+    let (path, digest) = rsplit_once(rest, b") = ")?;
+    if tag.contains(&b' ') {
+        return None;
+    }
+    Some(ChecksumEntry {
+        path: path.to_vec(),
+        size: None,
+        digests: vec![(normalize_algorithm(&String::from_utf8_lossy(tag)), hex_digest(digest)?)],
+    })
+}
+
+fn parse_hashdeep(data: &[u8]) -> Result<Vec<ChecksumEntry>, AppError> {
+    let mut columns: Vec<String> = Vec::new();
+    let mut entries = Vec::new();
+    for (i, line) in lines(data).enumerate() {
+        if let Some(header) = line.strip_prefix(b"%%%% ") {
+            if !header.starts_with(b"HASHDEEP") {
+                columns = String::from_utf8_lossy(header).split(',').map(|c| c.trim().to_lowercase()).collect();
+            }
+            continue;
+        }
+        if line.is_empty() || line.starts_with(b"#") {
+            continue;
+        }
+        if columns.last().map(String::as_str) != Some("filename") {
+            return Err(AppError::General("hashdeep file has no column header".to_string()));
+        }
+
+        // The file name is last and may itself contain commas
+        let fields: Vec<&[u8]> = line.splitn(columns.len(), |&b| b == b',').collect();
+        if fields.len() != columns.len() {
+            return Err(AppError::General(format!("Line {}: expected {} fields", i + 1, columns.len())));
+        }
+        let mut entry = ChecksumEntry {
+            path: fields[fields.len() - 1].to_vec(),
+            size: None,
+            digests: Vec::new(),
+        };
+        for (column, value) in columns.iter().zip(&fields[..fields.len() - 1]) {
+            if column == "size" {
+                entry.size = String::from_utf8_lossy(value).parse().ok();
+            } else if let Some(digest) = hex_digest(value) {
+                entry.digests.push((normalize_algorithm(column), digest));
+            }
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Maps tool spellings (`SHA256`, `SHA2-256`, `MD5`, `BLAKE3`) to the names
+/// used in the `digests` table.
+fn normalize_algorithm(name: &str) -> String {
+    match name.to_lowercase().replace('-', "").as_str() {
+        "sha2256" | "sha256" => "sha256".to_string(),
+        "b3" | "blake3" => "blake3".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Guesses the algorithm from the checksum file's name, by whole words with
+/// the extension first (`MD5SUMS`, `photos.b3`, `jobs-b3a.sha256`), then
+/// from the digest length.
+fn infer_algorithm(file_name: &str, digest_len: usize) -> Option<String> {
+    let name = file_name.to_lowercase();
+    let named = name
+        .rsplit(|c: char| !c.is_ascii_alphanumeric())
+        .find_map(|word| match word.trim_end_matches("sums").trim_end_matches("sum") {
+            "b3" | "blake3" => Some("blake3"),
+            "sha256" => Some("sha256"),
+            "sha1" => Some("sha1"),
+            "md5" => Some("md5"),
+            _ => None,
+        });
+    let algorithm = match (named, digest_len) {
+        (Some(algorithm), _) => algorithm,
+        (None, 32) => "md5",
+        (None, 40) => "sha1",
+        (None, 64) => SHA256_OR_BLAKE3,
+        (None, _) => return None,
+    };
+    Some(algorithm.to_string())
+}
+
+/// Settles a digest of unknown algorithm: it's BLAKE3 if it matches the
+/// catalog's hash for the file, and SHA-256 otherwise.
+fn resolve_algorithm<'a>(algorithm: &'a str, digest: &str, blake3_hash: &str) -> &'a str {
+    match algorithm {
+        SHA256_OR_BLAKE3 if digest == blake3_hash => "blake3",
+        SHA256_OR_BLAKE3 => "sha256",
+        other => other,
+    }
+}
+
+/// Undoes GNU coreutils escaping of `\\` and `\n` in file names.
+fn unescape(path: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(path.len());
+    let mut bytes = path.iter();
+    while let Some(&b) = bytes.next() {
+        if b == b'\\' {
+            match bytes.next() {
+                Some(b'n') => out.push(b'\n'),
+                Some(&other) => out.push(other),
+                None => out.push(b'\\'),
+            }
+        } else {
+            out.push(b);
+        }
+    }
+    out
+}
+
+/// Imports a checksum file found on a device. Each listed file is matched to
+/// its live catalog location, checked by size, and, with `rehash`, re-read to
+/// confirm the digest. Digests that check out are stored in `digests`; BLAKE3
+/// digests are compared with the catalog hash instead. Paths are relative to
+/// the checksum file's folder unless absolute.
+pub async fn import_checksum_file(
+    pool: &DbPool,
+    checksum_file: &Path,
+    algorithm: Option<&str>,
+    rehash: bool,
+) -> Result<ChecksumImportReport, AppError> {
+    let data = tokio::fs::read(checksum_file).await?;
+    let file_name = checksum_file.file_name().unwrap_or_default().to_string_lossy();
+    let entries = parse(&data, &file_name, algorithm)?;
+
+    let volumes = detect_volumes();
+    let checksum_str = checksum_file.to_string_lossy().to_string();
+    let (device_id, mount_point) = device_for_path(&volumes, &checksum_str)
+        .ok_or_else(|| AppError::General(format!("No device found for path: {}", checksum_str)))?;
+    let base_dir = checksum_file.parent().unwrap_or(Path::new(&mount_point)).to_path_buf();
+
+    let mut report = ChecksumImportReport {
+        checksum_file: checksum_str.clone(),
+        device_id: device_id.clone(),
+        entries: entries.len() as u64,
+        ..Default::default()
+    };
+
+    for entry in entries {
+        let issue = |kind: &str, algorithm: Option<&str>, expected: Option<&str>, actual: Option<&str>| ChecksumIssue {
+            file_path: String::from_utf8_lossy(&entry.path).into_owned(),
+            kind: kind.to_string(),
+            algorithm: algorithm.map(String::from),
+            expected: expected.map(String::from),
+            actual: actual.map(String::from),
+        };
+
+        let listed = paths::from_raw(entry.path.strip_prefix(b"./").unwrap_or(&entry.path));
+        let absolute: PathBuf = if listed.is_absolute() { listed } else { base_dir.join(listed) };
+        let Ok(relative) = absolute.strip_prefix(&mount_point) else {
+            report.issues.push(issue("outside_device", None, None, None));
+            continue;
+        };
+
+        let Some(loc) = find_location(pool, &device_id, relative).await? else {
+            report.issues.push(issue("not_catalogued", None, None, None));
+            continue;
+        };
+        if loc.blake3_hash.starts_with("deferred:") {
+            report.issues.push(issue("not_hashed", None, None, None));
+            continue;
+        }
+
+        let on_disk = match tokio::fs::metadata(&absolute).await {
+            Ok(m) => m.len() as i64,
+            Err(_) => {
+                report.issues.push(issue("missing", None, None, None));
+                continue;
+            }
+        };
+        let listed_size = entry.size.unwrap_or(loc.file_size);
+        if on_disk != loc.file_size || listed_size != loc.file_size {
+            let expected = listed_size.to_string();
+            let actual = on_disk.to_string();
+            report.issues.push(issue("size_mismatch", None, Some(&expected), Some(&actual)));
+            continue;
+        }
+        report.matched += 1;
+
+        for (algorithm, digest) in &entry.digests {
+            let algorithm = resolve_algorithm(algorithm, digest, &loc.blake3_hash);
+            if algorithm == "blake3" {
+                if *digest != loc.blake3_hash {
+                    report.issues.push(issue("digest_mismatch", Some(algorithm), Some(digest), Some(&loc.blake3_hash)));
+                }
+                continue;
+            }
+            if !hasher::EXTRA_ALGORITHMS.contains(&algorithm) {
+                continue;
+            }
+            if let Some(known) = db::get_digest(pool, loc.file_id, algorithm).await? {
+                if known != *digest {
+                    report.issues.push(issue("conflict", Some(algorithm), Some(digest), Some(&known)));
+                    continue;
+                }
+            }
+            if rehash {
+                let Ok(actual) = hasher::digest_file(&absolute, algorithm).await else {
+                    report.issues.push(issue("unreadable", Some(algorithm), Some(digest), None));
+                    continue;
+                };
+                if actual != *digest {
+                    report.issues.push(issue("digest_mismatch", Some(algorithm), Some(digest), Some(&actual)));
+                    continue;
+                }
+                report.verified += 1;
+            }
+            db::upsert_digest(pool, loc.file_id, algorithm, digest, &checksum_str, rehash).await?;
+            report.stored += 1;
+        }
+    }
+
+    Ok(report)
+}
+
+async fn find_location(pool: &DbPool, device_id: &str, relative: &Path) -> Result<Option<FileLocation>, AppError> {
+    if let Some(loc) = db::get_existing_location(pool, device_id, &paths::to_raw(relative)).await? {
+        return Ok(Some(loc));
+    }
+    db::get_location_by_key(pool, device_id, &paths::path_key(&relative.to_string_lossy())).await
+}
+
+/// Writes a `b3sum`, `sha256sum` or hashdeep file for everything under
+/// `prefix` on a device, from the catalog. Paths are relative to `prefix`, so
+/// the file checks out with `b3sum -c` and friends when placed in that folder.
+///
+/// Without `reverify`, deferred entries and entries lacking the digest the
+/// format needs are left out, and entries last verified more than
+/// `stale_after_days` ago are written but flagged. With `reverify`, the device
+/// must be connected and every file is re-read first; files that changed or
+/// disappeared are left out, the rest are marked verified.
+pub async fn export_checksum_file(
+    pool: &DbPool,
+    device_id: &str,
+    prefix: &str,
+    format: &str,
+    output: &Path,
+    stale_after_days: Option<i64>,
+    reverify: bool,
+) -> Result<ChecksumExportReport, AppError> {
+    let needed: Vec<String> = match format {
+        "b3sum" => Vec::new(),
+        "sha256sum" => vec!["sha256".to_string()],
+        "hashdeep" => vec!["md5".to_string(), "sha256".to_string()],
+        other => return Err(AppError::General(format!("Unsupported checksum format: {}", other))),
+    };
+
+    let prefix_raw = paths::prefix_raw(prefix);
+    let locations = db::get_locations_under(pool, device_id, &prefix_raw).await?;
+
+    let mount_point = if reverify {
+        let mount = detect_volumes()
+            .into_iter()
+            .find(|d| d.id == device_id)
+            .map(|d| d.mount_point)
+            .ok_or_else(|| AppError::General("Device must be connected to re-verify".to_string()))?;
+        Some(mount)
+    } else {
+        None
+    };
+    let stale_before = stale_after_days.map(|days| {
+        (chrono::Utc::now() - chrono::Duration::days(days))
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string()
+    });
+    // An earlier export written into the same folder isn't part of the listing
+    let output_name = paths::to_raw(Path::new(output.file_name().unwrap_or_default()));
+
+    let mut report = ChecksumExportReport {
+        output_path: output.to_string_lossy().to_string(),
+        format: format.to_string(),
+        ..Default::default()
+    };
+    let mut out: Vec<u8> = Vec::new();
+    if format == "hashdeep" {
+        out.extend_from_slice(b"%%%% HASHDEEP-1.0\n%%%% size,md5,sha256,filename\n## Exported by FileManager\n##\n");
+    }
+
+    for loc in locations {
+        let relative = &loc.path_raw[prefix_raw.len()..];
+        if relative == output_name.as_slice() {
+            continue;
+        }
+        let flag = |kind: &str, included: bool| ChecksumExportFlag {
+            file_path: loc.file_path.clone(),
+            kind: kind.to_string(),
+            last_verified: loc.last_verified.clone(),
+            included,
+        };
+        let deferred = loc.blake3_hash.starts_with("deferred:");
+
+        let (blake3, digests) = match &mount_point {
+            Some(mount) => {
+                let absolute = Path::new(mount).join(paths::from_raw(&loc.path_raw));
+                let hashes = match hasher::hash_file_with(&absolute, &needed).await {
+                    Ok(h) => h,
+                    Err(_) => {
+                        report.flags.push(flag("missing", false));
+                        continue;
+                    }
+                };
+                if deferred {
+                    report.flags.push(flag("deferred", true));
+                } else if hashes.blake3 != loc.blake3_hash {
+                    report.flags.push(flag("changed", false));
+                    continue;
+                } else {
+                    db::mark_verified(pool, loc.id).await?;
+                    for (algorithm, digest) in &hashes.extra {
+                        db::upsert_digest(pool, loc.file_id, algorithm, digest, "export", true).await?;
+                    }
+                }
+                report.verified += 1;
+                (hashes.blake3, hashes.extra.into_iter().map(|(_, d)| d).collect::<Vec<_>>())
+            }
+            None => {
+                if deferred {
+                    report.flags.push(flag("deferred", false));
+                    continue;
+                }
+                let mut digests = Vec::with_capacity(needed.len());
+                for algorithm in &needed {
+                    if let Some(d) = db::get_digest(pool, loc.file_id, algorithm).await? {
+                        digests.push(d);
+                    }
+                }
+                if digests.len() < needed.len() {
+                    report.flags.push(flag("missing_digest", false));
+                    continue;
+                }
+                if stale_before.as_ref().is_some_and(|before| loc.last_verified < *before) {
+                    report.flags.push(flag("stale", true));
+                }
+                (loc.blake3_hash.clone(), digests)
+            }
+        };
+
+        match format {
+            "b3sum" => write_sum_line(&mut out, &blake3, relative),
+            "sha256sum" => write_sum_line(&mut out, &digests[0], relative),
+            _ => {
+                out.extend_from_slice(format!("{},{},{},", loc.file_size, digests[0], digests[1]).as_bytes());
+                out.extend_from_slice(relative);
+                out.push(b'\n');
+            }
+        }
+        report.written += 1;
+    }
+
+    tokio::fs::write(output, &out).await?;
+    Ok(report)
+}
+
+/// Writes a coreutils-style `<hex>  <path>` line. Names containing `\` or a
+/// newline are escaped and the line prefixed with `\`, as the tools expect.
+fn write_sum_line(out: &mut Vec<u8>, digest: &str, path: &[u8]) {
+    let escape = path.contains(&b'\\') || path.contains(&b'\n');
+    if escape {
+        out.push(b'\\');
+    }
+    out.extend_from_slice(digest.as_bytes());
+    out.extend_from_slice(b"  ");
+    for &b in path {
+        match b {
+            b'\\' if escape => out.extend_from_slice(b"\\\\"),
+            b'\n' if escape => out.extend_from_slice(b"\\n"),
+            _ => out.push(b),
+        }
+    }
+    out.push(b'\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::{add_device, add_location, temp_pool};
+
+    #[test]
+    fn names_that_are_not_utf8_keep_their_bytes() {
+        let data = [b"d41d8cd98f00b204e9800998ecf8427e  Caf\xe9.jpg\r\n".as_slice(), b"MD5 (na\xefve.jpg) = D41D8CD98F00B204E9800998ECF8427E\n"].concat();
+        let entries = parse(&data, "MD5SUMS", None).unwrap();
+        assert_eq!(entries[0].path, b"Caf\xe9.jpg");
+        assert_eq!(entries[1].path, b"na\xefve.jpg");
+        assert_eq!(entries[1].digests, [("md5".to_string(), "d41d8cd98f00b204e9800998ecf8427e".to_string())]);
+    }
+
+    #[test]
+    fn unnamed_64_digit_digests_match_the_catalog_blake3_first() {
+        let blake3_hash = blake3::hash(b"x").to_hex().to_string();
+        let entries = parse(format!("{}  x\n", blake3_hash).as_bytes(), "CHECKSUMS", None).unwrap();
+        let (algorithm, digest) = &entries[0].digests[0];
+        assert_eq!(algorithm, SHA256_OR_BLAKE3);
+        assert_eq!(resolve_algorithm(algorithm, digest, &blake3_hash), "blake3");
+        assert_eq!(resolve_algorithm(algorithm, &"0".repeat(64), &blake3_hash), "sha256");
+        assert_eq!(resolve_algorithm("sha256", digest, &blake3_hash), "sha256");
+    }
+
+    #[test]
+    fn algorithm_names_match_whole_words_extension_first() {
+        let infer = |name: &str, len: usize| infer_algorithm(name, len);
+        assert_eq!(infer("MD5SUMS", 32).as_deref(), Some("md5"));
+        assert_eq!(infer("photos.b3", 64).as_deref(), Some("blake3"));
+        assert_eq!(infer("b3sums", 64).as_deref(), Some("blake3"));
+        assert_eq!(infer("SHA1SUMS.txt", 40).as_deref(), Some("sha1"));
+        assert_eq!(infer("jobs-b3a.sha256", 64).as_deref(), Some("sha256"));
+        assert_eq!(infer("b3sums.sha256", 64).as_deref(), Some("sha256"));
+        // Nothing named: decided by length
+        assert_eq!(infer("jobs-b3a.txt", 64).as_deref(), Some(SHA256_OR_BLAKE3));
+        assert_eq!(infer("checksums", 40).as_deref(), Some("sha1"));
+        assert_eq!(infer("checksums", 12), None);
+    }
+
+    #[tokio::test]
+    async fn export_writes_what_the_catalog_can_vouch_for() {
+        let (dir, pool) = temp_pool().await;
+        add_device(&pool, "d1", "hot").await;
+        let scan = db::begin_scan(&pool, "d1", "", b"", "full").await.unwrap();
+        let fresh = add_location(&pool, scan, "d1", "Job/a.mov").await;
+        let old = add_location(&pool, scan, "d1", "Job/b\\c.mov").await;
+        add_location(&pool, scan, "d1", "Other/x.mov").await;
+        let deferred = db::upsert_file(&pool, "deferred:1:100", 1, "d.mov", "mov").await.unwrap();
+        db::upsert_location(&pool, scan, deferred, "d1", "Job/d.mov", b"Job/d.mov", "d.mov", 1, None, "deferred")
+            .await
+            .unwrap();
+        sqlx::query("UPDATE file_locations SET last_verified = '2000-01-01 00:00:00' WHERE file_id = ?")
+            .bind(old)
+            .execute(&pool)
+            .await
+            .unwrap();
+        db::upsert_digest(&pool, fresh, "sha256", &"a".repeat(64), "scan", true).await.unwrap();
+
+        let output = dir.path().join("Job.b3");
+        let report = export_checksum_file(&pool, "d1", "Job", "b3sum", &output, Some(365), false).await.unwrap();
+        let flags: Vec<(&str, &str, bool)> =
+            report.flags.iter().map(|f| (f.file_path.as_str(), f.kind.as_str(), f.included)).collect();
+        assert_eq!(flags, [("Job/b\\c.mov", "stale", true), ("Job/d.mov", "deferred", false)]);
+        assert_eq!(report.written, 2);
+        let written = std::fs::read(&output).unwrap();
+        let entries = parse(&written, "Job.b3", None).unwrap();
+        let mut paths: Vec<&[u8]> = entries.iter().map(|e| e.path.as_slice()).collect();
+        paths.sort();
+        assert_eq!(paths, [b"a.mov".as_slice(), b"b\\c.mov"]);
+        assert!(entries.iter().all(|e| e.digests[0].0 == "blake3"));
+
+        // Without a stored SHA-256 a file can't go into a sha256sum file
+        let output = dir.path().join("Job.sha256");
+        let report = export_checksum_file(&pool, "d1", "Job", "sha256sum", &output, None, false).await.unwrap();
+        assert_eq!(report.written, 1);
+        assert!(report.flags.iter().any(|f| f.file_path == "Job/b\\c.mov" && f.kind == "missing_digest"));
+        assert_eq!(std::fs::read(&output).unwrap(), format!("{}  a.mov\n", "a".repeat(64)).into_bytes());
+    }
+}
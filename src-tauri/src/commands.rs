@@ -1,21 +1,26 @@
 use std::collections::HashSet;
 use std::path::PathBuf;
-use std::sync::Arc;
 
 use tauri::ipc::Channel;
 use tauri::State;
-use tokio::sync::Mutex;
-use tokio_util::sync::CancellationToken;
 
-use crate::db::{self, DbPool};
+use crate::capacity;
+use crate::catalog_io;
+use crate::checksums;
+use crate::compare;
+use crate::db;
 use crate::devices;
+use crate::directories;
+use crate::ingest;
 use crate::error::AppError;
+use crate::manifest;
 use crate::models::*;
-
-pub struct AppState {
-    pub pool: DbPool,
-    pub cancel_token: Arc<Mutex<Option<CancellationToken>>>,
-}
+use crate::query;
+use crate::scanner::ScanOptions;
+use crate::search;
+use crate::snapshot;
+use crate::state::AppState;
+use crate::wipe;
 
 fn mark_connected(mut devices: Vec<StorageDevice>, connected_ids: &HashSet<String>) -> Vec<StorageDevice> {
     for dev in &mut devices {
@@ -49,7 +54,20 @@ pub async fn set_device_type(
     device_id: String,
     device_type: String,
 ) -> Result<(), AppError> {
-    db::set_device_type(&state.pool, &device_id, &device_type).await
+    db::set_device_type(&state.pool, &device_id, &device_type).await?;
+    // Safety counts of every folder holding this device's content change with it
+    directories::rebuild_affected(&state.pool, &[&device_id]).await
+}
+
+/// An empty site clears it.
+#[tauri::command]
+pub async fn set_device_site(
+    state: State<'_, AppState>,
+    device_id: String,
+    site: Option<String>,
+) -> Result<(), AppError> {
+    let site = site.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    db::set_device_site(&state.pool, &device_id, site).await
 }
 
 #[tauri::command]
@@ -57,34 +75,42 @@ pub async fn start_scan(
     state: State<'_, AppState>,
     target: String,
     mode: String,
+    write_manifest: Option<bool>,
+    digests: Option<Vec<String>>,
     on_event: Channel<ScanEvent>,
 ) -> Result<(), AppError> {
-    let pool = state.pool.clone();
-    let cancel_token = CancellationToken::new();
-
-    {
-        let mut guard = state.cancel_token.lock().await;
-        *guard = Some(cancel_token.clone());
-    }
-
-    let target = PathBuf::from(target);
-    tokio::spawn(async move {
-        if let Err(e) = crate::scanner::run_scan(pool, target, mode, on_event.clone(), cancel_token).await {
-            let _ = on_event.send(ScanEvent::Error {
-                message: e.to_string(),
-            });
-        }
-    });
-
+    let options = ScanOptions {
+        mode,
+        write_manifest: write_manifest.unwrap_or(false),
+        extra_digests: digests.unwrap_or_default(),
+    };
+    state.start_scan(PathBuf::from(target), options, on_event).await?;
     Ok(())
 }
 
+/// Runs until the card is offloaded; progress streams over `on_event` and
+/// `cancel_scan` stops it between files.
+#[tauri::command]
+pub async fn ingest_card(
+    state: State<'_, AppState>,
+    source: String,
+    destination: String,
+    template: Option<String>,
+    on_event: Channel<ScanEvent>,
+) -> Result<IngestReport, AppError> {
+    let options = ingest::IngestOptions {
+        source: PathBuf::from(source),
+        destination: PathBuf::from(destination),
+        template: template.unwrap_or_else(|| ingest::DEFAULT_TEMPLATE.to_string()),
+    };
+    let task = state.start_ingest(options, on_event).await?;
+    task.await
+        .map_err(|e| AppError::General(format!("Ingest task failed: {}", e)))?
+}
+
 #[tauri::command]
 pub async fn cancel_scan(state: State<'_, AppState>) -> Result<(), AppError> {
-    let guard = state.cancel_token.lock().await;
-    if let Some(token) = guard.as_ref() {
-        token.cancel();
-    }
+    state.cancel_scan().await;
     Ok(())
 }
 
@@ -92,8 +118,46 @@ pub async fn cancel_scan(state: State<'_, AppState>) -> Result<(), AppError> {
 pub async fn get_files_on_device(
     state: State<'_, AppState>,
     device_id: String,
-) -> Result<Vec<FileLocation>, AppError> {
-    db::get_files_on_device(&state.pool, &device_id).await
+    query: Option<ListQuery>,
+) -> Result<Page<FileLocation>, AppError> {
+    db::list_device_files(&state.pool, &device_id, &query.unwrap_or_default()).await
+}
+
+#[tauri::command]
+pub async fn get_folder_listing(
+    state: State<'_, AppState>,
+    device_id: String,
+    path: String,
+) -> Result<FolderListing, AppError> {
+    directories::folder_listing(&state.pool, &device_id, &path).await
+}
+
+#[tauri::command]
+pub async fn get_folder_files(
+    state: State<'_, AppState>,
+    device_id: String,
+    path: String,
+    cursor: Option<String>,
+    limit: i64,
+) -> Result<FolderFilePage, AppError> {
+    directories::folder_files(&state.pool, &device_id, &path, cursor.as_deref(), limit).await
+}
+
+#[tauri::command]
+pub async fn get_non_utf8_files(
+    state: State<'_, AppState>,
+    device_id: Option<String>,
+) -> Result<Vec<NonUtf8File>, AppError> {
+    db::get_non_utf8_files(&state.pool, device_id.as_deref()).await
+}
+
+#[tauri::command]
+pub async fn get_name_conflicts(
+    state: State<'_, AppState>,
+    device_id: Option<String>,
+    across_devices: Option<bool>,
+) -> Result<Vec<NameConflict>, AppError> {
+    db::get_name_conflicts(&state.pool, device_id.as_deref(), across_devices.unwrap_or(false)).await
 }
 
 #[tauri::command]
@@ -105,16 +169,20 @@ pub async fn get_file_safety(
 }
 
 #[tauri::command]
-pub async fn get_unsafe_files(state: State<'_, AppState>) -> Result<Vec<FileSafety>, AppError> {
-    db::get_unsafe_files(&state.pool).await
+pub async fn get_unsafe_files(
+    state: State<'_, AppState>,
+    query: Option<ListQuery>,
+) -> Result<Page<FileSafety>, AppError> {
+    db::list_unsafe_files(&state.pool, &query.unwrap_or_default()).await
 }
 
 #[tauri::command]
 pub async fn get_waste_candidates(
     state: State<'_, AppState>,
     threshold: Option<i64>,
-) -> Result<Vec<WasteCandidate>, AppError> {
-    db::get_waste_candidates(&state.pool, threshold.unwrap_or(2)).await
+    query: Option<ListQuery>,
+) -> Result<Page<WasteCandidate>, AppError> {
+    db::list_waste_candidates(&state.pool, threshold.unwrap_or(2), &query.unwrap_or_default()).await
 }
 
 #[tauri::command]
@@ -133,11 +201,10 @@ pub async fn browse_directory(path: String) -> Result<Vec<DirEntry>, AppError> {
             .modified()
             .ok()
             .and_then(|t| t.duration_since(std::time::SystemTime::UNIX_EPOCH).ok())
-            .map(|d| {
+            .and_then(|d| {
                 chrono::DateTime::from_timestamp(d.as_secs() as i64, 0)
                     .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
-            })
-            .flatten();
+            });
 
         entries.push(DirEntry {
             name,
@@ -159,7 +226,214 @@ pub async fn get_file_locations(
     db::get_file_locations(&state.pool, &hash).await
 }
 
+#[tauri::command]
+pub async fn get_location_history(
+    state: State<'_, AppState>,
+    hash: String,
+) -> Result<Vec<FileLocation>, AppError> {
+    db::get_location_history(&state.pool, &hash).await
+}
+
+#[tauri::command]
+pub async fn get_lost_files(state: State<'_, AppState>) -> Result<Vec<LostFile>, AppError> {
+    db::get_lost_files(&state.pool).await
+}
+
+#[tauri::command]
+pub async fn get_snapshots(
+    state: State<'_, AppState>,
+    device_id: String,
+) -> Result<Vec<SnapshotInfo>, AppError> {
+    db::get_snapshots(&state.pool, &device_id).await
+}
+
+#[tauri::command]
+pub async fn diff_snapshots(
+    state: State<'_, AppState>,
+    from_scan_id: i64,
+    to_scan_id: i64,
+) -> Result<SnapshotDiff, AppError> {
+    snapshot::diff_snapshots(&state.pool, from_scan_id, to_scan_id).await
+}
+
+#[tauri::command]
+pub async fn export_catalog(
+    state: State<'_, AppState>,
+    path: String,
+) -> Result<CatalogExportSummary, AppError> {
+    catalog_io::export_catalog(&state.pool, &PathBuf::from(path)).await
+}
+
+#[tauri::command]
+pub async fn import_catalog(
+    state: State<'_, AppState>,
+    path: String,
+) -> Result<CatalogImportSummary, AppError> {
+    catalog_io::import_catalog(&state.pool, &PathBuf::from(path)).await
+}
+
+#[tauri::command]
+pub async fn write_device_manifest(
+    state: State<'_, AppState>,
+    device_id: String,
+    mount_point: String,
+) -> Result<ManifestSummary, AppError> {
+    manifest::write_manifest(&state.pool, &device_id, &PathBuf::from(mount_point)).await
+}
+
+#[tauri::command]
+pub async fn rebuild_from_manifest(
+    state: State<'_, AppState>,
+    mount_point: String,
+) -> Result<CatalogImportSummary, AppError> {
+    manifest::rebuild_from_manifest(&state.pool, &PathBuf::from(mount_point)).await
+}
+
+#[tauri::command]
+pub async fn import_checksum_file(
+    state: State<'_, AppState>,
+    path: String,
+    algorithm: Option<String>,
+    rehash: Option<bool>,
+) -> Result<ChecksumImportReport, AppError> {
+    checksums::import_checksum_file(
+        &state.pool,
+        &PathBuf::from(path),
+        algorithm.as_deref(),
+        rehash.unwrap_or(false),
+    )
+    .await
+}
+
+#[tauri::command]
+pub async fn export_checksum_file(
+    state: State<'_, AppState>,
+    device_id: String,
+    prefix: String,
+    format: String,
+    path: String,
+    stale_after_days: Option<i64>,
+    reverify: Option<bool>,
+) -> Result<ChecksumExportReport, AppError> {
+    checksums::export_checksum_file(
+        &state.pool,
+        &device_id,
+        &prefix,
+        &format,
+        &PathBuf::from(path),
+        stale_after_days,
+        reverify.unwrap_or(false),
+    )
+    .await
+}
+
+#[tauri::command]
+pub async fn get_file_digests(
+    state: State<'_, AppState>,
+    hash: String,
+) -> Result<Vec<FileDigest>, AppError> {
+    db::get_file_digests(&state.pool, &hash).await
+}
+
 #[tauri::command]
 pub async fn get_dashboard_stats(state: State<'_, AppState>) -> Result<DashboardStats, AppError> {
     db::get_dashboard_stats(&state.pool).await
 }
+
+#[tauri::command]
+pub async fn get_stats_breakdown(state: State<'_, AppState>) -> Result<StatsBreakdown, AppError> {
+    db::get_stats_breakdown(&state.pool).await
+}
+
+#[tauri::command]
+pub async fn get_capacity_report(state: State<'_, AppState>) -> Result<CapacityReport, AppError> {
+    capacity::capacity_report(&state.pool).await
+}
+
+#[tauri::command]
+pub async fn get_capacity_history(
+    state: State<'_, AppState>,
+    device_id: String,
+) -> Result<Vec<CapacitySample>, AppError> {
+    db::get_capacity_samples(&state.pool, Some(&device_id), None).await
+}
+
+#[tauri::command]
+pub async fn get_wipe_report(
+    state: State<'_, AppState>,
+    device_id: String,
+    prefix: Option<String>,
+    stale_after_days: Option<i64>,
+) -> Result<WipeReport, AppError> {
+    wipe::wipe_report(&state.pool, &device_id, prefix.as_deref().unwrap_or(""), stale_after_days).await
+}
+
+#[tauri::command]
+pub async fn get_overlap_matrix(state: State<'_, AppState>) -> Result<OverlapMatrix, AppError> {
+    Ok(OverlapMatrix {
+        devices: db::get_device_content_totals(&state.pool).await?,
+        pairs: db::get_device_overlaps(&state.pool).await?,
+    })
+}
+
+#[tauri::command]
+pub async fn compare_folders(
+    state: State<'_, AppState>,
+    left_device: String,
+    left_prefix: String,
+    right_device: String,
+    right_prefix: String,
+) -> Result<CompareReport, AppError> {
+    compare::compare_folders(&state.pool, &left_device, &left_prefix, &right_device, &right_prefix).await
+}
+
+#[tauri::command]
+pub async fn find_duplicate_folders(
+    state: State<'_, AppState>,
+    min_bytes: i64,
+    similarity: Option<f64>,
+) -> Result<DuplicateFolderReport, AppError> {
+    directories::find_duplicate_folders(&state.pool, min_bytes, similarity).await
+}
+
+#[tauri::command]
+pub async fn search_files(
+    state: State<'_, AppState>,
+    query: String,
+    limit: Option<i64>,
+) -> Result<Vec<SearchResult>, AppError> {
+    search::search_files(&state.pool, &query, limit.unwrap_or(search::DEFAULT_LIMIT)).await
+}
+
+#[tauri::command]
+pub async fn run_catalog_query(
+    state: State<'_, AppState>,
+    query: String,
+    page: Option<ListQuery>,
+) -> Result<Page<FileSafety>, AppError> {
+    query::run(&state.pool, &query, &page.unwrap_or_default()).await
+}
+
+#[tauri::command]
+pub async fn run_saved_query(
+    state: State<'_, AppState>,
+    name: String,
+    page: Option<ListQuery>,
+) -> Result<Page<FileSafety>, AppError> {
+    query::run_saved(&state.pool, &name, &page.unwrap_or_default()).await
+}
+
+#[tauri::command]
+pub async fn save_query(state: State<'_, AppState>, name: String, query: String) -> Result<(), AppError> {
+    query::save(&state.pool, &name, &query).await
+}
+
+#[tauri::command]
+pub async fn get_saved_queries(state: State<'_, AppState>) -> Result<Vec<SavedQuery>, AppError> {
+    db::get_saved_queries(&state.pool).await
+}
+
+#[tauri::command]
+pub async fn delete_saved_query(state: State<'_, AppState>, name: String) -> Result<bool, AppError> {
+    db::delete_saved_query(&state.pool, &name).await
+}
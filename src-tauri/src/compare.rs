@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+
+use crate::db::{self, DbPool};
+use crate::error::AppError;
+use crate::models::{CompareCategory, CompareEntry, CompareReport, FileLocation, MovedEntry, PathConflict};
+use crate::paths;
+
+/// One side of a comparison: live files under a prefix, keyed by raw path
+/// relative to that prefix.
+struct Side {
+    by_path: HashMap<Vec<u8>, FileLocation>,
+    hashes: HashMap<String, Vec<Vec<u8>>>,
+}
+
+async fn load_side(pool: &DbPool, device_id: &str, prefix: &str) -> Result<(Side, Vec<Vec<u8>>), AppError> {
+    let prefix_raw = paths::prefix_raw(prefix);
+    let locations = db::get_locations_under(pool, device_id, &prefix_raw).await?;
+    let mut side = Side {
+        by_path: HashMap::with_capacity(locations.len()),
+        hashes: HashMap::new(),
+    };
+    // get_locations_under returns path order; keep it for stable output
+    let mut order = Vec::with_capacity(locations.len());
+    for loc in locations {
+        let relative = loc.path_raw[prefix_raw.len()..].to_vec();
+        if !is_deferred(&loc) {
+            side.hashes.entry(loc.blake3_hash.clone()).or_default().push(relative.clone());
+        }
+        order.push(relative.clone());
+        side.by_path.insert(relative, loc);
+    }
+    Ok((side, order))
+}
+
+fn is_deferred(loc: &FileLocation) -> bool {
+    loc.blake3_hash.starts_with("deferred:")
+}
+
+fn display(relative: &[u8]) -> String {
+    paths::from_raw(relative).to_string_lossy().to_string()
+}
+
+fn entry(relative: &[u8], loc: &FileLocation) -> CompareEntry {
+    CompareEntry {
+        path: display(relative),
+        blake3_hash: loc.blake3_hash.clone(),
+        file_size: loc.file_size,
+    }
+}
+
+/// Compares two folders, typically a working folder and its backup, from
+/// the catalog alone, so neither device needs to be connected. Contents are
+/// matched by hash, so a file renamed or moved on one side is reported as
+/// moved rather than as one deletion and one new file. Files only known by
+/// a deferred placeholder can't be matched by content and are listed apart.
+pub async fn compare_folders(
+    pool: &DbPool,
+    left_device: &str,
+    left_prefix: &str,
+    right_device: &str,
+    right_prefix: &str,
+) -> Result<CompareReport, AppError> {
+    let (left, left_order) = load_side(pool, left_device, left_prefix).await?;
+    let (right, right_order) = load_side(pool, right_device, right_prefix).await?;
+
+    let mut report = CompareReport {
+        left_device: left_device.to_string(),
+        left_prefix: left_prefix.trim_matches('/').to_string(),
+        right_device: right_device.to_string(),
+        right_prefix: right_prefix.trim_matches('/').to_string(),
+        ..Default::default()
+    };
+
+    for relative in &left_order {
+        let loc = &left.by_path[relative];
+        let counterpart = right.by_path.get(relative);
+        if is_deferred(loc) || counterpart.is_some_and(is_deferred) {
+            report.not_hashed.push(display(relative));
+            continue;
+        }
+        match counterpart {
+            Some(other) if other.blake3_hash == loc.blake3_hash => add(&mut report.summary.identical, loc.file_size),
+            Some(other) => {
+                add(&mut report.summary.different, loc.file_size);
+                report.different.push(PathConflict {
+                    path: display(relative),
+                    left_hash: loc.blake3_hash.clone(),
+                    left_size: loc.file_size,
+                    right_hash: other.blake3_hash.clone(),
+                    right_size: other.file_size,
+                });
+            }
+            None => match right.hashes.get(&loc.blake3_hash) {
+                Some(right_paths) => {
+                    add(&mut report.summary.moved, loc.file_size);
+                    report.moved.push(MovedEntry {
+                        left_path: display(relative),
+                        right_path: display(&right_paths[0]),
+                        blake3_hash: loc.blake3_hash.clone(),
+                        file_size: loc.file_size,
+                    });
+                }
+                None => {
+                    add(&mut report.summary.only_left, loc.file_size);
+                    report.only_left.push(entry(relative, loc));
+                }
+            },
+        }
+    }
+
+    for relative in &right_order {
+        let loc = &right.by_path[relative];
+        if left.by_path.contains_key(relative) {
+            continue; // compared above
+        }
+        if is_deferred(loc) {
+            report.not_hashed.push(display(relative));
+        } else if !left.hashes.contains_key(&loc.blake3_hash) {
+            add(&mut report.summary.only_right, loc.file_size);
+            report.only_right.push(entry(relative, loc));
+        }
+    }
+
+    report.in_sync = report.only_left.is_empty() && report.different.is_empty() && report.not_hashed.is_empty();
+    Ok(report)
+}
+
+fn add(category: &mut CompareCategory, bytes: i64) {
+    category.files += 1;
+    category.bytes += bytes;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::{add_device, temp_pool};
+
+    async fn put(pool: &DbPool, scan: i64, device: &str, path: &str, content: &str) {
+        let hash = blake3::hash(content.as_bytes()).to_hex().to_string();
+        let file_id = db::upsert_file(pool, &hash, content.len() as i64, path, "").await.unwrap();
+        db::upsert_location(pool, scan, file_id, device, path, path.as_bytes(), path, content.len() as i64, None, "full")
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn moved_files_match_by_content_and_edits_by_path() {
+        let (_dir, pool) = temp_pool().await;
+        add_device(&pool, "work", "hot").await;
+        add_device(&pool, "backup", "cold").await;
+        let scan = db::begin_scan(&pool, "work", "", b"", "full").await.unwrap();
+        for (path, content) in [
+            ("Job/same.mov", "same"),
+            ("Job/edit.mov", "v2"),
+            ("Job/old/cut.mov", "cut"),
+            ("Job/left.mov", "left"),
+            ("Jobs/outside.mov", "outside"),
+        ] {
+            put(&pool, scan, "work", path, content).await;
+        }
+        for (path, content) in [
+            ("Archive/Job/same.mov", "same"),
+            ("Archive/Job/edit.mov", "v1"),
+            ("Archive/Job/new/cut.mov", "cut"),
+            ("Archive/Job/right.mov", "right"),
+        ] {
+            put(&pool, scan, "backup", path, content).await;
+        }
+        let deferred = db::upsert_file(&pool, "deferred:3:100", 3, "raw.mov", "mov").await.unwrap();
+        db::upsert_location(&pool, scan, deferred, "work", "Job/raw.mov", b"Job/raw.mov", "raw.mov", 3, None, "deferred")
+            .await
+            .unwrap();
+
+        let report = compare_folders(&pool, "work", "Job/", "backup", "Archive/Job").await.unwrap();
+        assert_eq!(report.summary.identical.files, 1);
+        let moved: Vec<_> = report.moved.iter().map(|m| (m.left_path.as_str(), m.right_path.as_str())).collect();
+        assert_eq!(moved, [("old/cut.mov", "new/cut.mov")]);
+        let different: Vec<_> = report.different.iter().map(|d| (d.path.as_str(), d.left_size, d.right_size)).collect();
+        assert_eq!(different, [("edit.mov", 2, 2)]);
+        assert_eq!(report.only_left.iter().map(|e| e.path.as_str()).collect::<Vec<_>>(), ["left.mov"]);
+        assert_eq!(report.only_right.iter().map(|e| e.path.as_str()).collect::<Vec<_>>(), ["right.mov"]);
+        assert_eq!(report.not_hashed, ["raw.mov"]);
+        assert!(!report.in_sync);
+
+        // A backup holding everything the working folder has is in sync,
+        // whatever else it keeps
+        let report = compare_folders(&pool, "work", "Job/old", "backup", "Archive/Job").await.unwrap();
+        assert_eq!((report.summary.moved.files, report.summary.only_right.files), (1, 3));
+        assert!(report.in_sync);
+    }
+}
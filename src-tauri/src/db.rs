@@ -1,10 +1,17 @@
-use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
-use sqlx::{Pool, Sqlite};
-use std::path::Path;
+use serde::{Deserialize, Serialize};
+use sqlx::migrate::{Migrate, Migrator};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions, SqliteRow};
+use sqlx::{FromRow, Pool, QueryBuilder, Row, Sqlite, SqliteConnection};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use crate::error::AppError;
+use crate::hasher;
 use crate::models::*;
+use crate::paths;
+use crate::query::{Condition, Term};
+use crate::snapshot::SnapshotEntry;
 
 pub type DbPool = Pool<Sqlite>;
 
@@ -26,12 +33,126 @@ pub async fn init_pool(db_path: &Path) -> Result<DbPool, AppError> {
     Ok(pool)
 }
 
-pub async fn run_migrations(pool: &DbPool) -> Result<(), AppError> {
-    let sql = include_str!("../migrations/001_initial.sql");
-    sqlx::raw_sql(sql).execute(pool).await?;
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// `003_path_keys.sql`, whose rows `backfill_path_keys` completes
+const PATH_KEYS_VERSION: i64 = 3;
+
+/// Brings the catalog up to the latest schema version. Applied versions are
+/// recorded in `_sqlx_migrations`; when an existing catalog has migrations
+/// pending, a copy of it is written next to `db_path` first.
+pub async fn run_migrations(pool: &DbPool, db_path: &Path) -> Result<(), AppError> {
+    let is_existing = table_exists(pool, "storage_devices").await?;
+    if is_existing && !table_exists(pool, "_sqlx_migrations").await? {
+        baseline_legacy_catalog(pool).await?;
+    }
+
+    let applied = applied_versions(pool).await?;
+    if is_existing && MIGRATOR.iter().any(|m| !applied.contains(&m.version)) {
+        let current = applied.iter().max().copied().unwrap_or(0);
+        let backup = backup_database(pool, db_path, current).await?;
+        log::info!("backed up catalog at schema v{} to {}", current, backup.display());
+    }
+
+    MIGRATOR.run(pool).await?;
+    if !applied.contains(&PATH_KEYS_VERSION) {
+        backfill_path_keys(pool).await?;
+    }
+    Ok(())
+}
+
+/// Catalogs created before versioned migrations ran `001_initial.sql` on every
+/// start and patched later columns in place. Record the versions their schema
+/// already matches so the migrator continues from there.
+async fn baseline_legacy_catalog(pool: &DbPool) -> Result<(), AppError> {
+    let mut level = 1;
+    if column_exists(pool, "file_locations", "path_raw").await? {
+        level = 2;
+    }
+    if column_exists(pool, "file_locations", "path_key").await? {
+        level = 3;
+    }
+
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    for migration in MIGRATOR.iter().filter(|m| m.version <= level) {
+        sqlx::query(
+            "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+             VALUES (?, ?, TRUE, ?, 0)"
+        )
+        .bind(migration.version)
+        .bind(&*migration.description)
+        .bind(&*migration.checksum)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+async fn applied_versions(pool: &DbPool) -> Result<Vec<i64>, AppError> {
+    if !table_exists(pool, "_sqlx_migrations").await? {
+        return Ok(Vec::new());
+    }
+    let rows = sqlx::query_as::<_, (i64,)>("SELECT version FROM _sqlx_migrations WHERE success = TRUE")
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().map(|(v,)| v).collect())
+}
+
+async fn backup_database(pool: &DbPool, db_path: &Path, version: i64) -> Result<PathBuf, AppError> {
+    let file_name = db_path.file_name().unwrap_or_default().to_string_lossy();
+    let backup = db_path.with_file_name(format!(
+        "{}.v{}-{}.bak",
+        file_name,
+        version,
+        chrono::Local::now().format("%Y%m%d%H%M%S")
+    ));
+    sqlx::query("VACUUM INTO ?")
+        .bind(backup.to_string_lossy().as_ref())
+        .execute(pool)
+        .await?;
+    Ok(backup)
+}
+
+/// Path keys need Unicode normalization, which SQL can't do, so rows added
+/// by `003_path_keys.sql` get their key filled in here, once, right after it runs.
+async fn backfill_path_keys(pool: &DbPool) -> Result<(), AppError> {
+    let rows = sqlx::query_as::<_, (i64, String)>("SELECT id, file_path FROM file_locations WHERE path_key = ''")
+        .fetch_all(pool)
+        .await?;
+    if rows.is_empty() {
+        return Ok(());
+    }
+
+    let mut tx = pool.begin().await?;
+    for (id, file_path) in rows {
+        sqlx::query("UPDATE file_locations SET path_key = ? WHERE id = ?")
+            .bind(paths::path_key(&file_path))
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
     Ok(())
 }
 
+async fn table_exists(pool: &DbPool, table: &str) -> Result<bool, AppError> {
+    let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?")
+        .bind(table)
+        .fetch_one(pool)
+        .await?;
+    Ok(count.0 > 0)
+}
+
+async fn column_exists(pool: &DbPool, table: &str, column: &str) -> Result<bool, AppError> {
+    let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM pragma_table_info(?) WHERE name = ?")
+        .bind(table)
+        .bind(column)
+        .fetch_one(pool)
+        .await?;
+    Ok(count.0 > 0)
+}
+
 // --- Device queries ---
 
 pub async fn upsert_device(pool: &DbPool, disk: &DetectedDisk) -> Result<(), AppError> {
@@ -72,47 +193,82 @@ pub async fn set_device_type(pool: &DbPool, device_id: &str, device_type: &str)
     Ok(())
 }
 
+pub async fn set_device_site(pool: &DbPool, device_id: &str, site: Option<&str>) -> Result<(), AppError> {
+    sqlx::query("UPDATE storage_devices SET site = ? WHERE id = ?")
+        .bind(site)
+        .bind(device_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
 // --- File queries ---
 
-pub async fn upsert_file(pool: &DbPool, hash: &str, size: i64, name: &str, ext: &str) -> Result<(), AppError> {
+/// Inserts the file if its hash is new and returns its id either way.
+pub async fn upsert_file(pool: &DbPool, hash: &str, size: i64, name: &str, ext: &str) -> Result<i64, AppError> {
+    let key = hasher::hash_key(hash);
     sqlx::query(
-        "INSERT INTO files (blake3_hash, file_size, representative_name, extension)
+        "INSERT INTO files (hash, file_size, representative_name, extension)
          VALUES (?, ?, ?, ?)
-         ON CONFLICT(blake3_hash) DO NOTHING"
+         ON CONFLICT(hash) DO NOTHING"
     )
-    .bind(hash)
+    .bind(&key)
     .bind(size)
     .bind(name)
     .bind(ext)
     .execute(pool)
     .await?;
-    Ok(())
+
+    let id: (i64,) = sqlx::query_as("SELECT id FROM files WHERE hash = ?")
+        .bind(&key)
+        .fetch_one(pool)
+        .await?;
+    Ok(id.0)
 }
 
+/// Records a live location. If different content was live at the same path,
+/// that row is tombstoned by `scan_id` first so its history is kept.
+#[allow(clippy::too_many_arguments)]
 pub async fn upsert_location(
     pool: &DbPool,
-    hash: &str,
+    scan_id: i64,
+    file_id: i64,
     device_id: &str,
     file_path: &str,
+    path_raw: &[u8],
     file_name: &str,
     file_size: i64,
     modified_at: Option<&str>,
     scan_mode: &str,
 ) -> Result<(), AppError> {
     sqlx::query(
-        "INSERT INTO file_locations (blake3_hash, device_id, file_path, file_name, file_size, modified_at, last_verified, scan_mode)
-         VALUES (?, ?, ?, ?, ?, ?, datetime('now'), ?)
-         ON CONFLICT(device_id, file_path) DO UPDATE SET
-           blake3_hash = excluded.blake3_hash,
+        "UPDATE file_locations SET removed_at = datetime('now'), removed_by_scan = ?
+         WHERE device_id = ? AND path_raw = ? AND removed_at IS NULL AND file_id <> ?"
+    )
+    .bind(scan_id)
+    .bind(device_id)
+    .bind(path_raw)
+    .bind(file_id)
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "INSERT INTO file_locations (file_id, device_id, file_path, path_raw, path_key, file_name, file_size, modified_at, last_verified, scan_mode)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, datetime('now'), ?)
+         ON CONFLICT(device_id, path_raw) WHERE removed_at IS NULL DO UPDATE SET
+           file_path = excluded.file_path,
+           path_key = excluded.path_key,
            file_name = excluded.file_name,
            file_size = excluded.file_size,
            modified_at = excluded.modified_at,
            last_verified = datetime('now'),
            scan_mode = excluded.scan_mode"
     )
-    .bind(hash)
+    .bind(file_id)
     .bind(device_id)
     .bind(file_path)
+    .bind(path_raw)
+    .bind(paths::path_key(file_path))
     .bind(file_name)
     .bind(file_size)
     .bind(modified_at)
@@ -125,33 +281,208 @@ pub async fn upsert_location(
 pub async fn get_existing_location(
     pool: &DbPool,
     device_id: &str,
-    file_path: &str,
+    path_raw: &[u8],
 ) -> Result<Option<FileLocation>, AppError> {
     let loc = sqlx::query_as::<_, FileLocation>(
-        "SELECT * FROM file_locations WHERE device_id = ? AND file_path = ?"
+        "SELECT fl.*, f.blake3_hash FROM file_locations fl
+         JOIN files f ON f.id = fl.file_id
+         WHERE fl.device_id = ? AND fl.path_raw = ? AND fl.removed_at IS NULL"
     )
     .bind(device_id)
-    .bind(file_path)
+    .bind(path_raw)
+    .fetch_optional(pool)
+    .await?;
+    Ok(loc)
+}
+
+/// Looks up a location by its normalized path key, for files whose name
+/// was re-encoded (e.g. NFC to NFD) since the last scan.
+pub async fn get_location_by_key(
+    pool: &DbPool,
+    device_id: &str,
+    path_key: &str,
+) -> Result<Option<FileLocation>, AppError> {
+    let loc = sqlx::query_as::<_, FileLocation>(
+        "SELECT fl.*, f.blake3_hash FROM file_locations fl
+         JOIN files f ON f.id = fl.file_id
+         WHERE fl.device_id = ? AND fl.path_key = ? AND fl.removed_at IS NULL LIMIT 1"
+    )
+    .bind(device_id)
+    .bind(path_key)
     .fetch_optional(pool)
     .await?;
     Ok(loc)
 }
 
+/// Points an existing location at a new raw path without touching its hash
+/// or verification state.
+pub async fn rekey_location(
+    pool: &DbPool,
+    id: i64,
+    file_path: &str,
+    path_raw: &[u8],
+    file_name: &str,
+) -> Result<(), AppError> {
+    sqlx::query("UPDATE file_locations SET file_path = ?, path_raw = ?, path_key = ?, file_name = ? WHERE id = ?")
+        .bind(file_path)
+        .bind(path_raw)
+        .bind(paths::path_key(file_path))
+        .bind(file_name)
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
 pub async fn get_files_on_device(pool: &DbPool, device_id: &str) -> Result<Vec<FileLocation>, AppError> {
     let locs = sqlx::query_as::<_, FileLocation>(
-        "SELECT * FROM file_locations WHERE device_id = ? ORDER BY file_path"
+        "SELECT fl.*, f.blake3_hash FROM file_locations fl
+         JOIN files f ON f.id = fl.file_id
+         WHERE fl.device_id = ? AND fl.removed_at IS NULL ORDER BY fl.file_path"
+    )
+    .bind(device_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(locs)
+}
+
+/// Live locations on a device whose raw path starts with `prefix_raw`,
+/// in path order.
+pub async fn get_locations_under(
+    pool: &DbPool,
+    device_id: &str,
+    prefix_raw: &[u8],
+) -> Result<Vec<FileLocation>, AppError> {
+    let locs = sqlx::query_as::<_, FileLocation>(
+        "SELECT fl.*, f.blake3_hash FROM file_locations fl
+         JOIN files f ON f.id = fl.file_id
+         WHERE fl.device_id = ? AND substr(fl.path_raw, 1, ?) = ? AND fl.removed_at IS NULL
+         ORDER BY fl.path_raw"
     )
     .bind(device_id)
+    .bind(prefix_raw.len() as i64)
+    .bind(prefix_raw)
     .fetch_all(pool)
     .await?;
     Ok(locs)
 }
 
+/// Whether the location aliased `o` is a backup a card can be formatted
+/// against: on a fixed (non-removable) device marked hot or cold, with its
+/// content hashed from the file itself rather than a placeholder or a
+/// manifest's say-so.
+const BACKUP_COPY: &str = "o.scan_mode NOT IN ('deferred', 'manifest')
+     AND EXISTS (SELECT 1 FROM storage_devices bd
+                 WHERE bd.id = o.device_id AND bd.device_type IN ('hot', 'cold') AND NOT bd.is_removable)";
+
+/// Whether content has a backup copy (see `BACKUP_COPY`) on a device other
+/// than `except_device`.
+pub async fn has_backup_copy(pool: &DbPool, hash: &str, except_device: &str) -> Result<bool, AppError> {
+    let sql = format!(
+        "SELECT EXISTS (
+             SELECT 1 FROM file_locations o
+             JOIN files f ON f.id = o.file_id
+             WHERE f.hash = ? AND o.device_id <> ? AND o.removed_at IS NULL AND {BACKUP_COPY}
+         )"
+    );
+    let row: (bool,) = sqlx::query_as(&sql)
+        .bind(hasher::hash_key(hash))
+        .bind(except_device)
+        .fetch_one(pool)
+        .await?;
+    Ok(row.0)
+}
+
+/// Live files under a prefix on a device, each with how many live copies of
+/// its content exist on other devices: in total, at a different site than
+/// `site`, at a different site and verified since `verified_since`, and as
+/// backups (see `BACKUP_COPY`).
+/// A device without a site, or a `site` of None, never counts as the same site.
+pub async fn get_wipe_candidates(
+    pool: &DbPool,
+    device_id: &str,
+    prefix_raw: &[u8],
+    site: Option<&str>,
+    verified_since: Option<&str>,
+) -> Result<Vec<WipeCandidate>, AppError> {
+    let sql = format!(
+        "SELECT fl.file_path, f.blake3_hash, fl.file_size,
+                COUNT(o.id) AS other_copies,
+                COALESCE(SUM(CASE WHEN o.id IS NOT NULL AND off_site THEN 1 ELSE 0 END), 0) AS off_site_copies,
+                COALESCE(SUM(CASE WHEN o.id IS NOT NULL AND off_site
+                                   AND (? IS NULL OR o.last_verified >= ?) THEN 1 ELSE 0 END), 0) AS verified_copies,
+                COALESCE(SUM(CASE WHEN o.id IS NOT NULL AND {BACKUP_COPY} THEN 1 ELSE 0 END), 0) AS backup_copies
+         FROM file_locations fl
+         JOIN files f ON f.id = fl.file_id
+         LEFT JOIN file_locations o
+                ON o.file_id = fl.file_id AND o.device_id <> fl.device_id AND o.removed_at IS NULL
+         LEFT JOIN (SELECT id, (? IS NULL OR site IS NULL OR site <> ?) AS off_site FROM storage_devices) od
+                ON od.id = o.device_id
+         WHERE fl.device_id = ? AND substr(fl.path_raw, 1, ?) = ? AND fl.removed_at IS NULL
+         GROUP BY fl.id
+         ORDER BY fl.file_size DESC"
+    );
+    let rows = sqlx::query_as::<_, WipeCandidate>(&sql)
+        .bind(verified_since)
+        .bind(verified_since)
+        .bind(site)
+        .bind(site)
+        .bind(device_id)
+        .bind(prefix_raw.len() as i64)
+        .bind(prefix_raw)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+/// Marks a location as just verified against the file on disk.
+pub async fn mark_verified(pool: &DbPool, location_id: i64) -> Result<(), AppError> {
+    sqlx::query("UPDATE file_locations SET last_verified = datetime('now') WHERE id = ?")
+        .bind(location_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
 pub async fn get_file_locations(pool: &DbPool, hash: &str) -> Result<Vec<FileLocation>, AppError> {
     let locs = sqlx::query_as::<_, FileLocation>(
-        "SELECT * FROM file_locations WHERE blake3_hash = ?"
+        "SELECT fl.*, f.blake3_hash FROM file_locations fl
+         JOIN files f ON f.id = fl.file_id
+         WHERE f.hash = ? AND fl.removed_at IS NULL"
+    )
+    .bind(hasher::hash_key(hash))
+    .fetch_all(pool)
+    .await?;
+    Ok(locs)
+}
+
+/// Live locations of several files at once, by file then path.
+pub async fn get_locations_for_files(pool: &DbPool, file_ids: &[i64]) -> Result<Vec<FileLocation>, AppError> {
+    if file_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut qb = QueryBuilder::new(
+        "SELECT fl.*, f.blake3_hash FROM file_locations fl
+         JOIN files f ON f.id = fl.file_id
+         WHERE fl.removed_at IS NULL AND fl.file_id IN (",
+    );
+    let mut list = qb.separated(", ");
+    for id in file_ids {
+        list.push_bind(*id);
+    }
+    qb.push(") ORDER BY fl.file_id, fl.path_raw");
+    Ok(qb.build_query_as::<FileLocation>().fetch_all(pool).await?)
+}
+
+/// Every location a file has had, including tombstoned ones, newest first.
+pub async fn get_location_history(pool: &DbPool, hash: &str) -> Result<Vec<FileLocation>, AppError> {
+    let locs = sqlx::query_as::<_, FileLocation>(
+        "SELECT fl.*, f.blake3_hash FROM file_locations fl
+         JOIN files f ON f.id = fl.file_id
+         WHERE f.hash = ?
+         ORDER BY COALESCE(fl.removed_at, fl.last_verified) DESC"
     )
-    .bind(hash)
+    .bind(hasher::hash_key(hash))
     .fetch_all(pool)
     .await?;
     Ok(locs)
@@ -164,18 +495,19 @@ pub async fn get_file_safety(pool: &DbPool, hash: &str) -> Result<Option<FileSaf
                 COALESCE(SUM(CASE WHEN d.device_type = 'hot' THEN 1 ELSE 0 END), 0) as hot_copies,
                 COALESCE(SUM(CASE WHEN d.device_type = 'cold' THEN 1 ELSE 0 END), 0) as cold_copies
          FROM files f
-         JOIN file_locations fl ON f.blake3_hash = fl.blake3_hash
+         JOIN file_locations fl ON f.id = fl.file_id AND fl.removed_at IS NULL
          JOIN storage_devices d ON fl.device_id = d.id
-         WHERE f.blake3_hash = ?
-         GROUP BY f.blake3_hash"
+         WHERE f.hash = ?
+         GROUP BY f.id"
     )
-    .bind(hash)
+    .bind(hasher::hash_key(hash))
     .fetch_optional(pool)
     .await?;
 
     match row {
         Some((blake3_hash, file_size, representative_name, total_copies, hot_copies, cold_copies)) => {
             let locations = get_file_locations(pool, &blake3_hash).await?;
+            let digests = get_file_digests(pool, &blake3_hash).await?;
             let is_safe = cold_copies >= 1 && total_copies >= 2;
             Ok(Some(FileSafety {
                 blake3_hash,
@@ -186,164 +518,2195 @@ pub async fn get_file_safety(pool: &DbPool, hash: &str) -> Result<Option<FileSaf
                 cold_copies,
                 is_safe,
                 locations,
+                digests,
             }))
         }
         None => Ok(None),
     }
 }
 
-pub async fn get_unsafe_files(pool: &DbPool) -> Result<Vec<FileSafety>, AppError> {
-    let rows = sqlx::query_as::<_, (String, i64, String, i64, i64, i64)>(
-        "SELECT f.blake3_hash, f.file_size, f.representative_name,
-                COUNT(fl.id) as total_copies,
-                COALESCE(SUM(CASE WHEN d.device_type = 'hot' THEN 1 ELSE 0 END), 0) as hot_copies,
-                COALESCE(SUM(CASE WHEN d.device_type = 'cold' THEN 1 ELSE 0 END), 0) as cold_copies
-         FROM files f
-         JOIN file_locations fl ON f.blake3_hash = fl.blake3_hash
-         JOIN storage_devices d ON fl.device_id = d.id
-         GROUP BY f.blake3_hash
-         HAVING cold_copies < 1 OR total_copies < 2
-         ORDER BY f.file_size DESC"
-    )
-    .fetch_all(pool)
-    .await?;
+// --- Listings ---
 
-    let mut results = Vec::new();
-    for (blake3_hash, file_size, representative_name, total_copies, hot_copies, cold_copies) in rows {
-        let locations = get_file_locations(pool, &blake3_hash).await?;
-        results.push(FileSafety {
-            blake3_hash,
-            file_size,
-            representative_name,
-            total_copies,
-            hot_copies,
-            cold_copies,
-            is_safe: false,
-            locations,
-        });
+pub const DEFAULT_PAGE_SIZE: i64 = 100;
+pub const MAX_PAGE_SIZE: i64 = 1000;
+
+/// A field a listing can be sorted by: the name clients pass and the SQL
+/// expression behind it, which must never be NULL. Numeric fields sort
+/// largest first unless asked otherwise, text and dates ascending.
+struct SortField {
+    name: &'static str,
+    expr: &'static str,
+    numeric: bool,
+}
+
+const fn sort_field(name: &'static str, expr: &'static str, numeric: bool) -> SortField {
+    SortField { name, expr, numeric }
+}
+
+/// The sort value of the last row of a page, for resuming after it.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum CursorKey {
+    Int(i64),
+    Text(String),
+}
+
+fn push_key(qb: &mut QueryBuilder<'static, Sqlite>, key: &CursorKey) {
+    match key {
+        CursorKey::Int(v) => qb.push_bind(*v),
+        CursorKey::Text(v) => qb.push_bind(v.clone()),
+    };
+}
+
+/// Cursors are `[sort, key, row id]` as JSON; a cursor from a listing
+/// sorted differently is rejected rather than silently misread.
+fn decode_cursor(cursor: &str, sort: &SortField) -> Result<(CursorKey, i64), AppError> {
+    let invalid = || AppError::General(format!("Invalid cursor: {}", cursor));
+    let (name, key, row_id): (String, CursorKey, i64) = serde_json::from_str(cursor).map_err(|_| invalid())?;
+    match (&key, sort.numeric) {
+        (CursorKey::Int(_), true) | (CursorKey::Text(_), false) if name == sort.name => Ok((key, row_id)),
+        _ => Err(invalid()),
     }
-    Ok(results)
 }
 
-pub async fn get_waste_candidates(pool: &DbPool, threshold: i64) -> Result<Vec<WasteCandidate>, AppError> {
-    let rows = sqlx::query_as::<_, WasteCandidate>(
-        "SELECT f.blake3_hash, f.file_size, f.representative_name,
-                COUNT(fl.id) as total_copies,
-                f.file_size * (COUNT(fl.id) - 1) as wasted_bytes
-         FROM files f
-         JOIN file_locations fl ON f.blake3_hash = fl.blake3_hash
-         GROUP BY f.blake3_hash
-         HAVING total_copies > ?
-         ORDER BY wasted_bytes DESC"
+fn encode_cursor(sort: &SortField, row: &SqliteRow) -> Result<String, AppError> {
+    let key = if sort.numeric {
+        CursorKey::Int(row.try_get("sort_key")?)
+    } else {
+        CursorKey::Text(row.try_get("sort_key")?)
+    };
+    let row_id: i64 = row.try_get("row_id")?;
+    serde_json::to_string(&(sort.name, key, row_id)).map_err(|e| AppError::General(e.to_string()))
+}
+
+/// Runs one page of a listing. `push_inner` writes the listing's query,
+/// filters included, selecting `sort_key` (from the expression it's given)
+/// and a unique `row_id`; paging is keyset on those two, so pages stay
+/// cheap and stable however deep they go.
+async fn fetch_page<T>(
+    pool: &DbPool,
+    query: &ListQuery,
+    sorts: &[SortField],
+    push_inner: impl Fn(&mut QueryBuilder<'static, Sqlite>, &str),
+) -> Result<Page<T>, AppError>
+where
+    T: for<'r> FromRow<'r, SqliteRow> + Send + Unpin,
+{
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(AppError::General(format!("Page size must be between 1 and {}", MAX_PAGE_SIZE)));
+    }
+    let sort = match &query.sort {
+        None => &sorts[0],
+        Some(name) => sorts.iter().find(|s| s.name == name).ok_or_else(|| {
+            let names: Vec<&str> = sorts.iter().map(|s| s.name).collect();
+            AppError::General(format!("Unknown sort field: {} (expected one of {})", name, names.join(", ")))
+        })?,
+    };
+    let (cmp, dir) = if query.descending.unwrap_or(sort.numeric) {
+        ("<", "DESC")
+    } else {
+        (">", "ASC")
+    };
+
+    let mut count = QueryBuilder::new("SELECT COUNT(*) FROM (");
+    push_inner(&mut count, sort.expr);
+    count.push(")");
+    let total: i64 = count.build_query_scalar().fetch_one(pool).await?;
+
+    let mut page = QueryBuilder::new("SELECT * FROM (");
+    push_inner(&mut page, sort.expr);
+    page.push(")");
+    if let Some(cursor) = &query.cursor {
+        let (key, row_id) = decode_cursor(cursor, sort)?;
+        page.push(format!(" WHERE sort_key {} ", cmp));
+        push_key(&mut page, &key);
+        page.push(" OR (sort_key = ");
+        push_key(&mut page, &key);
+        page.push(format!(" AND row_id {} ", cmp));
+        page.push_bind(row_id);
+        page.push(")");
+    }
+    page.push(format!(" ORDER BY sort_key {dir}, row_id {dir} LIMIT "));
+    page.push_bind(limit);
+    let rows = page.build().fetch_all(pool).await?;
+
+    let next_cursor = match rows.last() {
+        Some(row) if rows.len() as i64 == limit => Some(encode_cursor(sort, row)?),
+        _ => None,
+    };
+    let items = rows.iter().map(T::from_row).collect::<Result<Vec<_>, _>>()?;
+    Ok(Page { items, total, next_cursor })
+}
+
+/// A LIKE pattern (with `ESCAPE '\'`) matching text containing `needle`.
+fn contains_pattern(needle: &str) -> String {
+    let escaped = needle.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+/// Appends the query's filters as `AND` clauses. A location listing
+/// filters on `fl`/`d` (the location and its device) directly; a per-file
+/// listing on `f`, with location fields matched if any live copy matches.
+fn push_filters(qb: &mut QueryBuilder<'static, Sqlite>, query: &ListQuery, per_location: bool) {
+    let (size, name) = if per_location {
+        ("fl.file_size", "fl.file_name")
+    } else {
+        ("f.file_size", "f.representative_name")
+    };
+    if let Some(ext) = &query.extension {
+        qb.push(" AND f.extension = ");
+        qb.push_bind(ext.trim_start_matches('.').to_lowercase());
+    }
+    if let Some(min) = query.min_size {
+        qb.push(format!(" AND {} >= ", size));
+        qb.push_bind(min);
+    }
+    if let Some(max) = query.max_size {
+        qb.push(format!(" AND {} <= ", size));
+        qb.push_bind(max);
+    }
+    if let Some(needle) = &query.name_contains {
+        qb.push(format!(" AND {} LIKE ", name));
+        qb.push_bind(contains_pattern(needle));
+        qb.push(" ESCAPE '\\'");
+    }
+
+    if query.modified_after.is_none() && query.modified_before.is_none() && query.device_type.is_none() {
+        return;
+    }
+    if !per_location {
+        qb.push(
+            " AND EXISTS (SELECT 1 FROM file_locations fl
+               JOIN storage_devices d ON d.id = fl.device_id
+               WHERE fl.file_id = f.id AND fl.removed_at IS NULL",
+        );
+    }
+    if let Some(after) = &query.modified_after {
+        qb.push(" AND fl.modified_at >= ");
+        qb.push_bind(after.clone());
+    }
+    if let Some(before) = &query.modified_before {
+        qb.push(" AND fl.modified_at < ");
+        qb.push_bind(before.clone());
+    }
+    if let Some(device_type) = &query.device_type {
+        qb.push(" AND d.device_type = ");
+        qb.push_bind(device_type.clone());
+    }
+    if !per_location {
+        qb.push(")");
+    }
+}
+
+const DEVICE_FILE_SORTS: &[SortField] = &[
+    sort_field("path", "fl.file_path", false),
+    sort_field("name", "fl.file_name", false),
+    sort_field("size", "fl.file_size", true),
+    sort_field("modified", "COALESCE(fl.modified_at, '')", false),
+];
+
+/// A page of the live files on a device, by path unless sorted otherwise.
+pub async fn list_device_files(pool: &DbPool, device_id: &str, query: &ListQuery) -> Result<Page<FileLocation>, AppError> {
+    let device_id = device_id.to_string();
+    fetch_page(pool, query, DEVICE_FILE_SORTS, |qb, sort| {
+        qb.push(format!(
+            "SELECT fl.*, f.blake3_hash, {} AS sort_key, fl.id AS row_id
+             FROM file_locations fl
+             JOIN files f ON f.id = fl.file_id
+             JOIN storage_devices d ON d.id = fl.device_id
+             WHERE fl.removed_at IS NULL AND fl.device_id = ",
+            sort
+        ));
+        qb.push_bind(device_id.clone());
+        push_filters(qb, query, true);
+    })
+    .await
+}
+
+#[derive(FromRow)]
+struct SafetyRow {
+    row_id: i64,
+    blake3_hash: String,
+    file_size: i64,
+    representative_name: String,
+    total_copies: i64,
+    hot_copies: i64,
+    cold_copies: i64,
+}
+
+const FILE_SORTS: &[SortField] = &[
+    sort_field("size", "f.file_size", true),
+    sort_field("name", "f.representative_name", false),
+    sort_field("copies", "COUNT(fl.id)", true),
+];
+
+/// A page of files with no cold copy or fewer than two copies, largest
+/// first unless sorted otherwise, each with its locations and digests.
+pub async fn list_unsafe_files(pool: &DbPool, query: &ListQuery) -> Result<Page<FileSafety>, AppError> {
+    list_file_safety(pool, query, |_| {}, |qb| {
+        qb.push(" AND (cold_copies < 1 OR total_copies < 2)");
+    })
+    .await
+}
+
+/// A page of the files matching a parsed catalog query, largest first
+/// unless sorted otherwise, each with its locations and digests.
+pub async fn run_catalog_query(
+    pool: &DbPool,
+    conditions: &[Condition],
+    query: &ListQuery,
+) -> Result<Page<FileSafety>, AppError> {
+    list_file_safety(
+        pool,
+        query,
+        |qb| push_conditions(qb, conditions, false),
+        |qb| push_conditions(qb, conditions, true),
     )
-    .bind(threshold)
-    .fetch_all(pool)
+    .await
+}
+
+/// Appends the catalog query conditions that filter single files
+/// (`aggregate` false) or their copy counts (`aggregate` true), as `AND`
+/// clauses. Conditions on copies hold when any live copy matches.
+fn push_conditions(qb: &mut QueryBuilder<'static, Sqlite>, conditions: &[Condition], aggregate: bool) {
+    const ANY_COPY: &str = "EXISTS (SELECT 1 FROM file_locations fl
+        JOIN storage_devices d ON d.id = fl.device_id
+        WHERE fl.file_id = f.id AND fl.removed_at IS NULL AND ";
+    for condition in conditions {
+        let is_aggregate = matches!(
+            condition.term,
+            Term::Copies(..) | Term::HotCopies(..) | Term::ColdCopies(..) | Term::Safe(_)
+        );
+        if is_aggregate != aggregate {
+            continue;
+        }
+        qb.push(if condition.negated { " AND NOT (" } else { " AND (" });
+        match &condition.term {
+            Term::Extension(exts) => {
+                qb.push("f.extension IN (");
+                let mut list = qb.separated(", ");
+                for ext in exts {
+                    list.push_bind(ext.clone());
+                }
+                qb.push(")");
+            }
+            Term::Size(cmp, bytes) => {
+                qb.push(format!("f.file_size {} ", cmp.sql()));
+                qb.push_bind(*bytes);
+            }
+            Term::Copies(cmp, n) => {
+                qb.push(format!("total_copies {} ", cmp.sql()));
+                qb.push_bind(*n);
+            }
+            Term::HotCopies(cmp, n) => {
+                qb.push(format!("hot_copies {} ", cmp.sql()));
+                qb.push_bind(*n);
+            }
+            Term::ColdCopies(cmp, n) => {
+                qb.push(format!("cold_copies {} ", cmp.sql()));
+                qb.push_bind(*n);
+            }
+            Term::Safe(safe) => {
+                qb.push(if *safe {
+                    "cold_copies >= 1 AND total_copies >= 2"
+                } else {
+                    "cold_copies < 1 OR total_copies < 2"
+                });
+            }
+            Term::Device(device) => {
+                qb.push(ANY_COPY);
+                qb.push("(d.device_type = ");
+                qb.push_bind(device.to_lowercase());
+                qb.push(" OR d.label = ");
+                qb.push_bind(device.clone());
+                qb.push(" COLLATE NOCASE OR d.id = ");
+                qb.push_bind(device.clone());
+                qb.push("))");
+            }
+            Term::Modified(cmp, prefix) => {
+                qb.push(ANY_COPY);
+                qb.push(format!("substr(fl.modified_at, 1, {}) {} ", prefix.len(), cmp.sql()));
+                qb.push_bind(prefix.clone());
+                qb.push(")");
+            }
+            Term::Name(name) => {
+                qb.push("f.representative_name LIKE ");
+                qb.push_bind(contains_pattern(name));
+                qb.push(" ESCAPE '\\' OR ");
+                qb.push(ANY_COPY);
+                qb.push("fl.file_name LIKE ");
+                qb.push_bind(contains_pattern(name));
+                qb.push(" ESCAPE '\\')");
+            }
+            Term::Path(path) => {
+                qb.push(ANY_COPY);
+                qb.push("fl.file_path LIKE ");
+                qb.push_bind(contains_pattern(path));
+                qb.push(" ESCAPE '\\')");
+            }
+            Term::HashPrefix(prefix) => {
+                qb.push("f.blake3_hash LIKE ");
+                qb.push_bind(format!("{}%", prefix));
+            }
+        }
+        qb.push(")");
+    }
+}
+
+/// A page of files with their copy counts, locations and digests.
+/// `push_where` adds `AND` conditions on `f`; `push_having` adds them on
+/// `total_copies`, `hot_copies` and `cold_copies`.
+async fn list_file_safety(
+    pool: &DbPool,
+    query: &ListQuery,
+    push_where: impl Fn(&mut QueryBuilder<'static, Sqlite>),
+    push_having: impl Fn(&mut QueryBuilder<'static, Sqlite>),
+) -> Result<Page<FileSafety>, AppError> {
+    let page: Page<SafetyRow> = fetch_page(pool, query, FILE_SORTS, |qb, sort| {
+        qb.push(format!(
+            "SELECT f.id AS row_id, f.blake3_hash, f.file_size, f.representative_name,
+                    COUNT(fl.id) AS total_copies,
+                    COALESCE(SUM(CASE WHEN d.device_type = 'hot' THEN 1 ELSE 0 END), 0) AS hot_copies,
+                    COALESCE(SUM(CASE WHEN d.device_type = 'cold' THEN 1 ELSE 0 END), 0) AS cold_copies,
+                    {} AS sort_key
+             FROM files f
+             JOIN file_locations fl ON f.id = fl.file_id AND fl.removed_at IS NULL
+             JOIN storage_devices d ON fl.device_id = d.id
+             WHERE 1 = 1",
+            sort
+        ));
+        push_filters(qb, query, false);
+        push_where(qb);
+        qb.push(" GROUP BY f.id HAVING 1 = 1");
+        push_having(qb);
+    })
     .await?;
-    Ok(rows)
+
+    // Locations and digests for the whole page in one query each
+    let ids: Vec<i64> = page.items.iter().map(|r| r.row_id).collect();
+    let mut locations: HashMap<i64, Vec<FileLocation>> = HashMap::new();
+    let mut digests: HashMap<i64, Vec<FileDigest>> = HashMap::new();
+    if !ids.is_empty() {
+        for loc in get_locations_for_files(pool, &ids).await? {
+            locations.entry(loc.file_id).or_default().push(loc);
+        }
+
+        let mut qb = QueryBuilder::new(
+            "SELECT file_id, algorithm, digest, source, verified_at FROM digests WHERE file_id IN (",
+        );
+        let mut list = qb.separated(", ");
+        for id in &ids {
+            list.push_bind(*id);
+        }
+        qb.push(") ORDER BY algorithm");
+        for row in qb.build().fetch_all(pool).await? {
+            digests.entry(row.try_get("file_id")?).or_default().push(FileDigest::from_row(&row)?);
+        }
+    }
+
+    Ok(Page {
+        items: page
+            .items
+            .into_iter()
+            .map(|r| FileSafety {
+                locations: locations.remove(&r.row_id).unwrap_or_default(),
+                digests: digests.remove(&r.row_id).unwrap_or_default(),
+                blake3_hash: r.blake3_hash,
+                file_size: r.file_size,
+                representative_name: r.representative_name,
+                total_copies: r.total_copies,
+                hot_copies: r.hot_copies,
+                cold_copies: r.cold_copies,
+                is_safe: r.cold_copies >= 1 && r.total_copies >= 2,
+            })
+            .collect(),
+        total: page.total,
+        next_cursor: page.next_cursor,
+    })
+}
+
+const WASTE_SORTS: &[SortField] = &[
+    sort_field("wasted", "f.file_size * (COUNT(fl.id) - 1)", true),
+    sort_field("size", "f.file_size", true),
+    sort_field("name", "f.representative_name", false),
+    sort_field("copies", "COUNT(fl.id)", true),
+];
+
+/// A page of files stored more than `threshold` times, most wasted bytes
+/// first unless sorted otherwise.
+pub async fn list_waste_candidates(pool: &DbPool, threshold: i64, query: &ListQuery) -> Result<Page<WasteCandidate>, AppError> {
+    fetch_page(pool, query, WASTE_SORTS, |qb, sort| {
+        qb.push(format!(
+            "SELECT f.id AS row_id, f.blake3_hash, f.file_size, f.representative_name,
+                    COUNT(fl.id) AS total_copies,
+                    f.file_size * (COUNT(fl.id) - 1) AS wasted_bytes,
+                    {} AS sort_key
+             FROM files f
+             JOIN file_locations fl ON f.id = fl.file_id AND fl.removed_at IS NULL
+             WHERE 1 = 1",
+            sort
+        ));
+        push_filters(qb, query, false);
+        qb.push(" GROUP BY f.id HAVING total_copies > ");
+        qb.push_bind(threshold);
+    })
+    .await
 }
 
 pub async fn remove_stale_locations(
     pool: &DbPool,
+    scan_id: i64,
     device_id: &str,
-    path_prefix: &str,
-    seen_paths: &[String],
+    prefix_raw: &[u8],
+    seen_paths: &std::collections::HashSet<Vec<u8>>,
 ) -> Result<u64, AppError> {
-    // Delete locations under the scanned prefix that weren't seen
-    // Use a prefix match with LIKE (escape % and _ in prefix)
-    let prefix_pattern = format!(
-        "{}%",
-        path_prefix.replace('%', "\\%").replace('_', "\\_")
-    );
+    // Tombstone live locations under the scanned prefix that weren't seen.
+    // The prefix is compared on the raw path bytes, so no LIKE escaping is needed,
+    // and only up to a `/` so that `Photos` leaves `Photos 2` alone. The prefix
+    // itself matches too, for a scan of a single file.
+    // Seen paths are compared by raw bytes: a re-encoded name was rekeyed to its
+    // new spelling when seen, and two names differing only in case are two files.
+    let inside = paths::inside_raw(prefix_raw);
+    let inside_len = inside.len() as i64;
 
     if seen_paths.is_empty() {
         // Nothing seen = everything under prefix is gone
         let res = sqlx::query(
-            "DELETE FROM file_locations WHERE device_id = ? AND file_path LIKE ? ESCAPE '\\'"
+            "UPDATE file_locations SET removed_at = datetime('now'), removed_by_scan = ?
+             WHERE device_id = ? AND (path_raw = ? OR substr(path_raw, 1, ?) = ?) AND removed_at IS NULL"
         )
+        .bind(scan_id)
         .bind(device_id)
-        .bind(&prefix_pattern)
+        .bind(prefix_raw)
+        .bind(inside_len)
+        .bind(&inside)
         .execute(pool)
         .await?;
         return Ok(res.rows_affected());
     }
 
-    // Build a temp table approach: insert seen paths, delete those not in it
-    // For simplicity, batch delete with NOT IN (chunked to avoid SQLite limits)
-    let mut total_deleted: u64 = 0;
-    // Get all existing locations under prefix
-    let existing = sqlx::query_as::<_, (i64, String)>(
-        "SELECT id, file_path FROM file_locations WHERE device_id = ? AND file_path LIKE ? ESCAPE '\\'"
+    // Batch tombstone by id (chunked to avoid SQLite limits)
+    let mut total_removed: u64 = 0;
+    // Get all live locations under prefix
+    let existing = sqlx::query_as::<_, (i64, Vec<u8>)>(
+        "SELECT id, path_raw FROM file_locations
+         WHERE device_id = ? AND (path_raw = ? OR substr(path_raw, 1, ?) = ?) AND removed_at IS NULL"
     )
     .bind(device_id)
-    .bind(&prefix_pattern)
+    .bind(prefix_raw)
+    .bind(inside_len)
+    .bind(&inside)
     .fetch_all(pool)
     .await?;
 
-    let seen_set: std::collections::HashSet<&str> = seen_paths.iter().map(|s| s.as_str()).collect();
     let stale_ids: Vec<i64> = existing
         .iter()
-        .filter(|(_, path)| !seen_set.contains(path.as_str()))
+        .filter(|(_, path_raw)| !seen_paths.contains(path_raw))
         .map(|(id, _)| *id)
         .collect();
 
     for chunk in stale_ids.chunks(500) {
         let placeholders: String = chunk.iter().map(|_| "?").collect::<Vec<_>>().join(",");
-        let sql = format!("DELETE FROM file_locations WHERE id IN ({})", placeholders);
-        let mut query = sqlx::query(&sql);
+        let sql = format!(
+            "UPDATE file_locations SET removed_at = datetime('now'), removed_by_scan = ? WHERE id IN ({})",
+            placeholders
+        );
+        let mut query = sqlx::query(&sql).bind(scan_id);
         for id in chunk {
             query = query.bind(id);
         }
         let res = query.execute(pool).await?;
-        total_deleted += res.rows_affected();
+        total_removed += res.rows_affected();
     }
 
-    Ok(total_deleted)
+    Ok(total_removed)
 }
 
-pub async fn cleanup_orphaned_files(pool: &DbPool) -> Result<u64, AppError> {
-    let res = sqlx::query(
-        "DELETE FROM files WHERE blake3_hash NOT IN (SELECT DISTINCT blake3_hash FROM file_locations)"
+/// Locations whose raw path isn't valid UTF-8, so their display path
+/// had bytes replaced with U+FFFD.
+pub async fn get_non_utf8_files(pool: &DbPool, device_id: Option<&str>) -> Result<Vec<NonUtf8File>, AppError> {
+    let locs = sqlx::query_as::<_, FileLocation>(
+        "SELECT fl.*, f.blake3_hash FROM file_locations fl
+         JOIN files f ON f.id = fl.file_id
+         WHERE CAST(fl.file_path AS BLOB) <> fl.path_raw AND fl.removed_at IS NULL
+           AND (?1 IS NULL OR fl.device_id = ?1)
+         ORDER BY fl.device_id, fl.file_path"
     )
-    .execute(pool)
+    .bind(device_id)
+    .fetch_all(pool)
     .await?;
-    Ok(res.rows_affected())
+    Ok(locs
+        .into_iter()
+        .map(|location| NonUtf8File {
+            escaped_path: paths::escape_raw(&location.path_raw),
+            location,
+        })
+        .collect())
 }
 
-pub async fn get_dashboard_stats(pool: &DbPool) -> Result<DashboardStats, AppError> {
-    let total_files: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM files")
-        .fetch_one(pool)
+/// Groups of locations whose paths differ only by Unicode normalization or
+/// case. By default a group is per device (names that would clash when copied
+/// to a case-insensitive filesystem); with `across_devices` the same relative
+/// path spelled differently on different devices is grouped too.
+pub async fn get_name_conflicts(
+    pool: &DbPool,
+    device_id: Option<&str>,
+    across_devices: bool,
+) -> Result<Vec<NameConflict>, AppError> {
+    let group_cols = if across_devices { "path_key" } else { "device_id, path_key" };
+    let sql = format!(
+        "SELECT fl.*, f.blake3_hash FROM file_locations fl
+         JOIN files f ON f.id = fl.file_id
+         WHERE fl.removed_at IS NULL AND ({group_cols}) IN (
+             SELECT {group_cols} FROM file_locations
+             WHERE removed_at IS NULL
+             GROUP BY {group_cols}
+             HAVING COUNT(DISTINCT file_path) > 1 AND (?1 IS NULL OR SUM(device_id = ?1) > 0)
+         )
+         ORDER BY fl.path_key, fl.device_id, fl.file_path"
+    );
+    let locs = sqlx::query_as::<_, FileLocation>(&sql)
+        .bind(device_id)
+        .fetch_all(pool)
         .await?;
-    let total_locations: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM file_locations")
-        .fetch_one(pool)
+
+    let mut conflicts: Vec<NameConflict> = Vec::new();
+    for loc in locs {
+        let same_group = conflicts.last().is_some_and(|c| {
+            c.path_key == loc.path_key && (across_devices || c.variants[0].device_id == loc.device_id)
+        });
+        if same_group {
+            conflicts.last_mut().unwrap().variants.push(loc);
+        } else {
+            conflicts.push(NameConflict {
+                path_key: loc.path_key.clone(),
+                variants: vec![loc],
+            });
+        }
+    }
+    Ok(conflicts)
+}
+
+/// Deletes `deferred:` placeholders with no live location left, typically
+/// because the file has since been hashed and its location now points at the
+/// real content. The placeholder's tombstones only say the path was once
+/// unhashed, so they go with it. Real hashes are kept so files that vanished
+/// show up in `get_lost_files`.
+pub async fn cleanup_orphaned_files(pool: &DbPool) -> Result<u64, AppError> {
+    const ORPHANED: &str = "SELECT f.id FROM files f
+         WHERE substr(f.hash, 1, 9) = CAST('deferred:' AS BLOB)
+           AND NOT EXISTS (
+               SELECT 1 FROM file_locations fl WHERE fl.file_id = f.id AND fl.removed_at IS NULL
+           )";
+    let mut tx = pool.begin().await?;
+    sqlx::query(&format!("DELETE FROM digests WHERE file_id IN ({ORPHANED})"))
+        .execute(&mut *tx)
         .await?;
-    let total_devices: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM storage_devices")
-        .fetch_one(pool)
+    sqlx::query(&format!("DELETE FROM file_locations WHERE file_id IN ({ORPHANED})"))
+        .execute(&mut *tx)
         .await?;
-    let total_size: (i64,) = sqlx::query_as("SELECT COALESCE(SUM(file_size), 0) FROM files")
-        .fetch_one(pool)
+    let res = sqlx::query(&format!("DELETE FROM files WHERE id IN ({ORPHANED})"))
+        .execute(&mut *tx)
         .await?;
+    tx.commit().await?;
+    Ok(res.rows_affected())
+}
 
-    // Count unsafe files: those without cold_copies >= 1 AND total_copies >= 2
-    let unsafe_files: (i64,) = sqlx::query_as(
-        "SELECT COUNT(*) FROM (
-            SELECT f.blake3_hash,
-                   COUNT(fl.id) as total_copies,
-                   COALESCE(SUM(CASE WHEN d.device_type = 'cold' THEN 1 ELSE 0 END), 0) as cold_copies
-            FROM files f
-            JOIN file_locations fl ON f.blake3_hash = fl.blake3_hash
-            JOIN storage_devices d ON fl.device_id = d.id
-            GROUP BY f.blake3_hash
-            HAVING cold_copies < 1 OR total_copies < 2
-        )"
+/// Files that were catalogued at some point but have no live location left,
+/// with the most recent place each was seen. `deferred:` placeholders are
+/// never lost: their content was either hashed since or never known.
+pub async fn get_lost_files(pool: &DbPool) -> Result<Vec<LostFile>, AppError> {
+    let rows = sqlx::query_as::<_, LostFile>(
+        "SELECT f.blake3_hash, f.file_size, f.representative_name,
+                fl.device_id as last_device_id, fl.file_path as last_file_path,
+                fl.removed_at, fl.removed_by_scan
+         FROM files f
+         LEFT JOIN file_locations fl ON fl.id = (
+             SELECT id FROM file_locations WHERE file_id = f.id
+             ORDER BY removed_at DESC LIMIT 1
+         )
+         WHERE substr(f.hash, 1, 9) <> CAST('deferred:' AS BLOB)
+           AND NOT EXISTS (
+               SELECT 1 FROM file_locations live WHERE live.file_id = f.id AND live.removed_at IS NULL
+           )
+         ORDER BY fl.removed_at DESC, f.file_size DESC"
     )
-    .fetch_one(pool)
+    .fetch_all(pool)
     .await?;
+    Ok(rows)
+}
 
-    Ok(DashboardStats {
-        total_files: total_files.0,
-        total_locations: total_locations.0,
-        unsafe_files: unsafe_files.0,
-        total_devices: total_devices.0,
-        total_size_bytes: total_size.0,
-    })
+// --- Digests ---
+
+pub async fn get_digest(pool: &DbPool, file_id: i64, algorithm: &str) -> Result<Option<String>, AppError> {
+    let row = sqlx::query_as::<_, (String,)>("SELECT digest FROM digests WHERE file_id = ? AND algorithm = ?")
+        .bind(file_id)
+        .bind(algorithm)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.map(|r| r.0))
+}
+
+/// Whether any of `algorithms` has no stored digest for the file yet.
+pub async fn is_missing_digests(pool: &DbPool, file_id: i64, algorithms: &[String]) -> Result<bool, AppError> {
+    if algorithms.is_empty() {
+        return Ok(false);
+    }
+    let placeholders = algorithms.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+    let sql = format!(
+        "SELECT COUNT(*) FROM digests WHERE file_id = ? AND algorithm IN ({})",
+        placeholders
+    );
+    let mut query = sqlx::query_as::<_, (i64,)>(&sql).bind(file_id);
+    for algorithm in algorithms {
+        query = query.bind(algorithm);
+    }
+    let count = query.fetch_one(pool).await?;
+    Ok((count.0 as usize) < algorithms.len())
+}
+
+/// Stores a digest for a file. `verified` marks it as checked against the
+/// file's contents now; otherwise an earlier verification is kept.
+pub async fn upsert_digest(
+    pool: &DbPool,
+    file_id: i64,
+    algorithm: &str,
+    digest: &str,
+    source: &str,
+    verified: bool,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT INTO digests (file_id, algorithm, digest, source, verified_at)
+         VALUES (?1, ?2, ?3, ?4, CASE WHEN ?5 THEN datetime('now') END)
+         ON CONFLICT(file_id, algorithm) DO UPDATE SET
+           digest = excluded.digest,
+           source = excluded.source,
+           verified_at = COALESCE(excluded.verified_at, verified_at)"
+    )
+    .bind(file_id)
+    .bind(algorithm)
+    .bind(digest)
+    .bind(source)
+    .bind(verified)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_file_digests(pool: &DbPool, hash: &str) -> Result<Vec<FileDigest>, AppError> {
+    let rows = sqlx::query_as::<_, FileDigest>(
+        "SELECT dg.algorithm, dg.digest, dg.source, dg.verified_at FROM digests dg
+         JOIN files f ON f.id = dg.file_id
+         WHERE f.hash = ?
+         ORDER BY dg.algorithm"
+    )
+    .bind(hasher::hash_key(hash))
+    .fetch_all(pool)
+    .await?;
+    Ok(rows)
+}
+
+// --- Catalog exchange ---
+
+pub async fn get_catalog_devices(conn: &mut SqliteConnection) -> Result<Vec<StorageDevice>, AppError> {
+    let devices = sqlx::query_as::<_, StorageDevice>("SELECT * FROM storage_devices ORDER BY last_seen DESC")
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(devices)
+}
+
+pub async fn get_catalog_files(conn: &mut SqliteConnection, after_id: i64, limit: i64) -> Result<Vec<CatalogFile>, AppError> {
+    let rows = sqlx::query_as::<_, CatalogFile>(
+        "SELECT id, blake3_hash, file_size, representative_name, extension FROM files
+         WHERE id > ? ORDER BY id LIMIT ?"
+    )
+    .bind(after_id)
+    .bind(limit)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(rows)
+}
+
+pub async fn get_catalog_locations(conn: &mut SqliteConnection, after_id: i64, limit: i64) -> Result<Vec<CatalogLocation>, AppError> {
+    let rows = sqlx::query_as::<_, CatalogLocation>(
+        "SELECT fl.id, f.blake3_hash, fl.device_id, fl.file_path, lower(hex(fl.path_raw)) as path_raw,
+                fl.file_name, fl.file_size, fl.modified_at, fl.last_verified, fl.scan_mode, fl.removed_at
+         FROM file_locations fl
+         JOIN files f ON f.id = fl.file_id
+         WHERE fl.id > ? ORDER BY fl.id LIMIT ?"
+    )
+    .bind(after_id)
+    .bind(limit)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(rows)
+}
+
+pub async fn get_catalog_digests(conn: &mut SqliteConnection, after_id: i64, limit: i64) -> Result<Vec<CatalogDigest>, AppError> {
+    let rows = sqlx::query_as::<_, CatalogDigest>(
+        "SELECT dg.rowid as id, f.blake3_hash, dg.algorithm, dg.digest, dg.source, dg.verified_at
+         FROM digests dg
+         JOIN files f ON f.id = dg.file_id
+         WHERE dg.rowid > ? ORDER BY dg.rowid LIMIT ?"
+    )
+    .bind(after_id)
+    .bind(limit)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(rows)
+}
+
+/// Merges an imported device. Devices are keyed by volume UUID; label, mount
+/// point and capacity come from whichever catalog saw the device last, and a
+/// local hot/cold classification is never overwritten.
+pub async fn import_device(conn: &mut SqliteConnection, device: &StorageDevice) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT INTO storage_devices (id, label, mount_point, device_type, total_bytes, available_bytes, is_removable, first_seen, last_seen, site)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+         ON CONFLICT(id) DO UPDATE SET
+           site = COALESCE(site, excluded.site),
+           label = CASE WHEN excluded.last_seen > last_seen THEN excluded.label ELSE label END,
+           mount_point = CASE WHEN excluded.last_seen > last_seen THEN excluded.mount_point ELSE mount_point END,
+           total_bytes = CASE WHEN excluded.last_seen > last_seen THEN excluded.total_bytes ELSE total_bytes END,
+           available_bytes = CASE WHEN excluded.last_seen > last_seen THEN excluded.available_bytes ELSE available_bytes END,
+           device_type = CASE WHEN device_type = 'unknown' THEN excluded.device_type ELSE device_type END,
+           first_seen = MIN(first_seen, excluded.first_seen),
+           last_seen = MAX(last_seen, excluded.last_seen)"
+    )
+    .bind(&device.id)
+    .bind(&device.label)
+    .bind(&device.mount_point)
+    .bind(&device.device_type)
+    .bind(device.total_bytes)
+    .bind(device.available_bytes)
+    .bind(device.is_removable)
+    .bind(&device.first_seen)
+    .bind(&device.last_seen)
+    .bind(&device.site)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Same as `upsert_file`, on the import transaction.
+pub async fn import_file(conn: &mut SqliteConnection, file: &CatalogFile) -> Result<i64, AppError> {
+    let key = hasher::hash_key(&file.blake3_hash);
+    sqlx::query(
+        "INSERT INTO files (hash, file_size, representative_name, extension)
+         VALUES (?, ?, ?, ?)
+         ON CONFLICT(hash) DO NOTHING"
+    )
+    .bind(&key)
+    .bind(file.file_size)
+    .bind(&file.representative_name)
+    .bind(&file.extension)
+    .execute(&mut *conn)
+    .await?;
+
+    let id: (i64,) = sqlx::query_as("SELECT id FROM files WHERE hash = ?")
+        .bind(&key)
+        .fetch_one(&mut *conn)
+        .await?;
+    Ok(id.0)
+}
+
+pub async fn get_file_id(conn: &mut SqliteConnection, hash: &str) -> Result<Option<i64>, AppError> {
+    let row = sqlx::query_as::<_, (i64,)>("SELECT id FROM files WHERE hash = ?")
+        .bind(hasher::hash_key(hash))
+        .fetch_optional(&mut *conn)
+        .await?;
+    Ok(row.map(|r| r.0))
+}
+
+/// Merges an imported digest. A digest this catalog already has for the
+/// file wins; only a more recent verification of the same value is taken over.
+pub async fn import_digest(conn: &mut SqliteConnection, file_id: i64, digest: &CatalogDigest) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT INTO digests (file_id, algorithm, digest, source, verified_at)
+         VALUES (?, ?, ?, ?, ?)
+         ON CONFLICT(file_id, algorithm) DO UPDATE SET
+           verified_at = COALESCE(MAX(verified_at, excluded.verified_at), verified_at, excluded.verified_at)
+         WHERE digest = excluded.digest"
+    )
+    .bind(file_id)
+    .bind(&digest.algorithm)
+    .bind(&digest.digest)
+    .bind(&digest.source)
+    .bind(&digest.verified_at)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LocationMerge {
+    Added,
+    Updated,
+    Skipped,
+}
+
+/// Merges an imported location into the live row at the same device and raw
+/// path. Whichever side verified the path most recently wins: a fresher live
+/// row replaces the local one (tombstoning it if the content differs), and a
+/// tombstone newer than the local verification retires the local row.
+/// Older tombstones are kept as history.
+pub async fn import_location(
+    conn: &mut SqliteConnection,
+    file_id: i64,
+    path_raw: &[u8],
+    loc: &CatalogLocation,
+) -> Result<LocationMerge, AppError> {
+    let live = sqlx::query_as::<_, (i64, i64, String)>(
+        "SELECT id, file_id, last_verified FROM file_locations
+         WHERE device_id = ? AND path_raw = ? AND removed_at IS NULL"
+    )
+    .bind(&loc.device_id)
+    .bind(path_raw)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    match (&loc.removed_at, live) {
+        (None, None) => {
+            // A local tombstone newer than the import means the file is known gone
+            let newer_removal: (i64,) = sqlx::query_as(
+                "SELECT COUNT(*) FROM file_locations
+                 WHERE device_id = ? AND path_raw = ? AND removed_at >= ?"
+            )
+            .bind(&loc.device_id)
+            .bind(path_raw)
+            .bind(&loc.last_verified)
+            .fetch_one(&mut *conn)
+            .await?;
+            if newer_removal.0 > 0 {
+                return Ok(LocationMerge::Skipped);
+            }
+            insert_imported_location(conn, file_id, path_raw, loc).await?;
+            Ok(LocationMerge::Added)
+        }
+        (None, Some((id, local_file_id, last_verified))) if loc.last_verified > last_verified => {
+            if local_file_id == file_id {
+                sqlx::query(
+                    "UPDATE file_locations SET file_path = ?, path_key = ?, file_name = ?, file_size = ?,
+                            modified_at = ?, last_verified = ?, scan_mode = ?
+                     WHERE id = ?"
+                )
+                .bind(&loc.file_path)
+                .bind(paths::path_key(&loc.file_path))
+                .bind(&loc.file_name)
+                .bind(loc.file_size)
+                .bind(&loc.modified_at)
+                .bind(&loc.last_verified)
+                .bind(&loc.scan_mode)
+                .bind(id)
+                .execute(&mut *conn)
+                .await?;
+            } else {
+                tombstone_location(conn, id, &loc.last_verified).await?;
+                insert_imported_location(conn, file_id, path_raw, loc).await?;
+            }
+            Ok(LocationMerge::Updated)
+        }
+        (None, Some(_)) => Ok(LocationMerge::Skipped),
+        (Some(removed_at), Some((id, local_file_id, last_verified)))
+            if local_file_id == file_id && *removed_at > last_verified =>
+        {
+            tombstone_location(conn, id, removed_at).await?;
+            Ok(LocationMerge::Updated)
+        }
+        (Some(removed_at), _) => {
+            let known: (i64,) = sqlx::query_as(
+                "SELECT COUNT(*) FROM file_locations
+                 WHERE device_id = ? AND path_raw = ? AND file_id = ? AND removed_at = ?"
+            )
+            .bind(&loc.device_id)
+            .bind(path_raw)
+            .bind(file_id)
+            .bind(removed_at)
+            .fetch_one(&mut *conn)
+            .await?;
+            if known.0 > 0 {
+                return Ok(LocationMerge::Skipped);
+            }
+            insert_imported_location(conn, file_id, path_raw, loc).await?;
+            Ok(LocationMerge::Added)
+        }
+    }
+}
+
+async fn insert_imported_location(
+    conn: &mut SqliteConnection,
+    file_id: i64,
+    path_raw: &[u8],
+    loc: &CatalogLocation,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT INTO file_locations (file_id, device_id, file_path, path_raw, path_key, file_name, file_size,
+                                     modified_at, last_verified, scan_mode, removed_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+    )
+    .bind(file_id)
+    .bind(&loc.device_id)
+    .bind(&loc.file_path)
+    .bind(path_raw)
+    .bind(paths::path_key(&loc.file_path))
+    .bind(&loc.file_name)
+    .bind(loc.file_size)
+    .bind(&loc.modified_at)
+    .bind(&loc.last_verified)
+    .bind(&loc.scan_mode)
+    .bind(&loc.removed_at)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+async fn tombstone_location(conn: &mut SqliteConnection, id: i64, removed_at: &str) -> Result<(), AppError> {
+    sqlx::query("UPDATE file_locations SET removed_at = ?, removed_by_scan = NULL WHERE id = ?")
+        .bind(removed_at)
+        .bind(id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+// --- Scan records ---
+
+pub async fn begin_scan(
+    pool: &DbPool,
+    device_id: &str,
+    scan_prefix: &str,
+    prefix_raw: &[u8],
+    scan_mode: &str,
+) -> Result<i64, AppError> {
+    let res = sqlx::query(
+        "INSERT INTO scans (device_id, scan_prefix, prefix_raw, scan_mode) VALUES (?, ?, ?, ?)"
+    )
+    .bind(device_id)
+    .bind(scan_prefix)
+    .bind(prefix_raw)
+    .bind(scan_mode)
+    .execute(pool)
+    .await?;
+    Ok(res.last_insert_rowid())
+}
+
+pub async fn finish_scan(pool: &DbPool, scan_id: i64, status: &str, summary: &ScanSummary) -> Result<(), AppError> {
+    sqlx::query(
+        "UPDATE scans SET status = ?, finished_at = datetime('now'),
+                scanned = ?, hashed = ?, added = ?, removed = ?
+         WHERE id = ?"
+    )
+    .bind(status)
+    .bind(summary.scanned as i64)
+    .bind(summary.hashed as i64)
+    .bind(summary.added as i64)
+    .bind(summary.removed as i64)
+    .bind(scan_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// --- Snapshots ---
+
+/// Device id, raw prefix and live contents under a scan's prefix, sorted by path.
+pub async fn get_live_snapshot_entries(pool: &DbPool, scan_id: i64) -> Result<(String, Vec<u8>, Vec<SnapshotEntry>), AppError> {
+    let (device_id, prefix_raw): (String, Vec<u8>) =
+        sqlx::query_as("SELECT device_id, prefix_raw FROM scans WHERE id = ?")
+            .bind(scan_id)
+            .fetch_one(pool)
+            .await?;
+
+    let inside = paths::inside_raw(&prefix_raw);
+    let rows = sqlx::query_as::<_, (Vec<u8>, Vec<u8>, i64)>(
+        "SELECT fl.path_raw, f.hash, fl.file_size FROM file_locations fl
+         JOIN files f ON f.id = fl.file_id
+         WHERE fl.device_id = ? AND (fl.path_raw = ? OR substr(fl.path_raw, 1, ?) = ?) AND fl.removed_at IS NULL
+         ORDER BY fl.path_raw"
+    )
+    .bind(&device_id)
+    .bind(&prefix_raw)
+    .bind(inside.len() as i64)
+    .bind(&inside)
+    .fetch_all(pool)
+    .await?;
+
+    let entries = rows
+        .into_iter()
+        .map(|(path_raw, hash, size)| SnapshotEntry { path_raw, hash, size })
+        .collect();
+    Ok((device_id, prefix_raw, entries))
+}
+
+/// The latest snapshot of the same device and scan prefix taken before `scan_id`.
+pub async fn get_previous_snapshot_id(
+    pool: &DbPool,
+    device_id: &str,
+    prefix_raw: &[u8],
+    scan_id: i64,
+) -> Result<Option<i64>, AppError> {
+    let row = sqlx::query_as::<_, (i64,)>(
+        "SELECT s.scan_id FROM scan_snapshots s
+         JOIN scans sc ON sc.id = s.scan_id
+         WHERE s.device_id = ? AND sc.prefix_raw = ? AND s.scan_id < ?
+         ORDER BY s.scan_id DESC LIMIT 1"
+    )
+    .bind(device_id)
+    .bind(prefix_raw)
+    .bind(scan_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|r| r.0))
+}
+
+/// Stores a snapshot; `data` is a delta against `base_scan_id`'s when set.
+pub async fn insert_snapshot(
+    pool: &DbPool,
+    scan_id: i64,
+    device_id: &str,
+    entry_count: i64,
+    total_bytes: i64,
+    base_scan_id: Option<i64>,
+    data: &[u8],
+) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT INTO scan_snapshots (scan_id, device_id, entry_count, total_bytes, base_scan_id, data)
+         VALUES (?, ?, ?, ?, ?, ?)"
+    )
+    .bind(scan_id)
+    .bind(device_id)
+    .bind(entry_count)
+    .bind(total_bytes)
+    .bind(base_scan_id)
+    .bind(data)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_snapshots(pool: &DbPool, device_id: &str) -> Result<Vec<SnapshotInfo>, AppError> {
+    let rows = sqlx::query_as::<_, SnapshotInfo>(
+        "SELECT s.scan_id, s.device_id, sc.scan_prefix, sc.scan_mode, s.entry_count, s.total_bytes, s.created_at
+         FROM scan_snapshots s
+         JOIN scans sc ON sc.id = s.scan_id
+         WHERE s.device_id = ?
+         ORDER BY s.scan_id DESC"
+    )
+    .bind(device_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows)
+}
+
+/// Device id, raw scan prefix, delta base and encoded data of a snapshot.
+pub async fn get_snapshot_data(
+    pool: &DbPool,
+    scan_id: i64,
+) -> Result<Option<(String, Vec<u8>, Option<i64>, Vec<u8>)>, AppError> {
+    let row = sqlx::query_as::<_, (String, Vec<u8>, Option<i64>, Vec<u8>)>(
+        "SELECT s.device_id, sc.prefix_raw, s.base_scan_id, s.data
+         FROM scan_snapshots s
+         JOIN scans sc ON sc.id = s.scan_id
+         WHERE s.scan_id = ?"
+    )
+    .bind(scan_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row)
+}
+
+// --- Saved queries ---
+
+pub async fn save_query(pool: &DbPool, name: &str, query: &str) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT INTO saved_queries (name, query) VALUES (?, ?)
+         ON CONFLICT(name) DO UPDATE SET query = excluded.query, updated_at = datetime('now')"
+    )
+    .bind(name)
+    .bind(query)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_saved_queries(pool: &DbPool) -> Result<Vec<SavedQuery>, AppError> {
+    let rows = sqlx::query_as::<_, SavedQuery>("SELECT * FROM saved_queries ORDER BY name")
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn get_saved_query(pool: &DbPool, name: &str) -> Result<Option<SavedQuery>, AppError> {
+    let row = sqlx::query_as::<_, SavedQuery>("SELECT * FROM saved_queries WHERE name = ?")
+        .bind(name)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row)
+}
+
+/// Whether there was a query by that name.
+pub async fn delete_saved_query(pool: &DbPool, name: &str) -> Result<bool, AppError> {
+    let result = sqlx::query("DELETE FROM saved_queries WHERE name = ?")
+        .bind(name)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+// --- Filename search ---
+
+/// Files with a live location matching an FTS5 query on name or path, best
+/// match first: (id, blake3_hash, file_size, representative_name).
+pub async fn search_files(pool: &DbPool, fts_query: &str, limit: i64) -> Result<Vec<(i64, String, i64, String)>, AppError> {
+    let rows = sqlx::query_as::<_, (i64, String, i64, String)>(
+        "SELECT f.id, f.blake3_hash, f.file_size, f.representative_name
+         FROM (
+            SELECT fl.file_id, MIN(s.rank) AS best
+            FROM location_search s
+            JOIN file_locations fl ON fl.id = s.rowid
+            WHERE location_search MATCH ? AND fl.removed_at IS NULL
+            GROUP BY fl.file_id
+            ORDER BY best
+            LIMIT ?
+         ) m
+         JOIN files f ON f.id = m.file_id
+         ORDER BY m.best"
+    )
+    .bind(fts_query)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows)
+}
+
+// --- Directories ---
+
+/// Live locations on every device with their storage-form hash and copy counts.
+pub async fn get_live_entries(pool: &DbPool) -> Result<Vec<LiveEntry>, AppError> {
+    let rows = sqlx::query_as::<_, LiveEntry>(
+        "WITH copies AS (
+            SELECT fl.file_id, COUNT(*) AS total_copies,
+                   COALESCE(SUM(d.device_type = 'cold'), 0) AS cold_copies
+            FROM file_locations fl
+            JOIN storage_devices d ON d.id = fl.device_id
+            WHERE fl.removed_at IS NULL
+            GROUP BY fl.file_id
+         )
+         SELECT fl.device_id, fl.path_raw, fl.file_id, f.hash, fl.file_size,
+                c.total_copies, c.cold_copies,
+                COUNT(*) OVER (PARTITION BY fl.device_id, fl.file_id) AS device_copies
+         FROM file_locations fl
+         JOIN files f ON f.id = fl.file_id
+         JOIN copies c ON c.file_id = fl.file_id
+         WHERE fl.removed_at IS NULL"
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows)
+}
+
+/// Live entries on a device inside `folder_raw` (empty for the root): every
+/// one below it, or with `direct_only` just the folder's own files.
+pub async fn get_live_entries_in(
+    pool: &DbPool,
+    device_id: &str,
+    folder_raw: &[u8],
+    direct_only: bool,
+) -> Result<Vec<LiveEntry>, AppError> {
+    // A range rather than substr() so the live-path index finds the folder;
+    // '0' is the byte after '/'
+    let inside = paths::inside_raw(folder_raw);
+    let upper = inside.split_last().map(|(_, head)| [head, b"0"].concat());
+    let rows = sqlx::query_as::<_, LiveEntry>(
+        "WITH mine AS (
+            SELECT id, file_id FROM file_locations
+            WHERE device_id = ? AND removed_at IS NULL
+              AND path_raw >= ? AND (? IS NULL OR path_raw < ?)
+              AND (? = 0 OR instr(substr(path_raw, ? + 1), x'2f') = 0)
+         ),
+         copies AS (
+            SELECT fl.file_id, COUNT(*) AS total_copies,
+                   COALESCE(SUM(d.device_type = 'cold'), 0) AS cold_copies,
+                   COALESCE(SUM(fl.device_id = ?), 0) AS device_copies
+            FROM file_locations fl
+            JOIN storage_devices d ON d.id = fl.device_id
+            WHERE fl.removed_at IS NULL AND fl.file_id IN (SELECT file_id FROM mine)
+            GROUP BY fl.file_id
+         )
+         SELECT fl.device_id, fl.path_raw, fl.file_id, f.hash, fl.file_size,
+                c.total_copies, c.cold_copies, c.device_copies
+         FROM mine m
+         JOIN file_locations fl ON fl.id = m.id
+         JOIN files f ON f.id = fl.file_id
+         JOIN copies c ON c.file_id = fl.file_id"
+    )
+    .bind(device_id)
+    .bind(&inside)
+    .bind(&upper)
+    .bind(&upper)
+    .bind(direct_only)
+    .bind(inside.len() as i64)
+    .bind(device_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows)
+}
+
+/// Live locations, on any device, of content that is inside `folder_raw` on
+/// `device_id` or that `scan_id` removed from there. The folder's own
+/// locations are left out.
+pub async fn get_locations_sharing_folder(
+    pool: &DbPool,
+    device_id: &str,
+    folder_raw: &[u8],
+    scan_id: i64,
+) -> Result<Vec<(String, Vec<u8>)>, AppError> {
+    let inside = paths::inside_raw(folder_raw);
+    let rows = sqlx::query_as::<_, (String, Vec<u8>)>(
+        "SELECT DISTINCT other.device_id, other.path_raw
+         FROM file_locations mine
+         JOIN file_locations other ON other.file_id = mine.file_id AND other.removed_at IS NULL
+         WHERE mine.device_id = ? AND (mine.path_raw = ? OR substr(mine.path_raw, 1, ?) = ?)
+           AND (mine.removed_at IS NULL OR mine.removed_by_scan = ?)
+           AND NOT (other.device_id = mine.device_id
+                    AND (other.path_raw = ? OR substr(other.path_raw, 1, ?) = ?))"
+    )
+    .bind(device_id)
+    .bind(folder_raw)
+    .bind(inside.len() as i64)
+    .bind(&inside)
+    .bind(scan_id)
+    .bind(folder_raw)
+    .bind(inside.len() as i64)
+    .bind(&inside)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows)
+}
+
+/// Stored rows of a folder's immediate subfolders.
+pub async fn get_child_directories(pool: &DbPool, device_id: &str, parent_raw: &[u8]) -> Result<Vec<DirectoryRow>, AppError> {
+    let rows = sqlx::query_as::<_, DirectoryRow>(
+        "SELECT device_id, path_raw, path, parent_raw, content_hash, file_count, total_bytes, unsafe_count, duplicate_bytes
+         FROM directories WHERE device_id = ? AND parent_raw = ?"
+    )
+    .bind(device_id)
+    .bind(parent_raw)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows)
+}
+
+/// Swaps a device's rows for `folders` and everything inside `subtree` for
+/// a freshly built set, in one transaction.
+pub async fn replace_directories(
+    pool: &DbPool,
+    device_id: &str,
+    folders: &[Vec<u8>],
+    subtree: Option<&[u8]>,
+    dirs: &[DirectoryRow],
+) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+    if let Some(subtree) = subtree {
+        let inside = paths::inside_raw(subtree);
+        sqlx::query("DELETE FROM directories WHERE device_id = ? AND (path_raw = ? OR substr(path_raw, 1, ?) = ?)")
+            .bind(device_id)
+            .bind(subtree)
+            .bind(inside.len() as i64)
+            .bind(&inside)
+            .execute(&mut *tx)
+            .await?;
+    }
+    for chunk in folders.chunks(500) {
+        let mut qb = QueryBuilder::new("DELETE FROM directories WHERE device_id = ");
+        qb.push_bind(device_id).push(" AND path_raw IN (");
+        let mut separated = qb.separated(", ");
+        for folder in chunk {
+            separated.push_bind(folder);
+        }
+        qb.push(")");
+        qb.build().execute(&mut *tx).await?;
+    }
+    // 9 values a row keeps each statement well under SQLite's bind limit
+    for chunk in dirs.chunks(500) {
+        let mut qb = QueryBuilder::new(
+            "INSERT INTO directories
+                (device_id, path_raw, path, parent_raw, content_hash, file_count, total_bytes, unsafe_count, duplicate_bytes) ",
+        );
+        qb.push_values(chunk, |mut row, dir| {
+            row.push_bind(device_id)
+                .push_bind(&dir.path_raw)
+                .push_bind(&dir.path)
+                .push_bind(&dir.parent_raw)
+                .push_bind(&dir.content_hash)
+                .push_bind(dir.file_count)
+                .push_bind(dir.total_bytes)
+                .push_bind(dir.unsafe_count)
+                .push_bind(dir.duplicate_bytes);
+        });
+        qb.build().execute(&mut *tx).await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Folders (device roots aside) of at least `min_bytes`, optionally only
+/// those whose content hash occurs more than once.
+pub async fn get_directories(pool: &DbPool, min_bytes: i64, duplicated_only: bool) -> Result<Vec<DirectoryRow>, AppError> {
+    let rows = sqlx::query_as::<_, DirectoryRow>(
+        "SELECT device_id, path_raw, path, parent_raw, content_hash, file_count, total_bytes, unsafe_count, duplicate_bytes
+         FROM directories
+         WHERE path_raw <> x'' AND total_bytes >= ?
+           AND (? = 0 OR content_hash IN (
+                SELECT content_hash FROM directories
+                WHERE content_hash IS NOT NULL AND path_raw <> x''
+                GROUP BY content_hash HAVING COUNT(*) > 1))
+         ORDER BY total_bytes DESC, content_hash, device_id, path_raw"
+    )
+    .bind(min_bytes)
+    .bind(duplicated_only)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows)
+}
+
+/// Devices whose directory stats can change when `device_id`'s files do:
+/// itself, and every device holding content it holds or used to hold.
+pub async fn get_devices_sharing_content(pool: &DbPool, device_id: &str) -> Result<Vec<String>, AppError> {
+    let rows = sqlx::query_scalar::<_, String>(
+        "SELECT ? UNION
+         SELECT DISTINCT other.device_id
+         FROM file_locations mine
+         JOIN file_locations other ON other.file_id = mine.file_id AND other.removed_at IS NULL
+         WHERE mine.device_id = ?"
+    )
+    .bind(device_id)
+    .bind(device_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows)
+}
+
+const FOLDER_STATS_COLUMNS: &str =
+    "path, CAST(substr(path, length(rtrim(path, replace(path, '/', ''))) + 1) AS TEXT) AS name,
+     file_count, total_bytes, unsafe_count, duplicate_bytes";
+
+/// A folder's own totals and those of its immediate subfolders, by name.
+pub async fn get_folder_listing(pool: &DbPool, device_id: &str, path_raw: &[u8]) -> Result<FolderListing, AppError> {
+    let folder = sqlx::query_as::<_, FolderStats>(&format!(
+        "SELECT {} FROM directories WHERE device_id = ? AND path_raw = ?",
+        FOLDER_STATS_COLUMNS
+    ))
+    .bind(device_id)
+    .bind(path_raw)
+    .fetch_optional(pool)
+    .await?;
+    let children = sqlx::query_as::<_, FolderStats>(&format!(
+        "SELECT {} FROM directories WHERE device_id = ? AND parent_raw = ? ORDER BY path_raw",
+        FOLDER_STATS_COLUMNS
+    ))
+    .bind(device_id)
+    .bind(path_raw)
+    .fetch_all(pool)
+    .await?;
+    Ok(FolderListing {
+        device_id: device_id.to_string(),
+        folder,
+        children,
+    })
+}
+
+/// Files directly inside a folder (`prefix_raw` as from `paths::prefix_raw`),
+/// in path order after `after_raw`, plus how many there are in all.
+pub async fn get_folder_files(
+    pool: &DbPool,
+    device_id: &str,
+    prefix_raw: &[u8],
+    after_raw: Option<&[u8]>,
+    limit: i64,
+) -> Result<(Vec<FileLocation>, i64), AppError> {
+    const DIRECT_CHILD: &str = "fl.device_id = ? AND fl.removed_at IS NULL
+         AND substr(fl.path_raw, 1, ?) = ? AND instr(substr(fl.path_raw, ? + 1), x'2f') = 0";
+    let len = prefix_raw.len() as i64;
+    let files = sqlx::query_as::<_, FileLocation>(&format!(
+        "SELECT fl.*, f.blake3_hash FROM file_locations fl
+         JOIN files f ON f.id = fl.file_id
+         WHERE {} AND fl.path_raw > ?
+         ORDER BY fl.path_raw LIMIT ?",
+        DIRECT_CHILD
+    ))
+    .bind(device_id)
+    .bind(len)
+    .bind(prefix_raw)
+    .bind(len)
+    .bind(after_raw.unwrap_or(prefix_raw))
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM file_locations fl WHERE {}", DIRECT_CHILD))
+        .bind(device_id)
+        .bind(len)
+        .bind(prefix_raw)
+        .bind(len)
+        .fetch_one(pool)
+        .await?;
+    Ok((files, total))
+}
+
+// --- Device overlap ---
+
+/// Distinct live content per device. Deferred placeholders are left out:
+/// two of them matching says nothing about the files' contents.
+const DEVICE_CONTENT_CTE: &str = "WITH device_files AS (
+         SELECT DISTINCT fl.device_id, fl.file_id, f.file_size
+         FROM file_locations fl
+         JOIN files f ON f.id = fl.file_id
+         WHERE fl.removed_at IS NULL AND substr(f.hash, 1, 9) <> CAST('deferred:' AS BLOB)
+     )";
+
+/// Per device: its distinct content, and how much of it no other device holds.
+pub async fn get_device_content_totals(pool: &DbPool) -> Result<Vec<DeviceContentTotals>, AppError> {
+    let sql = format!(
+        "{DEVICE_CONTENT_CTE},
+         holders AS (SELECT file_id, COUNT(*) AS devices FROM device_files GROUP BY file_id)
+         SELECT d.id AS device_id, d.label, d.device_type,
+                COUNT(df.file_id) AS files,
+                COALESCE(SUM(df.file_size), 0) AS bytes,
+                COALESCE(SUM(CASE WHEN h.devices = 1 THEN 1 ELSE 0 END), 0) AS unique_files,
+                COALESCE(SUM(CASE WHEN h.devices = 1 THEN df.file_size ELSE 0 END), 0) AS unique_bytes
+         FROM storage_devices d
+         LEFT JOIN device_files df ON df.device_id = d.id
+         LEFT JOIN holders h ON h.file_id = df.file_id
+         GROUP BY d.id
+         ORDER BY bytes DESC"
+    );
+    let rows = sqlx::query_as::<_, DeviceContentTotals>(&sql).fetch_all(pool).await?;
+    Ok(rows)
+}
+
+/// Content shared by each pair of devices, once per pair (`device_a` < `device_b`).
+/// Pairs with nothing in common are left out.
+pub async fn get_device_overlaps(pool: &DbPool) -> Result<Vec<DeviceOverlap>, AppError> {
+    let sql = format!(
+        "{DEVICE_CONTENT_CTE}
+         SELECT a.device_id AS device_a, b.device_id AS device_b,
+                COUNT(*) AS shared_files, SUM(a.file_size) AS shared_bytes
+         FROM device_files a
+         JOIN device_files b ON b.file_id = a.file_id AND a.device_id < b.device_id
+         GROUP BY a.device_id, b.device_id
+         ORDER BY shared_bytes DESC"
+    );
+    let rows = sqlx::query_as::<_, DeviceOverlap>(&sql).fetch_all(pool).await?;
+    Ok(rows)
+}
+
+// --- Statistics ---
+
+/// Extensions counted under each media category; anything else is "other".
+pub const MEDIA_CATEGORIES: &[(&str, &[&str])] = &[
+    ("photo", &["jpg", "jpeg", "png", "heic", "heif", "gif", "webp", "tif", "tiff", "bmp"]),
+    ("raw", &["cr2", "cr3", "crw", "nef", "nrw", "arw", "srf", "sr2", "dng", "raf", "orf", "rw2", "pef", "srw", "3fr", "iiq", "x3f"]),
+    ("video", &["mp4", "mov", "m4v", "avi", "mkv", "mts", "m2ts", "wmv", "webm", "3gp", "mpg", "mpeg"]),
+    ("audio", &["mp3", "wav", "flac", "aac", "m4a", "ogg", "opus", "aif", "aiff", "wma"]),
+    ("document", &["pdf", "doc", "docx", "xls", "xlsx", "ppt", "pptx", "odt", "ods", "odp", "txt", "md", "rtf", "csv", "pages", "numbers", "key"]),
+];
+
+pub const STATS_DIMENSIONS: &[&str] = &["category", "extension", "year", "device_type"];
+
+/// Each live file once, with its copy counts and the earliest modified time
+/// of any copy, the closest the catalog has to a capture date. `deferred`
+/// marks placeholders, whose copies are only files of the same size and mtime.
+const FILE_STATS_CTE: &str = "WITH file_stats AS (
+         SELECT f.id, f.extension, f.file_size,
+                substr(f.hash, 1, 9) = CAST('deferred:' AS BLOB) AS deferred,
+                COUNT(*) AS copies,
+                SUM(CASE WHEN d.device_type = 'cold' THEN 1 ELSE 0 END) AS cold_copies,
+                MIN(fl.modified_at) AS modified_at
+         FROM files f
+         JOIN file_locations fl ON fl.file_id = f.id AND fl.removed_at IS NULL
+         JOIN storage_devices d ON d.id = fl.device_id
+         GROUP BY fl.file_id
+     )";
+
+fn media_category_sql(column: &str) -> String {
+    let mut sql = String::from("CASE");
+    for (category, extensions) in MEDIA_CATEGORIES {
+        let list: Vec<String> = extensions.iter().map(|e| format!("'{}'", e)).collect();
+        sql.push_str(&format!(" WHEN {} IN ({}) THEN '{}'", column, list.join(", "), category));
+    }
+    sql.push_str(" ELSE 'other' END");
+    sql
+}
+
+/// File counts and bytes per bucket of `dimension` (one of
+/// `STATS_DIMENSIONS`). A file counts once per bucket; by device type that
+/// is once per type it has a copy on, with `duplicate_bytes` the extra
+/// copies on that type. Files only known by a placeholder hash are counted
+/// apart as not hashed.
+pub async fn get_stat_buckets(pool: &DbPool, dimension: &str) -> Result<Vec<StatBucket>, AppError> {
+    let hashed = |expr: &str| format!("COALESCE(SUM(CASE WHEN NOT fs.deferred THEN {} ELSE 0 END), 0)", expr);
+    let totals = |copies: &str| {
+        format!(
+            "{} AS files, {} AS unique_bytes, {} AS duplicate_bytes, {} AS unsafe_bytes,
+             COALESCE(SUM(fs.deferred), 0) AS not_hashed_files,
+             COALESCE(SUM(CASE WHEN fs.deferred THEN fs.file_size ELSE 0 END), 0) AS not_hashed_bytes",
+            hashed("1"),
+            hashed("fs.file_size"),
+            hashed(&format!("fs.file_size * ({} - 1)", copies)),
+            hashed("CASE WHEN fs.cold_copies < 1 OR fs.copies < 2 THEN fs.file_size ELSE 0 END"),
+        )
+    };
+    let sql = if dimension == "device_type" {
+        format!(
+            "{FILE_STATS_CTE},
+             type_copies AS (
+                 SELECT d.device_type, fl.file_id, COUNT(*) AS copies
+                 FROM file_locations fl
+                 JOIN storage_devices d ON d.id = fl.device_id
+                 WHERE fl.removed_at IS NULL
+                 GROUP BY d.device_type, fl.file_id
+             )
+             SELECT tc.device_type AS key, {}
+             FROM type_copies tc
+             JOIN file_stats fs ON fs.id = tc.file_id
+             GROUP BY tc.device_type
+             ORDER BY unique_bytes DESC",
+            totals("tc.copies")
+        )
+    } else {
+        let (key, order) = match dimension {
+            "category" => (media_category_sql("fs.extension"), "unique_bytes DESC"),
+            "extension" => ("fs.extension".to_string(), "unique_bytes DESC"),
+            "year" => ("COALESCE(substr(fs.modified_at, 1, 4), 'unknown')".to_string(), "key DESC"),
+            _ => {
+                return Err(AppError::General(format!(
+                    "Unknown breakdown '{}' (expected {})",
+                    dimension,
+                    STATS_DIMENSIONS.join(", ")
+                )))
+            }
+        };
+        format!(
+            "{FILE_STATS_CTE}
+             SELECT {key} AS key, {}
+             FROM file_stats fs
+             GROUP BY 1
+             ORDER BY {order}",
+            totals("fs.copies")
+        )
+    };
+    let rows = sqlx::query_as::<_, StatBucket>(&sql).fetch_all(pool).await?;
+    Ok(rows)
+}
+
+pub async fn get_stats_breakdown(pool: &DbPool) -> Result<StatsBreakdown, AppError> {
+    Ok(StatsBreakdown {
+        by_category: get_stat_buckets(pool, "category").await?,
+        by_extension: get_stat_buckets(pool, "extension").await?,
+        by_year: get_stat_buckets(pool, "year").await?,
+        by_device_type: get_stat_buckets(pool, "device_type").await?,
+    })
+}
+
+// --- Capacity ---
+
+/// Records what a device last reported and its live catalogued bytes as
+/// today's sample, replacing an earlier one from the same day. Taken when a
+/// scan, ingest or manifest import changes what's catalogued, not on every
+/// detection.
+pub async fn record_capacity_sample(pool: &DbPool, device_id: &str) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT INTO capacity_samples (device_id, sampled_on, sampled_at, total_bytes, available_bytes, catalogued_bytes)
+         SELECT d.id, date('now'), datetime('now'), d.total_bytes, d.available_bytes,
+                (SELECT COALESCE(SUM(fl.file_size), 0) FROM file_locations fl
+                 WHERE fl.device_id = d.id AND fl.removed_at IS NULL)
+         FROM storage_devices d
+         WHERE d.id = ?
+         ON CONFLICT(device_id, sampled_on) DO UPDATE SET
+           sampled_at = excluded.sampled_at,
+           total_bytes = excluded.total_bytes,
+           available_bytes = excluded.available_bytes,
+           catalogued_bytes = excluded.catalogued_bytes"
+    )
+    .bind(device_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Samples oldest first, for one device or all, optionally only those taken
+/// on or after `since` (`YYYY-MM-DD`).
+pub async fn get_capacity_samples(
+    pool: &DbPool,
+    device_id: Option<&str>,
+    since: Option<&str>,
+) -> Result<Vec<CapacitySample>, AppError> {
+    let rows = sqlx::query_as::<_, CapacitySample>(
+        "SELECT device_id, sampled_at, total_bytes, available_bytes, catalogued_bytes
+         FROM capacity_samples
+         WHERE (?1 IS NULL OR device_id = ?1) AND (?2 IS NULL OR sampled_on >= ?2)
+         ORDER BY device_id, sampled_on"
+    )
+    .bind(device_id)
+    .bind(since)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows)
+}
+
+/// Files and bytes of live content with no copy on a cold device: what the
+/// cold devices still have to take in.
+pub async fn get_cold_backlog(pool: &DbPool) -> Result<(i64, i64), AppError> {
+    let row: (i64, i64) = sqlx::query_as(
+        "SELECT COUNT(*), COALESCE(SUM(file_size), 0) FROM (
+             SELECT f.file_size
+             FROM files f
+             JOIN file_locations fl ON fl.file_id = f.id AND fl.removed_at IS NULL
+             JOIN storage_devices d ON d.id = fl.device_id
+             GROUP BY fl.file_id
+             HAVING SUM(CASE WHEN d.device_type = 'cold' THEN 1 ELSE 0 END) = 0
+         )"
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(row)
+}
+
+pub async fn get_dashboard_stats(pool: &DbPool) -> Result<DashboardStats, AppError> {
+    let total_files: (i64,) = sqlx::query_as(
+        "SELECT COUNT(DISTINCT file_id) FROM file_locations WHERE removed_at IS NULL"
+    )
+    .fetch_one(pool)
+    .await?;
+    let total_locations: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM file_locations WHERE removed_at IS NULL")
+        .fetch_one(pool)
+        .await?;
+    let total_devices: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM storage_devices")
+        .fetch_one(pool)
+        .await?;
+    let total_size: (i64,) = sqlx::query_as(
+        "SELECT COALESCE(SUM(file_size), 0) FROM files
+         WHERE id IN (SELECT file_id FROM file_locations WHERE removed_at IS NULL)"
+    )
+    .fetch_one(pool)
+    .await?;
+    let lost_files: (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM files
+         WHERE substr(hash, 1, 9) <> CAST('deferred:' AS BLOB)
+           AND id NOT IN (SELECT file_id FROM file_locations WHERE removed_at IS NULL)"
+    )
+    .fetch_one(pool)
+    .await?;
+
+    // Count unsafe files: those without cold_copies >= 1 AND total_copies >= 2
+    let unsafe_files: (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM (
+            SELECT f.id,
+                   COUNT(fl.id) as total_copies,
+                   COALESCE(SUM(CASE WHEN d.device_type = 'cold' THEN 1 ELSE 0 END), 0) as cold_copies
+            FROM files f
+            JOIN file_locations fl ON f.id = fl.file_id AND fl.removed_at IS NULL
+            JOIN storage_devices d ON fl.device_id = d.id
+            GROUP BY f.id
+            HAVING cold_copies < 1 OR total_copies < 2
+        )"
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(DashboardStats {
+        total_files: total_files.0,
+        total_locations: total_locations.0,
+        unsafe_files: unsafe_files.0,
+        total_devices: total_devices.0,
+        total_size_bytes: total_size.0,
+        lost_files: lost_files.0,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /// A migrated catalog in a temporary directory, deleted with the guard.
+    pub async fn temp_pool() -> (tempfile::TempDir, DbPool) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("catalog.db");
+        let pool = init_pool(&path).await.unwrap();
+        run_migrations(&pool, &path).await.unwrap();
+        (dir, pool)
+    }
+
+    pub async fn add_device(pool: &DbPool, id: &str, device_type: &str) {
+        sqlx::query("INSERT INTO storage_devices (id, label, mount_point, device_type) VALUES (?, ?, ?, ?)")
+            .bind(id)
+            .bind(id)
+            .bind(format!("/Volumes/{}", id))
+            .bind(device_type)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    /// Catalogs `path` on `device_id` with content named after the path.
+    pub async fn add_location(pool: &DbPool, scan_id: i64, device_id: &str, path: &str) -> i64 {
+        let hash = blake3::hash(path.as_bytes()).to_hex().to_string();
+        let file_id = upsert_file(pool, &hash, 1, path, "").await.unwrap();
+        upsert_location(pool, scan_id, file_id, device_id, path, path.as_bytes(), path, 1, None, "full")
+            .await
+            .unwrap();
+        file_id
+    }
+
+    async fn live_paths(pool: &DbPool, device_id: &str) -> Vec<String> {
+        sqlx::query_as::<_, (String,)>(
+            "SELECT file_path FROM file_locations WHERE device_id = ? AND removed_at IS NULL ORDER BY file_path",
+        )
+        .bind(device_id)
+        .fetch_all(pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|(p,)| p)
+        .collect()
+    }
+
+    #[tokio::test]
+    async fn stale_locations_stop_at_the_folder_boundary() {
+        let (_dir, pool) = temp_pool().await;
+        add_device(&pool, "d1", "hot").await;
+        let scan_id = begin_scan(&pool, "d1", "Photos", b"Photos", "full").await.unwrap();
+        for path in ["Photos/a.jpg", "Photos/b.jpg", "Photos 2/c.jpg", "Photos.bak/d.jpg", "Photos-old.jpg"] {
+            add_location(&pool, scan_id, "d1", path).await;
+        }
+
+        let seen: HashSet<Vec<u8>> = [b"Photos/a.jpg".to_vec()].into();
+        assert_eq!(remove_stale_locations(&pool, scan_id, "d1", b"Photos", &seen).await.unwrap(), 1);
+        assert_eq!(
+            live_paths(&pool, "d1").await,
+            ["Photos 2/c.jpg", "Photos-old.jpg", "Photos.bak/d.jpg", "Photos/a.jpg"]
+        );
+
+        assert_eq!(remove_stale_locations(&pool, scan_id, "d1", b"Photos", &HashSet::new()).await.unwrap(), 1);
+        assert_eq!(live_paths(&pool, "d1").await, ["Photos 2/c.jpg", "Photos-old.jpg", "Photos.bak/d.jpg"]);
+    }
+
+    #[tokio::test]
+    async fn hashed_placeholders_are_cleaned_up_not_lost() {
+        let (_dir, pool) = temp_pool().await;
+        add_device(&pool, "d1", "hot").await;
+        let quick = begin_scan(&pool, "d1", "", b"", "quick").await.unwrap();
+        let placeholder = upsert_file(&pool, "deferred:9:2024-01-01 00:00:00", 9, "big.mov", "mov").await.unwrap();
+        upsert_location(&pool, quick, placeholder, "d1", "big.mov", b"big.mov", "big.mov", 9, None, "deferred")
+            .await
+            .unwrap();
+
+        // A full scan hashes it, superseding the placeholder's location
+        let full = begin_scan(&pool, "d1", "", b"", "full").await.unwrap();
+        let real = upsert_file(&pool, &"ab".repeat(32), 9, "big.mov", "mov").await.unwrap();
+        upsert_location(&pool, full, real, "d1", "big.mov", b"big.mov", "big.mov", 9, None, "full")
+            .await
+            .unwrap();
+        assert!(get_lost_files(&pool).await.unwrap().is_empty());
+        assert_eq!(get_dashboard_stats(&pool).await.unwrap().lost_files, 0);
+
+        assert_eq!(cleanup_orphaned_files(&pool).await.unwrap(), 1);
+        let files: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM files").fetch_one(&pool).await.unwrap();
+        assert_eq!(files.0, 1);
+        assert_eq!(get_files_on_device(&pool, "d1").await.unwrap()[0].file_id, real);
+
+        // A real file that vanished is still lost
+        remove_stale_locations(&pool, full, "d1", b"", &HashSet::new()).await.unwrap();
+        assert_eq!(cleanup_orphaned_files(&pool).await.unwrap(), 0);
+        assert_eq!(get_lost_files(&pool).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn names_differing_in_case_go_stale_separately() {
+        let (_dir, pool) = temp_pool().await;
+        add_device(&pool, "d1", "hot").await;
+        let scan_id = begin_scan(&pool, "d1", "", b"", "full").await.unwrap();
+        add_location(&pool, scan_id, "d1", "Foo.jpg").await;
+        add_location(&pool, scan_id, "d1", "foo.jpg").await;
+
+        // foo.jpg was deleted; Foo.jpg shares its key but not its path
+        let seen: HashSet<Vec<u8>> = [b"Foo.jpg".to_vec()].into();
+        assert_eq!(remove_stale_locations(&pool, scan_id, "d1", b"", &seen).await.unwrap(), 1);
+        assert_eq!(live_paths(&pool, "d1").await, ["Foo.jpg"]);
+    }
+
+    #[tokio::test]
+    async fn only_hashed_copies_on_fixed_hot_or_cold_devices_are_backups() {
+        let (_dir, pool) = temp_pool().await;
+        add_device(&pool, "card", "unknown").await;
+        add_device(&pool, "card2", "cold").await;
+        add_device(&pool, "nas", "cold").await;
+        sqlx::query("UPDATE storage_devices SET is_removable = 1 WHERE id LIKE 'card%'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let hash = blake3::hash(b"IMG_0001.JPG").to_hex().to_string();
+        let scan = |device: &'static str| begin_scan(&pool, device, "", b"", "full");
+        let (card, card2, nas) = (scan("card").await.unwrap(), scan("card2").await.unwrap(), scan("nas").await.unwrap());
+        add_location(&pool, card, "card", "IMG_0001.JPG").await;
+
+        // Another card, even one marked cold, isn't a backup
+        add_location(&pool, card2, "card2", "IMG_0001.JPG").await;
+        assert!(!has_backup_copy(&pool, &hash, "card").await.unwrap());
+
+        // Nor is a copy the catalog only knows of from a manifest
+        let file_id = add_location(&pool, nas, "nas", "IMG_0001.JPG").await;
+        sqlx::query("UPDATE file_locations SET scan_mode = 'manifest' WHERE device_id = 'nas'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(!has_backup_copy(&pool, &hash, "card").await.unwrap());
+        let candidates = get_wipe_candidates(&pool, "card", b"", None, None).await.unwrap();
+        assert_eq!((candidates[0].other_copies, candidates[0].backup_copies), (2, 0));
+
+        upsert_location(&pool, nas, file_id, "nas", "IMG_0001.JPG", b"IMG_0001.JPG", "IMG_0001.JPG", 1, None, "full")
+            .await
+            .unwrap();
+        assert!(has_backup_copy(&pool, &hash, "card").await.unwrap());
+        assert_eq!(get_wipe_candidates(&pool, "card", b"", None, None).await.unwrap()[0].backup_copies, 1);
+    }
+
+    #[tokio::test]
+    async fn overlaps_count_shared_live_hashed_content_once_per_pair() {
+        let (_dir, pool) = temp_pool().await;
+        for device in ["a", "b", "c"] {
+            add_device(&pool, device, "hot").await;
+        }
+        let scan = begin_scan(&pool, "a", "", b"", "full").await.unwrap();
+        let x = add_location(&pool, scan, "a", "x").await;
+        for (device, path) in [("a", "y"), ("b", "x"), ("c", "z"), ("c", "y")] {
+            add_location(&pool, scan, device, path).await;
+        }
+        // Another copy of x on a, a removed copy of y on c, and placeholders
+        // that merely agree on size and mtime, none of which add overlap
+        upsert_location(&pool, scan, x, "a", "copy/x", b"copy/x", "x", 1, None, "full").await.unwrap();
+        sqlx::query("UPDATE file_locations SET removed_at = datetime('now') WHERE device_id = 'c' AND file_path = 'y'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let deferred = upsert_file(&pool, "deferred:1:100", 1, "d", "").await.unwrap();
+        for device in ["a", "b"] {
+            upsert_location(&pool, scan, deferred, device, "d", b"d", "d", 1, None, "deferred").await.unwrap();
+        }
+
+        let pairs: Vec<_> = get_device_overlaps(&pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|p| (p.device_a, p.device_b, p.shared_files, p.shared_bytes))
+            .collect();
+        assert_eq!(pairs, [("a".to_string(), "b".to_string(), 1, 1)]);
+
+        let mut totals: Vec<_> = get_device_content_totals(&pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|t| (t.device_id, t.files, t.unique_files))
+            .collect();
+        totals.sort();
+        assert_eq!(totals, [("a".to_string(), 2, 1), ("b".to_string(), 1, 0), ("c".to_string(), 1, 1)]);
+    }
+
+    #[tokio::test]
+    async fn pages_walk_ties_on_the_sort_key_without_gaps_or_repeats() {
+        let (_dir, pool) = temp_pool().await;
+        add_device(&pool, "d1", "hot").await;
+        let scan = begin_scan(&pool, "d1", "", b"", "full").await.unwrap();
+        // Every file has size 1, so the size order is decided by row id alone
+        for name in ["e.jpg", "b.jpg", "d_1.jpg", "a.jpg", "c.jpg"] {
+            add_location(&pool, scan, "d1", name).await;
+        }
+
+        let walk = |sort: &'static str, name_contains: Option<&'static str>| {
+            let pool = pool.clone();
+            async move {
+                let mut query = ListQuery {
+                    limit: Some(2),
+                    sort: Some(sort.to_string()),
+                    name_contains: name_contains.map(str::to_string),
+                    ..Default::default()
+                };
+                let mut seen = Vec::new();
+                loop {
+                    let page = list_device_files(&pool, "d1", &query).await.unwrap();
+                    assert!(page.items.len() <= 2);
+                    seen.extend(page.items.into_iter().map(|l| l.file_path));
+                    match page.next_cursor {
+                        Some(cursor) => query.cursor = Some(cursor),
+                        None => return (seen, page.total),
+                    }
+                }
+            }
+        };
+
+        let (by_size, total) = walk("size", None).await;
+        assert_eq!(total, 5);
+        assert_eq!(by_size, ["c.jpg", "a.jpg", "d_1.jpg", "b.jpg", "e.jpg"]);
+        let (by_name, _) = walk("name", None).await;
+        assert_eq!(by_name, ["a.jpg", "b.jpg", "c.jpg", "d_1.jpg", "e.jpg"]);
+        // `_` is matched literally, not as a LIKE wildcard
+        assert_eq!(walk("name", Some("_")).await, (vec!["d_1.jpg".to_string()], 1));
+
+        // A full last page is followed by an empty one
+        let query = ListQuery { limit: Some(5), ..Default::default() };
+        let page = list_device_files(&pool, "d1", &query).await.unwrap();
+        let query = ListQuery { cursor: page.next_cursor, ..query };
+        assert!(list_device_files(&pool, "d1", &query).await.unwrap().items.is_empty());
+
+        // A cursor from another sort order is rejected
+        let query = ListQuery { sort: Some("name".to_string()), ..query };
+        assert!(list_device_files(&pool, "d1", &query).await.is_err());
+    }
+
+    /// Catalogs a file of `size` bytes with content named after `path`.
+    async fn add_sized(pool: &DbPool, scan_id: i64, device_id: &str, path: &str, size: i64, modified_at: Option<&str>) -> i64 {
+        let hash = blake3::hash(path.as_bytes()).to_hex().to_string();
+        let ext = path.rsplit_once('.').map_or("", |(_, ext)| ext);
+        let file_id = upsert_file(pool, &hash, size, path, ext).await.unwrap();
+        upsert_location(pool, scan_id, file_id, device_id, path, path.as_bytes(), path, size, modified_at, "full")
+            .await
+            .unwrap();
+        file_id
+    }
+
+    #[tokio::test]
+    async fn stat_buckets_count_each_file_once_and_extra_copies_as_duplicates() {
+        let (_dir, pool) = temp_pool().await;
+        add_device(&pool, "h", "hot").await;
+        add_device(&pool, "c", "cold").await;
+        let scan = begin_scan(&pool, "h", "", b"", "full").await.unwrap();
+        // a.jpg is safe; b.cr2 has two copies but none cold; c.mov has one
+        let a = add_sized(&pool, scan, "h", "a.jpg", 10, Some("2023-05-01 10:00:00")).await;
+        upsert_location(&pool, scan, a, "c", "a.jpg", b"a.jpg", "a.jpg", 10, None, "full").await.unwrap();
+        let b = add_sized(&pool, scan, "h", "b.cr2", 20, Some("2023-08-01 10:00:00")).await;
+        upsert_location(&pool, scan, b, "h", "copy/b.cr2", b"copy/b.cr2", "b.cr2", 20, None, "full").await.unwrap();
+        add_sized(&pool, scan, "h", "c.mov", 40, None).await;
+        // Two placeholders for files of the same size and mtime aren't
+        // duplicates, or anything but not hashed
+        let deferred = upsert_file(&pool, "deferred:80:100", 80, "d.mov", "mov").await.unwrap();
+        for path in ["d.mov", "e.mov"] {
+            upsert_location(&pool, scan, deferred, "h", path, path.as_bytes(), path, 80, None, "deferred").await.unwrap();
+        }
+
+        let buckets = |dimension: &'static str| {
+            let pool = pool.clone();
+            async move {
+                get_stat_buckets(&pool, dimension)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|b| (b.key, b.files, b.unique_bytes, b.duplicate_bytes, b.unsafe_bytes, b.not_hashed_bytes))
+                    .collect::<Vec<_>>()
+            }
+        };
+        let row = |key: &str, files, unique, duplicate, unsafe_bytes, not_hashed| {
+            (key.to_string(), files, unique, duplicate, unsafe_bytes, not_hashed)
+        };
+        assert_eq!(
+            buckets("category").await,
+            [row("video", 1, 40, 0, 40, 80), row("raw", 1, 20, 20, 20, 0), row("photo", 1, 10, 10, 0, 0)]
+        );
+        assert_eq!(buckets("year").await, [row("unknown", 1, 40, 0, 40, 80), row("2023", 2, 30, 30, 20, 0)]);
+        assert_eq!(
+            buckets("device_type").await,
+            [row("hot", 3, 70, 20, 60, 80), row("cold", 1, 10, 0, 0, 0)]
+        );
+        assert!(get_stat_buckets(&pool, "colour").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn detection_alone_records_no_capacity_sample() {
+        let (_dir, pool) = temp_pool().await;
+        let disk = DetectedDisk {
+            id: "d1".to_string(),
+            label: "Photos".to_string(),
+            mount_point: "/Volumes/Photos".to_string(),
+            total_bytes: 1000,
+            available_bytes: 400,
+            is_removable: false,
+        };
+        upsert_device(&pool, &disk).await.unwrap();
+        upsert_device(&pool, &disk).await.unwrap();
+        assert!(get_capacity_samples(&pool, Some("d1"), None).await.unwrap().is_empty());
+
+        // A second sample the same day replaces the first
+        record_capacity_sample(&pool, "d1").await.unwrap();
+        upsert_device(&pool, &DetectedDisk { available_bytes: 300, ..disk }).await.unwrap();
+        record_capacity_sample(&pool, "d1").await.unwrap();
+        let samples = get_capacity_samples(&pool, Some("d1"), None).await.unwrap();
+        assert_eq!(samples.len(), 1);
+        assert_eq!(samples[0].available_bytes, 300);
+    }
+
+    /// A catalog from before versioned migrations: `001_initial.sql` only,
+    /// with hex text hashes and no `_sqlx_migrations` table.
+    #[tokio::test]
+    async fn legacy_catalog_upgrades_with_its_rows_intact() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("catalog.db");
+        let pool = init_pool(&path).await.unwrap();
+        sqlx::raw_sql(include_str!("../migrations/001_initial.sql")).execute(&pool).await.unwrap();
+
+        let hash = blake3::hash(b"beach").to_hex().to_string();
+        sqlx::raw_sql(&format!(
+            "INSERT INTO storage_devices (id, label, mount_point, device_type) VALUES ('d1', 'Photos', '/Volumes/Photos', 'hot');
+             INSERT INTO files (blake3_hash, file_size, representative_name, extension)
+                 VALUES ('{hash}', 5, 'beach.jpg', 'jpg'), ('deferred:d1:Card/big.mov', 9, 'big.mov', 'mov');
+             INSERT INTO file_locations (blake3_hash, device_id, file_path, file_name, file_size, scan_mode)
+                 VALUES ('{hash}', 'd1', 'Trips/beach.jpg', 'beach.jpg', 5, 'full'),
+                        ('deferred:d1:Card/big.mov', 'd1', 'Card/big.mov', 'big.mov', 9, 'deferred');"
+        ))
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        run_migrations(&pool, &path).await.unwrap();
+
+        let files = sqlx::query_as::<_, (Vec<u8>, String)>("SELECT hash, blake3_hash FROM files ORDER BY file_size")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(files[0], (blake3::hash(b"beach").as_bytes().to_vec(), hash.clone()));
+        assert_eq!(files[1], (b"deferred:d1:Card/big.mov".to_vec(), "deferred:d1:Card/big.mov".to_string()));
+
+        let locations = sqlx::query_as::<_, (String, String, Vec<u8>, String)>(
+            "SELECT f.blake3_hash, fl.file_path, fl.path_raw, fl.path_key
+             FROM file_locations fl JOIN files f ON f.id = fl.file_id ORDER BY fl.file_path",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(locations.len(), 2);
+        assert_eq!(locations[1].0, hash);
+        assert_eq!(locations[1].1, "Trips/beach.jpg");
+        assert_eq!(locations[1].2, b"Trips/beach.jpg");
+        assert_eq!(locations[1].3, paths::path_key("Trips/beach.jpg"));
+
+        // 001 is recorded as already applied rather than run again; the rest ran
+        let baselined = sqlx::query_as::<_, (i64, i64)>("SELECT version, execution_time FROM _sqlx_migrations ORDER BY version")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(baselined[0], (1, 0));
+        assert_eq!(baselined.len(), MIGRATOR.iter().count());
+
+        let backups: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with("catalog.db.v1-") && name.ends_with(".bak"))
+            .collect();
+        assert_eq!(backups.len(), 1);
+
+        // Later starts don't look for keys to fill in again
+        sqlx::query("UPDATE file_locations SET path_key = ''").execute(&pool).await.unwrap();
+        run_migrations(&pool, &path).await.unwrap();
+        let (blank,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM file_locations WHERE path_key = ''")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(blank, 2);
+    }
+
+    /// Times the location joins on a generated catalog before and after
+    /// `004_file_ids.sql`. Slow, so it only runs on request:
+    /// `cargo test --release -- --ignored file_id_joins --nocapture`.
+    /// `OFM_BENCH_FILES` sets the file count (default 1M; two thirds have a second copy).
+    #[tokio::test]
+    #[ignore]
+    async fn file_id_joins_benchmark() {
+        use std::time::Instant;
+
+        let files: i64 = std::env::var("OFM_BENCH_FILES").ok().and_then(|n| n.parse().ok()).unwrap_or(1_000_000);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("catalog.db");
+        let pool = init_pool(&path).await.unwrap();
+        for sql in [
+            include_str!("../migrations/001_initial.sql"),
+            include_str!("../migrations/002_raw_paths.sql"),
+            include_str!("../migrations/003_path_keys.sql"),
+        ] {
+            sqlx::raw_sql(sql).execute(&pool).await.unwrap();
+        }
+
+        // Every file sits on the hot drive; every third one is missing from the cold drive
+        sqlx::raw_sql(&format!(
+            "INSERT INTO storage_devices (id, label, mount_point, device_type)
+                 VALUES ('hot', 'hot', '/Volumes/hot', 'hot'), ('cold', 'cold', '/Volumes/cold', 'cold');
+             WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < {files})
+             INSERT INTO files (blake3_hash, file_size, representative_name, extension)
+             SELECT printf('%064x', i * 2654435761), i, 'IMG_' || i || '.jpg', 'jpg' FROM n;
+             INSERT INTO file_locations (blake3_hash, device_id, file_path, path_raw, file_name, file_size, path_key)
+             SELECT blake3_hash, 'hot', 'Photos/' || representative_name, CAST('Photos/' || representative_name AS BLOB),
+                    representative_name, file_size, 'photos/' || lower(representative_name)
+             FROM files;
+             INSERT INTO file_locations (blake3_hash, device_id, file_path, path_raw, file_name, file_size, path_key)
+             SELECT blake3_hash, 'cold', 'Archive/' || representative_name, CAST('Archive/' || representative_name AS BLOB),
+                    representative_name, file_size, 'archive/' || lower(representative_name)
+             FROM files WHERE file_size % 3 <> 0;"
+        ))
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        async fn time(pool: &DbPool, label: &str, sql: &str) {
+            let started = Instant::now();
+            let rows = sqlx::query(sql).fetch_all(pool).await.unwrap().len();
+            println!("{:<28} {:>8.1} ms  ({} rows)", label, started.elapsed().as_secs_f64() * 1000.0, rows);
+        }
+        let size = |pool: &DbPool| {
+            let pool = pool.clone();
+            async move {
+                let (pages, page_size) = sqlx::query_as::<_, (i64, i64)>(
+                    "SELECT page_count, page_size FROM pragma_page_count(), pragma_page_size()",
+                )
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+                pages * page_size / (1024 * 1024)
+            }
+        };
+
+        println!("{} files, {} locations", files, files + files - files / 3);
+        println!("hex TEXT keys, {} MB", size(&pool).await);
+        time(
+            &pool,
+            "single-copy files",
+            "SELECT f.blake3_hash FROM files f JOIN file_locations fl ON fl.blake3_hash = f.blake3_hash
+             GROUP BY f.blake3_hash HAVING COUNT(DISTINCT fl.device_id) < 2",
+        )
+        .await;
+        time(
+            &pool,
+            "device listing",
+            "SELECT fl.file_path, f.file_size FROM file_locations fl JOIN files f ON f.blake3_hash = fl.blake3_hash
+             WHERE fl.device_id = 'cold'",
+        )
+        .await;
+
+        run_migrations(&pool, &path).await.unwrap();
+        sqlx::query("VACUUM").execute(&pool).await.unwrap();
+
+        println!("integer ids, BLOB hashes, {} MB", size(&pool).await);
+        time(
+            &pool,
+            "single-copy files",
+            "SELECT f.id FROM files f JOIN file_locations fl ON fl.file_id = f.id
+             GROUP BY f.id HAVING COUNT(DISTINCT fl.device_id) < 2",
+        )
+        .await;
+        time(
+            &pool,
+            "device listing",
+            "SELECT fl.file_path, f.file_size FROM file_locations fl JOIN files f ON f.id = fl.file_id
+             WHERE fl.device_id = 'cold'",
+        )
+        .await;
+    }
 }
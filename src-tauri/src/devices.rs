@@ -1,5 +1,4 @@
 use std::collections::HashSet;
-use std::process::Command;
 
 use sysinfo::Disks;
 
@@ -12,6 +11,7 @@ const EXCLUDED_MOUNT_PREFIXES: &[&str] = &[
     "/dev",
     "/home",
     "/cores",
+    "/boot",
 ];
 
 const EXCLUDED_MOUNT_CONTAINS: &[&str] = &[
@@ -24,8 +24,9 @@ const EXCLUDED_MOUNT_CONTAINS: &[&str] = &[
     "Hardware",
 ];
 
+#[cfg(not(target_os = "linux"))]
 fn get_volume_uuid(mount_point: &str) -> Option<String> {
-    let output = Command::new("diskutil")
+    let output = std::process::Command::new("diskutil")
         .args(["info", mount_point])
         .output()
         .ok()?;
@@ -39,6 +40,53 @@ fn get_volume_uuid(mount_point: &str) -> Option<String> {
     None
 }
 
+/// On Linux the filesystem UUID is the name of the `/dev/disk/by-uuid` link
+/// resolving to the device mounted at `mount_point`.
+#[cfg(target_os = "linux")]
+fn get_volume_uuid(mount_point: &str) -> Option<String> {
+    let mounts = std::fs::read_to_string("/proc/self/mounts").ok()?;
+    // The last entry for a mount point is the one currently visible
+    let device = mounts.lines().rev().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        let device = fields.next()?;
+        let mount = fields.next()?;
+        (unescape_mount_field(mount) == mount_point).then(|| device.to_string())
+    })?;
+    let device = std::fs::canonicalize(device).ok()?;
+
+    std::fs::read_dir("/dev/disk/by-uuid")
+        .ok()?
+        .filter_map(|e| e.ok())
+        .find(|e| std::fs::canonicalize(e.path()).is_ok_and(|target| target == device))
+        .map(|e| e.file_name().to_string_lossy().to_string())
+}
+
+/// `/proc/self/mounts` writes spaces, tabs, newlines and backslashes in
+/// paths as three-digit octal escapes.
+#[cfg(target_os = "linux")]
+fn unescape_mount_field(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes.get(i + 1..i + 4).and_then(|digits| {
+            let digits = std::str::from_utf8(digits).ok()?;
+            u8::from_str_radix(digits, 8).ok()
+        });
+        match escaped {
+            Some(b) if bytes[i] == b'\\' => {
+                out.push(b);
+                i += 4;
+            }
+            _ => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
 fn is_excluded(mount_point: &str) -> bool {
     for prefix in EXCLUDED_MOUNT_PREFIXES {
         if mount_point.starts_with(prefix) {
@@ -0,0 +1,517 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::db::{self, DbPool};
+use crate::error::AppError;
+use crate::hasher;
+use crate::models::{
+    DirectoryRow, DuplicateFolder, DuplicateFolderGroup, DuplicateFolderReport, FolderFilePage, FolderListing,
+    SimilarFolderPair,
+};
+use crate::paths;
+
+/// Children of one folder, by name. A file carries its hash key and size,
+/// a subfolder is filled in once its own children are done. Totals start
+/// with the subfolders' and get the folder's own files added last.
+#[derive(Default)]
+struct Node {
+    files: BTreeMap<Vec<u8>, (Vec<u8>, i64)>,
+    dirs: BTreeMap<Vec<u8>, Option<[u8; 32]>>,
+    file_count: i64,
+    total_bytes: i64,
+    unsafe_count: i64,
+    duplicate_bytes: i64,
+}
+
+fn parent_of(path_raw: &[u8]) -> Option<(&[u8], &[u8])> {
+    match path_raw.iter().rposition(|&b| b == b'/') {
+        Some(i) => Some((&path_raw[..i], &path_raw[i + 1..])),
+        None if path_raw.is_empty() => None,
+        None => Some((&[], path_raw)),
+    }
+}
+
+/// Merkle hash of a folder: BLAKE3 over its children in name order, each as
+/// a kind tag, the name's length and bytes, then the child's 32-byte hash.
+/// `None` when any child has no content hash yet.
+fn content_hash(node: &Node) -> Option<[u8; 32]> {
+    let mut entries: BTreeMap<&[u8], (u8, [u8; 32])> = BTreeMap::new();
+    for (name, (key, _)) in &node.files {
+        if key.starts_with(b"deferred:") {
+            return None;
+        }
+        entries.insert(name, (b'f', <[u8; 32]>::try_from(key.as_slice()).ok()?));
+    }
+    for (name, hash) in &node.dirs {
+        entries.insert(name, (b'd', (*hash)?));
+    }
+    let mut hasher = blake3::Hasher::new();
+    for (name, (tag, hash)) in entries {
+        hasher.update(&[tag]);
+        hasher.update(&(name.len() as u64).to_le_bytes());
+        hasher.update(name);
+        hasher.update(&hash);
+    }
+    Some(*hasher.finalize().as_bytes())
+}
+
+/// Scans touching more folders than this on another device rebuild that
+/// device whole; one query per folder stops paying off well before.
+const MAX_INCREMENTAL_FOLDERS: usize = 500;
+
+/// Rebuilds a device's folder rows from its live locations. Call after
+/// anything that changes which files a device holds.
+pub async fn rebuild_device(pool: &DbPool, device_id: &str) -> Result<(), AppError> {
+    rebuild_folders(pool, device_id, BTreeSet::from([Vec::new()]), Some(&[])).await
+}
+
+/// Refreshes folder rows after a finished scan of `folder_raw` on
+/// `device_id`: that folder's subtree and ancestors, and on every device the
+/// folders holding content that is in it or that the scan removed from it.
+pub async fn rebuild_scanned(pool: &DbPool, device_id: &str, folder_raw: &[u8], scan_id: i64) -> Result<(), AppError> {
+    let mut dirty: BTreeMap<String, BTreeSet<Vec<u8>>> = BTreeMap::new();
+    add_with_ancestors(dirty.entry(device_id.to_string()).or_default(), folder_raw);
+    for (device, path_raw) in db::get_locations_sharing_folder(pool, device_id, folder_raw, scan_id).await? {
+        if let Some((parent, _)) = parent_of(&path_raw) {
+            add_with_ancestors(dirty.entry(device).or_default(), parent);
+        }
+    }
+
+    for (device, folders) in dirty {
+        if device == device_id {
+            rebuild_folders(pool, &device, folders, Some(folder_raw)).await?;
+        } else if folders.len() > MAX_INCREMENTAL_FOLDERS {
+            rebuild_device(pool, &device).await?;
+        } else {
+            rebuild_folders(pool, &device, folders, None).await?;
+        }
+    }
+    Ok(())
+}
+
+fn add_with_ancestors(folders: &mut BTreeSet<Vec<u8>>, mut folder: &[u8]) {
+    while folders.insert(folder.to_vec()) {
+        match parent_of(folder) {
+            Some((parent, _)) => folder = parent,
+            None => break,
+        }
+    }
+}
+
+/// Rebuilds a device's rows for `dirty` (which must hold every ancestor of
+/// its folders) and for everything inside `subtree`. Other folders keep
+/// their stored rows, which feed into their rebuilt parents.
+async fn rebuild_folders(
+    pool: &DbPool,
+    device_id: &str,
+    dirty: BTreeSet<Vec<u8>>,
+    subtree: Option<&[u8]>,
+) -> Result<(), AppError> {
+    let in_subtree = |path: &[u8]| subtree.is_some_and(|s| paths::is_within(path, s));
+    let mut nodes: HashMap<Vec<u8>, Node> = dirty.iter().map(|p| (p.clone(), Node::default())).collect();
+    let mut entries = match subtree {
+        Some(subtree) => db::get_live_entries_in(pool, device_id, subtree, false).await?,
+        None => Vec::new(),
+    };
+    for folder in dirty.iter().filter(|f| !in_subtree(f)) {
+        entries.extend(db::get_live_entries_in(pool, device_id, folder, true).await?);
+        for child in db::get_child_directories(pool, device_id, folder).await? {
+            if dirty.contains(&child.path_raw) || in_subtree(&child.path_raw) {
+                continue;
+            }
+            let Some((_, name)) = parent_of(&child.path_raw) else {
+                continue;
+            };
+            let node = nodes.entry(folder.clone()).or_default();
+            let hash = child.content_hash.as_deref().and_then(|h| <[u8; 32]>::try_from(h).ok());
+            node.dirs.insert(name.to_vec(), hash);
+            node.file_count += child.file_count;
+            node.total_bytes += child.total_bytes;
+            node.unsafe_count += child.unsafe_count;
+            node.duplicate_bytes += child.duplicate_bytes;
+        }
+    }
+
+    for entry in entries {
+        let Some((dir, name)) = parent_of(&entry.path_raw) else {
+            continue;
+        };
+        let node = nodes.entry(dir.to_vec()).or_default();
+        if entry.cold_copies < 1 || entry.total_copies < 2 {
+            node.unsafe_count += 1;
+        }
+        if entry.device_copies > 1 {
+            node.duplicate_bytes += entry.file_size;
+        }
+        node.files.insert(name.to_vec(), (entry.hash, entry.file_size));
+        // Register every ancestor so empty-of-files folders still link up
+        let mut child = dir;
+        while let Some((parent, name)) = parent_of(child) {
+            let parent_node = nodes.entry(parent.to_vec()).or_default();
+            if parent_node.dirs.contains_key(name) {
+                break;
+            }
+            parent_node.dirs.insert(name.to_vec(), None);
+            child = parent;
+        }
+    }
+
+    // Deepest folders first, so each parent sees finished children
+    let mut order: Vec<Vec<u8>> = nodes.keys().cloned().collect();
+    order.sort_by_key(|p| std::cmp::Reverse(if p.is_empty() { 0 } else { p.iter().filter(|&&b| b == b'/').count() + 1 }));
+
+    let mut rows = Vec::with_capacity(order.len());
+    for path_raw in order {
+        let mut node = nodes.remove(&path_raw).unwrap_or_default();
+        // A dirty folder whose files are all gone; the root row always stays
+        if node.files.is_empty() && node.dirs.is_empty() && !path_raw.is_empty() {
+            continue;
+        }
+        node.file_count += node.files.len() as i64;
+        node.total_bytes += node.files.values().map(|(_, size)| size).sum::<i64>();
+        let hash = content_hash(&node);
+
+        let parent_raw = parent_of(&path_raw).map(|(parent, name)| {
+            if let Some(parent_node) = nodes.get_mut(parent) {
+                parent_node.dirs.insert(name.to_vec(), hash);
+                parent_node.file_count += node.file_count;
+                parent_node.total_bytes += node.total_bytes;
+                parent_node.unsafe_count += node.unsafe_count;
+                parent_node.duplicate_bytes += node.duplicate_bytes;
+            }
+            parent.to_vec()
+        });
+        rows.push(DirectoryRow {
+            device_id: device_id.to_string(),
+            path: paths::from_raw(&path_raw).to_string_lossy().to_string(),
+            path_raw,
+            parent_raw,
+            content_hash: hash.map(|h| h.to_vec()),
+            file_count: node.file_count,
+            total_bytes: node.total_bytes,
+            unsafe_count: node.unsafe_count,
+            duplicate_bytes: node.duplicate_bytes,
+        });
+    }
+
+    let dirty: Vec<Vec<u8>> = dirty.into_iter().collect();
+    db::replace_directories(pool, device_id, &dirty, subtree, &rows).await
+}
+
+/// Rebuilds the given devices' folders and those of every device sharing
+/// content with them, whose safety counts depend on how many copies the
+/// changed devices hold.
+pub async fn rebuild_affected(pool: &DbPool, device_ids: &[&str]) -> Result<(), AppError> {
+    let mut affected = BTreeSet::new();
+    for device_id in device_ids {
+        affected.extend(db::get_devices_sharing_content(pool, device_id).await?);
+    }
+    for device_id in &affected {
+        rebuild_device(pool, device_id).await?;
+    }
+    Ok(())
+}
+
+/// Totals for a folder on a device (`""` for the root) and each of its
+/// immediate subfolders, from the stored folder rows.
+pub async fn folder_listing(pool: &DbPool, device_id: &str, path: &str) -> Result<FolderListing, AppError> {
+    let path_raw = paths::prefix_raw(path);
+    let path_raw = path_raw.strip_suffix(b"/").unwrap_or(&path_raw);
+    db::get_folder_listing(pool, device_id, path_raw).await
+}
+
+/// A page of the files directly inside a folder, in path order. `cursor` is
+/// the previous page's `next_cursor`.
+pub async fn folder_files(
+    pool: &DbPool,
+    device_id: &str,
+    path: &str,
+    cursor: Option<&str>,
+    limit: i64,
+) -> Result<FolderFilePage, AppError> {
+    if !(1..=db::MAX_PAGE_SIZE).contains(&limit) {
+        return Err(AppError::General(format!("Page size must be between 1 and {}", db::MAX_PAGE_SIZE)));
+    }
+    let after = cursor
+        .map(|c| paths::from_hex(c).ok_or_else(|| AppError::General(format!("Invalid cursor: {}", c))))
+        .transpose()?;
+    let (files, total) =
+        db::get_folder_files(pool, device_id, &paths::prefix_raw(path), after.as_deref(), limit).await?;
+    let next_cursor = if files.len() as i64 == limit {
+        files.last().map(|f| paths::to_hex(&f.path_raw))
+    } else {
+        None
+    };
+    Ok(FolderFilePage { files, total, next_cursor })
+}
+
+fn folder(row: &DirectoryRow) -> DuplicateFolder {
+    DuplicateFolder {
+        device_id: row.device_id.clone(),
+        path: row.path.clone(),
+        file_count: row.file_count,
+        total_bytes: row.total_bytes,
+    }
+}
+
+/// Key of a folder's parent, unless the parent is the device root.
+fn parent_key(row: &DirectoryRow) -> Option<(String, Vec<u8>)> {
+    row.parent_raw
+        .as_ref()
+        .filter(|p| !p.is_empty())
+        .map(|p| (row.device_id.clone(), p.clone()))
+}
+
+fn is_nested(a: &DirectoryRow, b: &DirectoryRow) -> bool {
+    let under = |inner: &[u8], outer: &[u8]| inner.len() > outer.len() && inner.starts_with(outer) && inner[outer.len()] == b'/';
+    a.device_id == b.device_id && (under(&a.path_raw, &b.path_raw) || under(&b.path_raw, &a.path_raw))
+}
+
+/// Folders that duplicate each other, within or across devices, from the
+/// stored folder hashes. Only the topmost folders of a duplicated tree are
+/// reported: when two folders' parents are duplicates too, the parents'
+/// group already covers them. With `similarity` set, also reports pairs
+/// of folders whose contents overlap at least that much (0.0-1.0) even
+/// though names or a few files differ.
+pub async fn find_duplicate_folders(
+    pool: &DbPool,
+    min_bytes: i64,
+    similarity: Option<f64>,
+) -> Result<DuplicateFolderReport, AppError> {
+    if similarity.is_some_and(|s| !(0.0..=1.0).contains(&s)) {
+        return Err(AppError::General("Similarity must be between 0 and 1".to_string()));
+    }
+    let mut report = DuplicateFolderReport::default();
+
+    let duplicated = db::get_directories(pool, min_bytes, true).await?;
+    let duplicated_keys: HashSet<(String, Vec<u8>)> = duplicated
+        .iter()
+        .map(|d| (d.device_id.clone(), d.path_raw.clone()))
+        .collect();
+    let mut groups: Vec<(Vec<u8>, Vec<&DirectoryRow>)> = Vec::new();
+    for row in &duplicated {
+        let hash = row.content_hash.clone().unwrap_or_default();
+        match groups.last_mut() {
+            Some((h, members)) if *h == hash => members.push(row),
+            _ => groups.push((hash, vec![row])),
+        }
+    }
+    for (hash, members) in groups {
+        let implied = members
+            .iter()
+            .all(|m| parent_key(m).is_some_and(|k| duplicated_keys.contains(&k)));
+        if implied {
+            continue;
+        }
+        let first = members[0];
+        let reclaimable = (members.len() as i64 - 1) * first.total_bytes;
+        report.reclaimable_bytes += reclaimable;
+        report.exact.push(DuplicateFolderGroup {
+            content_hash: hasher::hash_hex(&hash),
+            folders: members.into_iter().map(folder).collect(),
+            file_count: first.file_count,
+            total_bytes: first.total_bytes,
+            reclaimable_bytes: reclaimable,
+        });
+    }
+
+    if let Some(threshold) = similarity {
+        report.similar = similar_folders(pool, min_bytes, threshold).await?;
+        report.similar_shared_bytes = report.similar.iter().map(|p| p.shared_bytes).sum();
+    }
+    Ok(report)
+}
+
+/// Pairs of folders sharing at least `threshold` of the larger one's
+/// distinct content, found through an index from each file to the folders
+/// holding it. Pairs the exact report already explains (same tree, or one
+/// folder holding an exact copy of the other) and pairs whose parents
+/// already qualify are left out.
+async fn similar_folders(pool: &DbPool, min_bytes: i64, threshold: f64) -> Result<Vec<SimilarFolderPair>, AppError> {
+    let dirs = db::get_directories(pool, min_bytes, false).await?;
+    let index: HashMap<(String, Vec<u8>), usize> = dirs
+        .iter()
+        .enumerate()
+        .map(|(i, d)| ((d.device_id.clone(), d.path_raw.clone()), i))
+        .collect();
+    let mut by_hash: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    for (i, d) in dirs.iter().enumerate() {
+        if let Some(hash) = &d.content_hash {
+            by_hash.entry(hash).or_default().push(i);
+        }
+    }
+    let holds_copy = |outer: &DirectoryRow, inner: &DirectoryRow| {
+        inner.content_hash.as_deref().and_then(|h| by_hash.get(h)).is_some_and(|copies| {
+            copies.iter().any(|&c| {
+                let copy = &dirs[c];
+                copy.content_hash == outer.content_hash || (is_nested(copy, outer) && copy.path_raw.len() > outer.path_raw.len())
+            })
+        })
+    };
+
+    // Distinct content per folder, and folders per content
+    let mut contents: Vec<HashSet<i64>> = vec![HashSet::new(); dirs.len()];
+    let mut unique_bytes = vec![0i64; dirs.len()];
+    let mut holders: HashMap<i64, (i64, Vec<usize>)> = HashMap::new();
+    for entry in db::get_live_entries(pool).await? {
+        if entry.hash.starts_with(b"deferred:") {
+            continue;
+        }
+        let mut path: &[u8] = &entry.path_raw;
+        while let Some((parent, _)) = parent_of(path) {
+            if parent.is_empty() {
+                break;
+            }
+            if let Some(&i) = index.get(&(entry.device_id.clone(), parent.to_vec())) {
+                if contents[i].insert(entry.file_id) {
+                    unique_bytes[i] += entry.file_size;
+                    holders.entry(entry.file_id).or_insert((entry.file_size, Vec::new())).1.push(i);
+                }
+            }
+            path = parent;
+        }
+    }
+
+    let mut shared: HashMap<(usize, usize), i64> = HashMap::new();
+    for (size, folders) in holders.values() {
+        for (n, &a) in folders.iter().enumerate() {
+            for &b in &folders[n + 1..] {
+                let pair = if a < b { (a, b) } else { (b, a) };
+                *shared.entry(pair).or_default() += size;
+            }
+        }
+    }
+
+    let score = |&(a, b): &(usize, usize), bytes: i64| -> Option<f64> {
+        let (left, right) = (&dirs[a], &dirs[b]);
+        if is_nested(left, right) || holds_copy(left, right) || holds_copy(right, left) {
+            return None;
+        }
+        let larger = unique_bytes[a].max(unique_bytes[b]);
+        let ratio = if larger > 0 { bytes as f64 / larger as f64 } else { 0.0 };
+        (ratio >= threshold).then_some(ratio)
+    };
+    let passing: HashMap<(usize, usize), f64> = shared
+        .iter()
+        .filter_map(|(pair, &bytes)| score(pair, bytes).map(|ratio| (*pair, ratio)))
+        .collect();
+
+    let mut pairs: Vec<SimilarFolderPair> = passing
+        .iter()
+        .filter(|((a, b), _)| {
+            let parents = parent_key(&dirs[*a])
+                .and_then(|k| index.get(&k))
+                .zip(parent_key(&dirs[*b]).and_then(|k| index.get(&k)));
+            !parents.is_some_and(|(&pa, &pb)| passing.contains_key(&(pa.min(pb), pa.max(pb))))
+        })
+        .map(|(&(a, b), &ratio)| SimilarFolderPair {
+            left: folder(&dirs[a]),
+            right: folder(&dirs[b]),
+            similarity: ratio,
+            shared_bytes: shared[&(a, b)],
+        })
+        .collect();
+    pairs.sort_by(|x, y| {
+        y.shared_bytes
+            .cmp(&x.shared_bytes)
+            .then_with(|| (&x.left.device_id, &x.left.path).cmp(&(&y.left.device_id, &y.left.path)))
+    });
+    Ok(pairs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::{add_device, add_location, temp_pool};
+    use std::collections::HashSet;
+
+    type Row = (String, Vec<u8>, Option<Vec<u8>>, Option<Vec<u8>>, i64, i64, i64, i64);
+
+    async fn all_rows(pool: &DbPool) -> Vec<Row> {
+        sqlx::query_as::<_, Row>(
+            "SELECT device_id, path_raw, parent_raw, content_hash, file_count, total_bytes, unsafe_count, duplicate_bytes
+             FROM directories ORDER BY device_id, path_raw",
+        )
+        .fetch_all(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn copy_to(pool: &DbPool, scan_id: i64, file_id: i64, device_id: &str, path: &str) {
+        db::upsert_location(pool, scan_id, file_id, device_id, path, path.as_bytes(), path, 1, None, "full")
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn scanned_subtree_rebuild_matches_a_full_rebuild() {
+        let (_dir, pool) = temp_pool().await;
+        add_device(&pool, "hot", "hot").await;
+        add_device(&pool, "cold", "cold").await;
+        let first = db::begin_scan(&pool, "hot", "", b"", "full").await.unwrap();
+        let a = add_location(&pool, first, "hot", "Photos/2024/a.jpg").await;
+        add_location(&pool, first, "hot", "Photos/2024/b.jpg").await;
+        let c = add_location(&pool, first, "hot", "Photos/2023/c.jpg").await;
+        add_location(&pool, first, "hot", "Docs/d.pdf").await;
+        copy_to(&pool, first, a, "cold", "Archive/2024/a.jpg").await;
+        copy_to(&pool, first, c, "cold", "Archive/2023/c.jpg").await;
+        add_location(&pool, first, "cold", "Archive/other/e.jpg").await;
+        rebuild_affected(&pool, &["hot"]).await.unwrap();
+
+        // Rescan Photos/2024: a.jpg is gone, a new folder appears and c's content
+        // gains a second copy on the same device
+        let scan_id = db::begin_scan(&pool, "hot", "Photos/2024", b"Photos/2024", "full").await.unwrap();
+        add_location(&pool, scan_id, "hot", "Photos/2024/new/f.jpg").await;
+        copy_to(&pool, scan_id, c, "hot", "Photos/2024/c2.jpg").await;
+        let seen: HashSet<Vec<u8>> =
+            [&b"Photos/2024/b.jpg"[..], b"Photos/2024/new/f.jpg", b"Photos/2024/c2.jpg"].iter().map(|p| p.to_vec()).collect();
+        db::remove_stale_locations(&pool, scan_id, "hot", b"Photos/2024", &seen).await.unwrap();
+
+        rebuild_scanned(&pool, "hot", b"Photos/2024", scan_id).await.unwrap();
+        let incremental = all_rows(&pool).await;
+        rebuild_device(&pool, "hot").await.unwrap();
+        rebuild_device(&pool, "cold").await.unwrap();
+        assert_eq!(incremental, all_rows(&pool).await);
+
+        // The cold copy of a.jpg is now its only one
+        let archive_2024 = incremental.iter().find(|r| r.0 == "cold" && r.1 == b"Archive/2024").unwrap();
+        assert_eq!(archive_2024.6, 1);
+    }
+
+    #[tokio::test]
+    async fn duplicate_trees_are_reported_once_and_near_copies_by_overlap() {
+        let (_dir, pool) = temp_pool().await;
+        add_device(&pool, "hot", "hot").await;
+        add_device(&pool, "cold", "cold").await;
+        let scan = db::begin_scan(&pool, "hot", "", b"", "full").await.unwrap();
+        let a = add_location(&pool, scan, "hot", "Shoot/a.jpg").await;
+        let b = add_location(&pool, scan, "hot", "Shoot/raw/b.cr2").await;
+        copy_to(&pool, scan, a, "cold", "Backup/Shoot/a.jpg").await;
+        copy_to(&pool, scan, b, "cold", "Backup/Shoot/raw/b.cr2").await;
+        // Edit and Final share three of their four files
+        for name in ["1.jpg", "2.jpg", "3.jpg"] {
+            let id = add_location(&pool, scan, "hot", &format!("Edit/{}", name)).await;
+            copy_to(&pool, scan, id, "cold", &format!("Final/{}", name)).await;
+        }
+        add_location(&pool, scan, "hot", "Edit/4.jpg").await;
+        add_location(&pool, scan, "cold", "Final/5.jpg").await;
+        rebuild_affected(&pool, &["hot", "cold"]).await.unwrap();
+
+        let report = find_duplicate_folders(&pool, 0, Some(0.7)).await.unwrap();
+        // Shoot/raw duplicates Backup/Shoot/raw too, but that follows from its parent
+        assert_eq!(report.exact.len(), 1);
+        let mut folders: Vec<_> = report.exact[0].folders.iter().map(|f| (f.device_id.as_str(), f.path.as_str())).collect();
+        folders.sort();
+        assert_eq!(folders, [("cold", "Backup/Shoot"), ("hot", "Shoot")]);
+        assert_eq!((report.exact[0].file_count, report.reclaimable_bytes), (2, 2));
+
+        assert_eq!(report.similar.len(), 1);
+        let pair = &report.similar[0];
+        let mut paths = [pair.left.path.as_str(), pair.right.path.as_str()];
+        paths.sort();
+        assert_eq!(paths, ["Edit", "Final"]);
+        assert_eq!((pair.shared_bytes, pair.similarity), (3, 0.75));
+
+        assert!(find_duplicate_folders(&pool, 0, Some(0.8)).await.unwrap().similar.is_empty());
+        assert!(find_duplicate_folders(&pool, 0, Some(1.5)).await.is_err());
+    }
+}
@@ -11,6 +11,9 @@ pub enum AppError {
     #[error("Migrate error: {0}")]
     Migrate(#[from] sqlx::migrate::MigrateError),
 
+    #[error("A scan is already running")]
+    ScanRunning,
+
     #[error("{0}")]
     General(String),
 }
@@ -1,7 +1,14 @@
+use std::io::Read;
 use std::path::Path;
 use tokio::task::spawn_blocking;
 
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use xxhash_rust::xxh3::Xxh3;
+
 use crate::error::AppError;
+use crate::paths;
 
 pub async fn hash_file(path: &Path) -> Result<String, AppError> {
     let path = path.to_path_buf();
@@ -15,3 +22,151 @@ pub async fn hash_file(path: &Path) -> Result<String, AppError> {
 
     Ok(hash)
 }
+
+/// Digests besides BLAKE3 that can be stored in the `digests` table.
+pub const EXTRA_ALGORITHMS: &[&str] = &["sha256", "sha1", "md5", "xxh3"];
+
+/// BLAKE3 plus any extra digests of one file, all lowercase hex.
+#[derive(Debug, Clone)]
+pub struct FileHashes {
+    pub blake3: String,
+    pub extra: Vec<(String, String)>,
+}
+
+enum ExtraHasher {
+    Sha256(Sha256),
+    Sha1(Sha1),
+    Md5(Md5),
+    Xxh3(Box<Xxh3>),
+}
+
+impl ExtraHasher {
+    fn new(algorithm: &str) -> Result<Self, AppError> {
+        match algorithm {
+            "sha256" => Ok(Self::Sha256(Sha256::new())),
+            "sha1" => Ok(Self::Sha1(Sha1::new())),
+            "md5" => Ok(Self::Md5(Md5::new())),
+            "xxh3" => Ok(Self::Xxh3(Box::new(Xxh3::new()))),
+            other => Err(AppError::General(format!("Unsupported digest algorithm: {}", other))),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha256(h) => h.update(data),
+            Self::Sha1(h) => h.update(data),
+            Self::Md5(h) => h.update(data),
+            Self::Xxh3(h) => h.update(data),
+        }
+    }
+
+    fn finalize_hex(self) -> String {
+        match self {
+            Self::Sha256(h) => paths::to_hex(&h.finalize()),
+            Self::Sha1(h) => paths::to_hex(&h.finalize()),
+            Self::Md5(h) => paths::to_hex(&h.finalize()),
+            // Big-endian, as xxhsum prints it
+            Self::Xxh3(h) => format!("{:016x}", h.digest()),
+        }
+    }
+}
+
+/// Hashes a file with BLAKE3 and every algorithm in `extra` in a single read,
+/// so requesting checksums for delivery doesn't mean reading the file twice.
+pub async fn hash_file_with(path: &Path, extra: &[String]) -> Result<FileHashes, AppError> {
+    if extra.is_empty() {
+        let blake3 = hash_file(path).await?;
+        return Ok(FileHashes { blake3, extra: Vec::new() });
+    }
+
+    let path = path.to_path_buf();
+    let extra = extra.to_vec();
+    spawn_blocking(move || -> Result<FileHashes, AppError> {
+        let mut blake3 = blake3::Hasher::new();
+        let mut hashers = extra
+            .iter()
+            .map(|a| ExtraHasher::new(a))
+            .collect::<Result<Vec<_>, _>>()?;
+        read_chunks(&path, |chunk| {
+            blake3.update(chunk);
+            for h in &mut hashers {
+                h.update(chunk);
+            }
+        })?;
+        Ok(FileHashes {
+            blake3: blake3.finalize().to_hex().to_string(),
+            extra: extra.into_iter().zip(hashers.into_iter().map(ExtraHasher::finalize_hex)).collect(),
+        })
+    })
+    .await
+    .map_err(|e| AppError::General(e.to_string()))?
+}
+
+/// Computes one of `EXTRA_ALGORITHMS` for a file as lowercase hex.
+pub async fn digest_file(path: &Path, algorithm: &str) -> Result<String, AppError> {
+    let mut hasher = ExtraHasher::new(algorithm)?;
+    let path = path.to_path_buf();
+    spawn_blocking(move || -> Result<String, AppError> {
+        read_chunks(&path, |chunk| hasher.update(chunk))?;
+        Ok(hasher.finalize_hex())
+    })
+    .await
+    .map_err(|e| AppError::General(e.to_string()))?
+}
+
+fn read_chunks(path: &Path, mut f: impl FnMut(&[u8])) -> Result<(), AppError> {
+    let mut file = std::fs::File::open(path)?;
+    let mut buf = vec![0u8; 1024 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            return Ok(());
+        }
+        f(&buf[..n]);
+    }
+}
+
+/// Storage form of a hash: the 32 raw BLAKE3 bytes for a hex digest, or the
+/// text itself for `deferred:` placeholders (and anything else that isn't hex).
+pub fn hash_key(hash: &str) -> Vec<u8> {
+    match blake3::Hash::from_hex(hash) {
+        Ok(h) => h.as_bytes().to_vec(),
+        Err(_) => hash.as_bytes().to_vec(),
+    }
+}
+
+/// Inverse of `hash_key`: hex for 32-byte digests, text for placeholders.
+pub fn hash_hex(key: &[u8]) -> String {
+    match <[u8; 32]>::try_from(key) {
+        Ok(bytes) if !key.starts_with(b"deferred:") => blake3::Hash::from_bytes(bytes).to_hex().to_string(),
+        _ => String::from_utf8_lossy(key).into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn extra_digests_match_the_standard_tools() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("abc.txt");
+        std::fs::write(&path, b"abc").unwrap();
+
+        let algorithms: Vec<String> = EXTRA_ALGORITHMS.iter().map(|a| a.to_string()).collect();
+        let hashes = hash_file_with(&path, &algorithms).await.unwrap();
+        assert_eq!(hashes.blake3, hash_file(&path).await.unwrap());
+        assert_eq!(
+            hashes.extra,
+            [
+                ("sha256", "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string()),
+                ("sha1", "a9993e364706816aba3e25717850c26c9cd0d89d".to_string()),
+                ("md5", "900150983cd24fb0d6963f7d28e17f72".to_string()),
+                ("xxh3", format!("{:016x}", xxhash_rust::xxh3::xxh3_64(b"abc"))),
+            ]
+            .map(|(a, d)| (a.to_string(), d))
+        );
+        assert_eq!(digest_file(&path, "md5").await.unwrap(), "900150983cd24fb0d6963f7d28e17f72");
+        assert!(hash_file_with(&path, &["crc32".to_string()]).await.is_err());
+    }
+}
@@ -0,0 +1,429 @@
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+use chrono::{DateTime, Datelike, Local};
+use tokio_util::sync::CancellationToken;
+use walkdir::WalkDir;
+
+use crate::db::{self, DbPool};
+use crate::devices::{detect_volumes, device_for_path};
+use crate::directories;
+use crate::error::AppError;
+use crate::hasher;
+use crate::manifest;
+use crate::models::{IngestIssue, IngestReport, ScanEvent, ScanSummary};
+use crate::paths;
+use crate::progress::{ProgressSink, ProgressTracker};
+
+pub const DEFAULT_TEMPLATE: &str = "{year}/{yyyy-mm-dd}/{original_name}";
+
+pub struct IngestOptions {
+    /// Card, or a DCIM folder on it
+    pub source: PathBuf,
+    /// Folder on a hot device the template is resolved against
+    pub destination: PathBuf,
+    /// Relative path for each copied file. Placeholders: `{year}`, `{month}`,
+    /// `{day}`, `{yyyy-mm-dd}` (from the file's modification time),
+    /// `{original_name}`, `{stem}`, `{ext}` and `{device}` (the card's label).
+    pub template: String,
+}
+
+/// Where one side of the ingest lives in the catalog.
+struct Side {
+    device_id: String,
+    mount_point: PathBuf,
+    /// The ingested folder relative to the mount point, as raw bytes
+    prefix_raw: Vec<u8>,
+    scan_id: i64,
+    summary: ScanSummary,
+}
+
+impl Side {
+    fn relative<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.mount_point).unwrap_or(path)
+    }
+}
+
+/// Offloads a camera card: files whose content already has a backup (a
+/// hashed copy on a fixed hot or cold device) are left alone, the rest are
+/// copied into `destination`, hashed again from the copy, and catalogued on
+/// both devices. Files catalogued on the card before but gone now are
+/// tombstoned. The card is reported safe to format once every file on it
+/// has such a backup; a copy on another card doesn't count.
+pub async fn ingest(
+    pool: &DbPool,
+    options: &IngestOptions,
+    sink: &dyn ProgressSink,
+    cancel_token: &CancellationToken,
+) -> Result<IngestReport, AppError> {
+    // Fail on a bad template before touching anything
+    render_template(&options.template, Path::new("IMG_0001.JPG"), "CARD", SystemTime::UNIX_EPOCH)?;
+
+    let volumes = detect_volumes();
+    let source_str = options.source.to_string_lossy().to_string();
+    let (source_id, source_mount) = device_for_path(&volumes, &source_str)
+        .ok_or_else(|| AppError::General(format!("No device found for path: {}", source_str)))?;
+    let source_disk = volumes.iter().find(|d| d.id == source_id);
+    if !source_disk.is_some_and(|d| d.is_removable) && !has_dcim(&options.source) {
+        return Err(AppError::General(format!(
+            "{} is neither a removable device nor a camera card (no DCIM folder)",
+            source_str
+        )));
+    }
+    let source_label = source_disk.map(|d| d.label.clone()).unwrap_or_default();
+
+    let dest_str = options.destination.to_string_lossy().to_string();
+    let (dest_id, dest_mount) = device_for_path(&volumes, &dest_str)
+        .ok_or_else(|| AppError::General(format!("No device found for path: {}", dest_str)))?;
+    if dest_id == source_id {
+        return Err(AppError::General("Destination is on the card being ingested".to_string()));
+    }
+    let dest_type = db::get_all_devices(pool)
+        .await?
+        .into_iter()
+        .find(|d| d.id == dest_id)
+        .map(|d| d.device_type);
+    if dest_type.as_deref() != Some("hot") {
+        return Err(AppError::General("Destination must be on a device marked hot".to_string()));
+    }
+
+    for disk in volumes.iter().filter(|d| d.id == source_id || d.id == dest_id) {
+        db::upsert_device(pool, disk).await?;
+    }
+    let mut source = begin_side(pool, source_id, &source_mount, &options.source).await?;
+    let mut dest = begin_side(pool, dest_id, &dest_mount, &options.destination).await?;
+
+    let result = ingest_files(pool, options, &source_label, &mut source, &mut dest, sink, cancel_token).await;
+    let status = match &result {
+        Ok(report) if report.cancelled => "cancelled",
+        Ok(_) => "finished",
+        Err(_) => "failed",
+    };
+    db::finish_scan(pool, source.scan_id, status, &source.summary).await?;
+    db::finish_scan(pool, dest.scan_id, status, &dest.summary).await?;
+    directories::rebuild_affected(pool, &[&source.device_id, &dest.device_id]).await?;
+    db::record_capacity_sample(pool, &source.device_id).await?;
+    db::record_capacity_sample(pool, &dest.device_id).await?;
+    if status == "cancelled" {
+        sink.emit(ScanEvent::Cancelled);
+    }
+    result
+}
+
+async fn begin_side(pool: &DbPool, device_id: String, mount_point: &str, target: &Path) -> Result<Side, AppError> {
+    let prefix = target.strip_prefix(mount_point).unwrap_or(target);
+    let prefix_raw = paths::to_raw(prefix);
+    let scan_id = db::begin_scan(pool, &device_id, &prefix.to_string_lossy(), &prefix_raw, "ingest").await?;
+    Ok(Side {
+        device_id,
+        mount_point: PathBuf::from(mount_point),
+        prefix_raw,
+        scan_id,
+        summary: ScanSummary::default(),
+    })
+}
+
+async fn ingest_files(
+    pool: &DbPool,
+    options: &IngestOptions,
+    source_label: &str,
+    source: &mut Side,
+    dest: &mut Side,
+    sink: &dyn ProgressSink,
+    cancel_token: &CancellationToken,
+) -> Result<IngestReport, AppError> {
+    let manifest_dir = source.mount_point.join(manifest::MANIFEST_DIR);
+    let files: Vec<(PathBuf, u64)> = WalkDir::new(&options.source)
+        .into_iter()
+        .filter_entry(|e| e.path() != manifest_dir)
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|e| {
+            let size = e.metadata().map(|m| m.len()).unwrap_or(0);
+            (e.into_path(), size)
+        })
+        .collect();
+
+    let total = files.len() as u64;
+    let total_bytes: u64 = files.iter().map(|(_, size)| size).sum();
+    sink.emit(ScanEvent::Started { total_files: total, total_bytes });
+    let mut progress = ProgressTracker::new(total, total_bytes);
+
+    let mut report = IngestReport {
+        source_device_id: source.device_id.clone(),
+        destination_device_id: dest.device_id.clone(),
+        files: total,
+        ..Default::default()
+    };
+    let mut seen_paths: HashSet<Vec<u8>> = HashSet::with_capacity(files.len());
+
+    for (file_path, size) in &files {
+        if cancel_token.is_cancelled() {
+            report.cancelled = true;
+            return Ok(report);
+        }
+        if let Some(event) = progress.due(source.summary.scanned) {
+            sink.emit(event);
+        }
+        source.summary.scanned += 1;
+        progress.bytes_processed += size;
+        seen_paths.insert(paths::to_raw(source.relative(file_path)));
+
+        if let Err(e) = ingest_file(pool, options, source_label, source, dest, file_path, &mut report, sink).await {
+            let file_path = source.relative(file_path).to_string_lossy().to_string();
+            sink.emit(ScanEvent::Error {
+                message: format!("{}: {}", file_path, e),
+            });
+            report.issues.push(IngestIssue {
+                file_path,
+                message: e.to_string(),
+            });
+        }
+    }
+    sink.emit(progress.event(source.summary.scanned));
+
+    // Files from an earlier ingest of a card that has since been formatted
+    source.summary.removed =
+        db::remove_stale_locations(pool, source.scan_id, &source.device_id, &source.prefix_raw, &seen_paths).await?;
+    if source.summary.removed > 0 {
+        db::cleanup_orphaned_files(pool).await?;
+    }
+
+    let candidates =
+        db::get_wipe_candidates(pool, &source.device_id, &paths::inside_raw(&source.prefix_raw), None, None).await?;
+    for c in candidates.into_iter().filter(|c| c.backup_copies == 0) {
+        if !report.issues.iter().any(|i| i.file_path == c.file_path) {
+            report.issues.push(IngestIssue {
+                file_path: c.file_path,
+                message: "No hashed copy on a fixed hot or cold device".to_string(),
+            });
+        }
+    }
+    report.safe_to_format = report.issues.is_empty();
+    Ok(report)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn ingest_file(
+    pool: &DbPool,
+    options: &IngestOptions,
+    source_label: &str,
+    source: &mut Side,
+    dest: &mut Side,
+    file_path: &Path,
+    report: &mut IngestReport,
+    sink: &dyn ProgressSink,
+) -> Result<(), AppError> {
+    let metadata = std::fs::metadata(file_path)?;
+    let modified = metadata.modified()?;
+    let hash = hasher::hash_file(file_path).await?;
+    source.summary.hashed += 1;
+
+    catalog_location(pool, source, file_path, &hash, &metadata).await?;
+
+    if db::has_backup_copy(pool, &hash, &source.device_id).await? {
+        report.already_catalogued += 1;
+        return Ok(());
+    }
+
+    let relative = render_template(&options.template, file_path, source_label, modified)?;
+    let mut target = options.destination.join(&relative);
+    let mut suffix = 0;
+    loop {
+        if !target.exists() {
+            break;
+        }
+        // A previous, interrupted ingest may have copied it already
+        if hasher::hash_file(&target).await? == hash {
+            break;
+        }
+        suffix += 1;
+        target = with_suffix(&options.destination.join(&relative), suffix);
+    }
+
+    if !target.exists() {
+        copy_verified(file_path, &target, &hash, modified).await?;
+        report.copied += 1;
+        report.bytes_copied += metadata.len();
+    } else {
+        report.already_present += 1;
+    }
+
+    let dest_metadata = std::fs::metadata(&target)?;
+    catalog_location(pool, dest, &target, &hash, &dest_metadata).await?;
+    dest.summary.scanned += 1;
+    dest.summary.hashed += 1;
+    sink.emit(ScanEvent::FileHashed {
+        path: dest.relative(&target).to_string_lossy().to_string(),
+        hash,
+    });
+    Ok(())
+}
+
+/// Copies through a hidden part file that is hashed before being renamed
+/// into place, so a file at `target` is always a complete, verified copy.
+async fn copy_verified(source: &Path, target: &Path, hash: &str, modified: SystemTime) -> Result<(), AppError> {
+    let parent = target
+        .parent()
+        .ok_or_else(|| AppError::General(format!("Invalid destination: {}", target.display())))?;
+    tokio::fs::create_dir_all(parent).await?;
+    let mut part_name = OsString::from(".");
+    part_name.push(target.file_name().unwrap_or_default());
+    part_name.push(".ingest-part");
+    let part = parent.join(part_name);
+
+    let (src, dst) = (source.to_path_buf(), part.clone());
+    let copied = tokio::task::spawn_blocking(move || -> Result<(), AppError> {
+        std::fs::copy(&src, &dst)?;
+        let file = std::fs::File::options().write(true).open(&dst)?;
+        file.set_modified(modified)?;
+        file.sync_all()?;
+        Ok(())
+    })
+    .await
+    .map_err(|e| AppError::General(e.to_string()))?;
+    if let Err(e) = copied {
+        let _ = tokio::fs::remove_file(&part).await;
+        return Err(e);
+    }
+
+    if hasher::hash_file(&part).await? != hash {
+        let _ = tokio::fs::remove_file(&part).await;
+        return Err(AppError::General("Copy does not match the original".to_string()));
+    }
+    tokio::fs::rename(&part, target).await?;
+    Ok(())
+}
+
+async fn catalog_location(
+    pool: &DbPool,
+    side: &mut Side,
+    path: &Path,
+    hash: &str,
+    metadata: &std::fs::Metadata,
+) -> Result<(), AppError> {
+    let relative = side.relative(path);
+    let relative_raw = paths::to_raw(relative);
+    let relative_path = relative.to_string_lossy().to_string();
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let extension = path.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+    let file_size = metadata.len() as i64;
+    let modified_at = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .and_then(|d| {
+            chrono::DateTime::from_timestamp(d.as_secs() as i64, 0)
+                .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+        });
+
+    let is_new = db::get_existing_location(pool, &side.device_id, &relative_raw).await?.is_none();
+    let file_id = db::upsert_file(pool, hash, file_size, &file_name, &extension).await?;
+    db::upsert_location(
+        pool,
+        side.scan_id,
+        file_id,
+        &side.device_id,
+        &relative_path,
+        &relative_raw,
+        &file_name,
+        file_size,
+        modified_at.as_deref(),
+        "full",
+    )
+    .await?;
+    if is_new {
+        side.summary.added += 1;
+    }
+    Ok(())
+}
+
+/// A camera card keeps its pictures under DCIM, at the path itself or just below it.
+fn has_dcim(path: &Path) -> bool {
+    let is_dcim = |p: &Path| p.file_name().is_some_and(|n| n.eq_ignore_ascii_case("DCIM"));
+    path.ancestors().any(is_dcim)
+        || std::fs::read_dir(path)
+            .map(|entries| entries.filter_map(|e| e.ok()).any(|e| is_dcim(&e.path()) && e.path().is_dir()))
+            .unwrap_or(false)
+}
+
+/// Built as an OS string so a name that isn't valid UTF-8 is carried over
+/// byte for byte.
+fn render_template(template: &str, file: &Path, device_label: &str, modified: SystemTime) -> Result<PathBuf, AppError> {
+    let name = file
+        .file_name()
+        .ok_or_else(|| AppError::General(format!("Not a file: {}", file.display())))?;
+    let stem = file.file_stem().unwrap_or(name);
+    let ext = file.extension().unwrap_or_default();
+    let date: DateTime<Local> = modified.into();
+
+    let mut out = OsString::with_capacity(template.len() + name.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| AppError::General(format!("Unclosed placeholder in template: {}", template)))?;
+        let key = &rest[start + 1..start + end];
+        match key {
+            "year" => out.push(format!("{:04}", date.year())),
+            "month" => out.push(format!("{:02}", date.month())),
+            "day" => out.push(format!("{:02}", date.day())),
+            "yyyy-mm-dd" => out.push(date.format("%Y-%m-%d").to_string()),
+            "original_name" => out.push(name),
+            "stem" => out.push(stem),
+            "ext" => out.push(ext),
+            "device" => out.push(device_label),
+            _ => return Err(AppError::General(format!("Unknown template placeholder: {{{}}}", key))),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    out.push(rest);
+
+    let path = PathBuf::from(out);
+    if path.file_name().is_none() || !path.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(AppError::General(format!(
+            "Template must give a relative path without '..': {}",
+            template
+        )));
+    }
+    Ok(path)
+}
+
+/// `IMG_0001.JPG` -> `IMG_0001-1.JPG`
+fn with_suffix(path: &Path, n: u32) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(format!("-{}", n));
+    if let Some(ext) = path.extension() {
+        name.push(".");
+        name.push(ext);
+    }
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn templates_keep_names_that_are_not_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+        let name = std::ffi::OsStr::from_bytes(b"IMG_\xff01.JPG");
+        let file = Path::new("/card/DCIM").join(name);
+        let path = render_template("{device}/{original_name}", &file, "EOS", SystemTime::UNIX_EPOCH).unwrap();
+        assert_eq!(path.as_os_str().as_bytes(), b"EOS/IMG_\xff01.JPG");
+        assert_eq!(with_suffix(&path, 2).as_os_str().as_bytes(), b"EOS/IMG_\xff01-2.JPG");
+    }
+
+    #[tokio::test]
+    async fn failed_copy_leaves_no_part_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("out/IMG_0001.JPG");
+        let missing = dir.path().join("card/IMG_0001.JPG");
+        assert!(copy_verified(&missing, &target, "00", SystemTime::UNIX_EPOCH).await.is_err());
+        assert_eq!(std::fs::read_dir(dir.path().join("out")).unwrap().count(), 0);
+    }
+}
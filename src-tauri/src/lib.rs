@@ -1,19 +1,35 @@
+#[cfg(feature = "api")]
+pub mod api;
+pub mod capacity;
+pub mod catalog_io;
+pub mod checksums;
+pub mod compare;
+#[cfg(feature = "gui")]
 mod commands;
-mod db;
-mod devices;
-mod error;
-mod hasher;
-mod models;
-mod scanner;
-
-use std::sync::Arc;
-use tauri::Manager;
-use tokio::sync::Mutex;
-
-use commands::AppState;
+pub mod db;
+pub mod devices;
+pub mod directories;
+pub mod error;
+pub mod hasher;
+pub mod ingest;
+pub mod manifest;
+pub mod models;
+pub mod paths;
+pub mod progress;
+pub mod query;
+pub mod scanner;
+pub mod search;
+pub mod snapshot;
+pub mod state;
+pub mod wipe;
 
+#[cfg(feature = "gui")]
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    use tauri::Manager;
+
+    use state::AppState;
+
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
@@ -27,14 +43,21 @@ pub fn run() {
             let rt = tokio::runtime::Runtime::new().expect("failed to create runtime");
             let pool = rt.block_on(async {
                 let pool = db::init_pool(&db_path).await.expect("failed to init db pool");
-                db::run_migrations(&pool).await.expect("failed to run migrations");
+                db::run_migrations(&pool, &db_path).await.expect("failed to run migrations");
                 pool
             });
 
-            app.manage(AppState {
-                pool,
-                cancel_token: Arc::new(Mutex::new(None)),
-            });
+            let state = AppState::new(pool);
+            #[cfg(feature = "api")]
+            if let Some(config) = api::ApiConfig::from_env() {
+                let api_state = state.clone();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = api::serve(api_state, config).await {
+                        log::error!("API server stopped: {}", e);
+                    }
+                });
+            }
+            app.manage(state);
 
             Ok(())
         })
@@ -42,15 +65,45 @@ pub fn run() {
             commands::detect_devices,
             commands::get_devices,
             commands::set_device_type,
+            commands::set_device_site,
             commands::start_scan,
             commands::cancel_scan,
+            commands::ingest_card,
             commands::get_files_on_device,
+            commands::get_folder_listing,
+            commands::get_folder_files,
+            commands::get_non_utf8_files,
+            commands::get_name_conflicts,
             commands::get_file_safety,
             commands::get_unsafe_files,
             commands::get_waste_candidates,
             commands::browse_directory,
             commands::get_file_locations,
+            commands::get_location_history,
+            commands::get_lost_files,
+            commands::get_snapshots,
+            commands::diff_snapshots,
+            commands::export_catalog,
+            commands::import_catalog,
+            commands::write_device_manifest,
+            commands::rebuild_from_manifest,
+            commands::import_checksum_file,
+            commands::export_checksum_file,
+            commands::get_file_digests,
             commands::get_dashboard_stats,
+            commands::get_stats_breakdown,
+            commands::get_capacity_report,
+            commands::get_capacity_history,
+            commands::get_wipe_report,
+            commands::get_overlap_matrix,
+            commands::compare_folders,
+            commands::find_duplicate_folders,
+            commands::search_files,
+            commands::run_catalog_query,
+            commands::run_saved_query,
+            commands::save_query,
+            commands::get_saved_queries,
+            commands::delete_saved_query,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
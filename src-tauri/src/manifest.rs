@@ -0,0 +1,300 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
+
+use crate::db::{self, DbPool, LocationMerge};
+use crate::devices::{detect_volumes, device_for_path};
+use crate::directories;
+use crate::error::AppError;
+use crate::models::{CatalogFile, CatalogImportSummary, CatalogLocation, ManifestSummary};
+use crate::paths;
+
+/// Hidden folder at the device root holding the manifest. Scans skip it.
+pub const MANIFEST_DIR: &str = ".filemanager";
+const MANIFEST_FILE: &str = "manifest.jsonl";
+const FORMAT_NAME: &str = "filemanager-manifest";
+/// Version 2 keys the trailer checksum by device; version 1's is plain BLAKE3.
+const FORMAT_VERSION: u32 = 2;
+const KEY_CONTEXT: &str = "filemanager-manifest 2024 device trailer";
+
+/// One line of a device manifest: a header, one entry per live file, and a
+/// trailer holding the entry count and a BLAKE3 of every line before it,
+/// keyed by the device id.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Record {
+    Header {
+        format: String,
+        version: u32,
+        device_id: String,
+        label: String,
+        written_at: String,
+    },
+    Entry {
+        path_raw: String,
+        file_path: String,
+        file_size: i64,
+        modified_at: Option<String>,
+        blake3_hash: String,
+    },
+    Checksum {
+        entries: u64,
+        blake3: String,
+    },
+}
+
+pub fn manifest_path(mount_point: &Path) -> PathBuf {
+    mount_point.join(MANIFEST_DIR).join(MANIFEST_FILE)
+}
+
+/// Writes the catalog's live view of a device to its manifest. The file is
+/// written beside the old one and renamed over it, so an interrupted write
+/// never leaves a half manifest in place.
+pub async fn write_manifest(pool: &DbPool, device_id: &str, mount_point: &Path) -> Result<ManifestSummary, AppError> {
+    let label = db::get_all_devices(pool)
+        .await?
+        .into_iter()
+        .find(|d| d.id == device_id)
+        .map(|d| d.label)
+        .unwrap_or_default();
+    let locations = db::get_files_on_device(pool, device_id).await?;
+
+    let path = manifest_path(mount_point);
+    let tmp_path = path.with_extension("jsonl.tmp");
+    tokio::fs::create_dir_all(mount_point.join(MANIFEST_DIR)).await?;
+    let mut out = BufWriter::new(tokio::fs::File::create(&tmp_path).await?);
+    let mut checksum = trailer_hasher(FORMAT_VERSION, device_id);
+
+    let header = Record::Header {
+        format: FORMAT_NAME.to_string(),
+        version: FORMAT_VERSION,
+        device_id: device_id.to_string(),
+        label,
+        written_at: chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    };
+    write_record(&mut out, &mut checksum, &header).await?;
+
+    let mut entries = 0u64;
+    for loc in locations {
+        let entry = Record::Entry {
+            path_raw: paths::to_hex(&loc.path_raw),
+            file_path: loc.file_path,
+            file_size: loc.file_size,
+            modified_at: loc.modified_at,
+            blake3_hash: loc.blake3_hash,
+        };
+        write_record(&mut out, &mut checksum, &entry).await?;
+        entries += 1;
+    }
+
+    let trailer = Record::Checksum {
+        entries,
+        blake3: checksum.finalize().to_hex().to_string(),
+    };
+    write_record(&mut out, &mut blake3::Hasher::new(), &trailer).await?;
+    out.flush().await?;
+    drop(out);
+    tokio::fs::rename(&tmp_path, &path).await?;
+
+    Ok(ManifestSummary {
+        device_id: device_id.to_string(),
+        manifest_path: path.to_string_lossy().to_string(),
+        entries,
+    })
+}
+
+/// Recreates a device's catalog entries from the manifest on its root,
+/// without rehashing. The manifest must verify against its checksum and
+/// belong to the volume mounted at `mount_point`; it needs nothing from the
+/// catalog that wrote it, so a new catalog can be rebuilt from its drives.
+pub async fn rebuild_from_manifest(pool: &DbPool, mount_point: &Path) -> Result<CatalogImportSummary, AppError> {
+    let volumes = detect_volumes();
+    let mount_str = mount_point.to_string_lossy().to_string();
+    let (device_id, _) = device_for_path(&volumes, &mount_str)
+        .ok_or_else(|| AppError::General(format!("No device found for path: {}", mount_str)))?;
+    if let Some(disk) = volumes.iter().find(|d| d.id == device_id) {
+        db::upsert_device(pool, disk).await?;
+    }
+    import_manifest(pool, &device_id, &manifest_path(mount_point)).await
+}
+
+/// Merges a verified manifest of `device_id` into the catalog, like a
+/// catalog import, verified as of the time the manifest was written.
+async fn import_manifest(pool: &DbPool, device_id: &str, path: &Path) -> Result<CatalogImportSummary, AppError> {
+    let (manifest_device, written_at, entries) = read_manifest(path).await?;
+    if manifest_device != device_id {
+        return Err(AppError::General(format!(
+            "Manifest belongs to device {}, not {}",
+            manifest_device, device_id
+        )));
+    }
+    let device_id = manifest_device;
+
+    let mut summary = CatalogImportSummary {
+        devices: 1,
+        ..Default::default()
+    };
+    let mut tx = pool.begin().await?;
+    for entry in entries {
+        let Record::Entry { path_raw, file_path, file_size, modified_at, blake3_hash } = entry else {
+            continue;
+        };
+        let raw = paths::from_hex(&path_raw)
+            .ok_or_else(|| AppError::General(format!("Invalid path in manifest: {}", file_path)))?;
+        let name_path = paths::from_raw(&raw);
+        let file_name = name_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let extension = name_path.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+        let scan_mode = if blake3_hash.starts_with("deferred:") { "deferred" } else { "manifest" };
+
+        let file_id = db::import_file(
+            &mut tx,
+            &CatalogFile {
+                id: 0,
+                blake3_hash: blake3_hash.clone(),
+                file_size,
+                representative_name: file_name.clone(),
+                extension,
+            },
+        )
+        .await?;
+        summary.files += 1;
+
+        let loc = CatalogLocation {
+            id: 0,
+            blake3_hash,
+            device_id: device_id.clone(),
+            file_path,
+            path_raw,
+            file_name,
+            file_size,
+            modified_at,
+            last_verified: written_at.clone(),
+            scan_mode: scan_mode.to_string(),
+            removed_at: None,
+        };
+        match db::import_location(&mut tx, file_id, &raw, &loc).await? {
+            LocationMerge::Added => summary.locations_added += 1,
+            LocationMerge::Updated => summary.locations_updated += 1,
+            LocationMerge::Skipped => summary.locations_skipped += 1,
+        }
+    }
+    tx.commit().await?;
+    directories::rebuild_affected(pool, &[&device_id]).await?;
+    db::record_capacity_sample(pool, &device_id).await?;
+    Ok(summary)
+}
+
+/// Checksum over a manifest's lines. Keying by device id means a manifest
+/// copied onto another drive doesn't verify there.
+fn trailer_hasher(version: u32, device_id: &str) -> blake3::Hasher {
+    if version < 2 {
+        blake3::Hasher::new()
+    } else {
+        blake3::Hasher::new_keyed(&blake3::derive_key(KEY_CONTEXT, device_id.as_bytes()))
+    }
+}
+
+/// Reads and verifies a manifest, returning its device id, write time and
+/// entries. Fails if the trailer is missing (truncation), or if the entry
+/// count or checksum doesn't match what precedes it (edits).
+async fn read_manifest(path: &Path) -> Result<(String, String, Vec<Record>), AppError> {
+    let file = tokio::fs::File::open(path)
+        .await
+        .map_err(|e| AppError::General(format!("No manifest at {}: {}", path.display(), e)))?;
+    let mut lines = BufReader::new(file).lines();
+    let invalid = |msg: &str| AppError::General(format!("Manifest is invalid: {}", msg));
+
+    let Some(header_line) = lines.next_line().await? else {
+        return Err(invalid("empty file"));
+    };
+    let (device_id, written_at, mut checksum) = match parse_record(&header_line)? {
+        Record::Header { format, version, device_id, written_at, .. } if format == FORMAT_NAME => {
+            if version > FORMAT_VERSION {
+                return Err(invalid("written by a newer version"));
+            }
+            let checksum = trailer_hasher(version, &device_id);
+            (device_id, written_at, checksum)
+        }
+        _ => return Err(invalid("missing header")),
+    };
+    checksum.update(header_line.as_bytes());
+    checksum.update(b"\n");
+
+    let mut entries = Vec::new();
+    while let Some(line) = lines.next_line().await? {
+        match parse_record(&line)? {
+            Record::Checksum { entries: count, blake3 } => {
+                if lines.next_line().await?.is_some() {
+                    return Err(invalid("data after checksum"));
+                }
+                if count != entries.len() as u64 {
+                    return Err(invalid("entry count does not match"));
+                }
+                if checksum.finalize() != blake3::Hash::from_hex(&blake3).map_err(|_| invalid("malformed checksum"))? {
+                    return Err(invalid("checksum does not match"));
+                }
+                return Ok((device_id, written_at, entries));
+            }
+            entry @ Record::Entry { .. } => {
+                checksum.update(line.as_bytes());
+                checksum.update(b"\n");
+                entries.push(entry);
+            }
+            Record::Header { .. } => return Err(invalid("unexpected header")),
+        }
+    }
+    Err(invalid("truncated, checksum missing"))
+}
+
+async fn write_record(
+    out: &mut BufWriter<tokio::fs::File>,
+    checksum: &mut blake3::Hasher,
+    record: &Record,
+) -> Result<(), AppError> {
+    let mut line = serde_json::to_vec(record).map_err(|e| AppError::General(e.to_string()))?;
+    line.push(b'\n');
+    checksum.update(&line);
+    out.write_all(&line).await?;
+    Ok(())
+}
+
+fn parse_record(line: &str) -> Result<Record, AppError> {
+    serde_json::from_str(line).map_err(|e| AppError::General(format!("Manifest is invalid: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::{add_device, add_location, temp_pool};
+
+    #[tokio::test]
+    async fn a_new_catalog_rebuilds_from_an_unedited_manifest() {
+        let (dir, pool) = temp_pool().await;
+        add_device(&pool, "d1", "cold").await;
+        let scan_id = db::begin_scan(&pool, "d1", "", b"", "full").await.unwrap();
+        add_location(&pool, scan_id, "d1", "Photos/a.jpg").await;
+        add_location(&pool, scan_id, "d1", "Photos/b.jpg").await;
+
+        let mount = dir.path().join("volume");
+        let summary = write_manifest(&pool, "d1", &mount).await.unwrap();
+        assert_eq!(summary.entries, 2);
+        let path = manifest_path(&mount);
+        drop(pool);
+
+        // The catalog that wrote it is gone
+        let (_new_dir, new_pool) = temp_pool().await;
+        add_device(&new_pool, "d1", "cold").await;
+        let rebuilt = import_manifest(&new_pool, "d1", &path).await.unwrap();
+        assert_eq!((rebuilt.files, rebuilt.locations_added), (2, 2));
+        assert!(import_manifest(&new_pool, "d2", &path).await.is_err());
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, text.replace("Photos/b.jpg", "Photos/c.jpg")).unwrap();
+        assert!(read_manifest(&path).await.unwrap_err().to_string().contains("checksum"));
+
+        // Copied onto another drive with its device id changed to match
+        std::fs::write(&path, text.replace("\"d1\"", "\"d2\"")).unwrap();
+        assert!(read_manifest(&path).await.is_err());
+    }
+}
@@ -12,6 +12,9 @@ pub struct StorageDevice {
     pub is_removable: bool,
     pub first_seen: String,
     pub last_seen: String,
+    /// Where the device is kept; exports from before sites existed lack it
+    #[serde(default)]
+    pub site: Option<String>,
     #[sqlx(default)]
     pub is_connected: bool,
 }
@@ -19,14 +22,32 @@ pub struct StorageDevice {
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct FileLocation {
     pub id: i64,
+    pub file_id: i64,
     pub blake3_hash: String,
     pub device_id: String,
     pub file_path: String,
+    #[serde(skip)]
+    pub path_raw: Vec<u8>,
+    pub path_key: String,
     pub file_name: String,
     pub file_size: i64,
     pub modified_at: Option<String>,
     pub last_verified: String,
     pub scan_mode: String,
+    pub removed_at: Option<String>,
+    pub removed_by_scan: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NonUtf8File {
+    pub escaped_path: String,
+    pub location: FileLocation,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NameConflict {
+    pub path_key: String,
+    pub variants: Vec<FileLocation>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -49,6 +70,7 @@ pub struct FileSafety {
     pub cold_copies: i64,
     pub is_safe: bool,
     pub locations: Vec<FileLocation>,
+    pub digests: Vec<FileDigest>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -60,6 +82,36 @@ pub struct WasteCandidate {
     pub wasted_bytes: i64,
 }
 
+/// Paging, sorting and filtering shared by the file listings. Every field
+/// is optional: `sort` names one of the listing's sort fields (its default
+/// when None), `cursor` is the previous page's `next_cursor`, and sizes are
+/// in bytes, dates as stored (`YYYY-MM-DD HH:MM:SS`, UTC).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ListQuery {
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+    pub sort: Option<String>,
+    pub descending: Option<bool>,
+    pub extension: Option<String>,
+    pub min_size: Option<i64>,
+    pub max_size: Option<i64>,
+    pub modified_after: Option<String>,
+    pub modified_before: Option<String>,
+    pub name_contains: Option<String>,
+    /// Only files with a copy on a device of this type
+    pub device_type: Option<String>,
+}
+
+/// One page of a listing, with the number of matches across all pages.
+/// `next_cursor` is None on the last page.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+    pub next_cursor: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DashboardStats {
     pub total_files: i64,
@@ -67,6 +119,89 @@ pub struct DashboardStats {
     pub unsafe_files: i64,
     pub total_devices: i64,
     pub total_size_bytes: i64,
+    pub lost_files: i64,
+}
+
+/// Totals for one bucket of a statistics breakdown. `duplicate_bytes` is the
+/// space taken by copies beyond the first; `unsafe_bytes` is the size of
+/// files with no cold copy or fewer than two copies. Files with only a
+/// placeholder hash are left out of those and counted as `not_hashed_*`.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct StatBucket {
+    pub key: String,
+    pub files: i64,
+    pub unique_bytes: i64,
+    pub duplicate_bytes: i64,
+    pub unsafe_bytes: i64,
+    pub not_hashed_files: i64,
+    pub not_hashed_bytes: i64,
+}
+
+/// Catalogue totals by media category (photo, raw, video, audio, document,
+/// other), extension, year last modified and device type.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsBreakdown {
+    pub by_category: Vec<StatBucket>,
+    pub by_extension: Vec<StatBucket>,
+    pub by_year: Vec<StatBucket>,
+    pub by_device_type: Vec<StatBucket>,
+}
+
+/// A device's capacity on one day, as last reported that day.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct CapacitySample {
+    pub device_id: String,
+    pub sampled_at: String,
+    pub total_bytes: i64,
+    pub available_bytes: i64,
+    pub catalogued_bytes: i64,
+}
+
+/// Growth of a device's used space and when, at that rate, it fills up.
+/// Growth needs samples spanning at least a day; a device whose usage is
+/// flat or shrinking has no fill date.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceForecast {
+    pub device_id: String,
+    pub label: String,
+    pub device_type: String,
+    pub total_bytes: i64,
+    pub available_bytes: i64,
+    pub catalogued_bytes: i64,
+    pub samples: i64,
+    pub growth_bytes_per_day: Option<f64>,
+    pub catalogued_growth_bytes_per_day: Option<f64>,
+    pub days_until_full: Option<f64>,
+    pub full_on: Option<String>,
+}
+
+/// Whether the cold devices' free space (as last reported) can take every
+/// file that has no cold copy yet.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColdCapacity {
+    pub cold_devices: i64,
+    pub available_bytes: i64,
+    pub backlog_files: i64,
+    pub backlog_bytes: i64,
+    pub shortfall_bytes: i64,
+    pub enough_room: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapacityReport {
+    pub devices: Vec<DeviceForecast>,
+    pub cold: ColdCapacity,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct LostFile {
+    pub blake3_hash: String,
+    pub file_size: i64,
+    pub representative_name: String,
+    pub last_device_id: Option<String>,
+    pub last_file_path: Option<String>,
+    pub removed_at: Option<String>,
+    pub removed_by_scan: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -77,12 +212,482 @@ pub struct DirEntry {
     pub modified: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SnapshotInfo {
+    pub scan_id: i64,
+    pub device_id: String,
+    pub scan_prefix: String,
+    pub scan_mode: String,
+    pub entry_count: i64,
+    pub total_bytes: i64,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotChange {
+    pub file_path: String,
+    pub blake3_hash: String,
+    pub file_size: i64,
+    pub previous_hash: Option<String>,
+    pub previous_size: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotMove {
+    pub from_path: String,
+    pub to_path: String,
+    pub blake3_hash: String,
+    pub file_size: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SnapshotDiff {
+    pub from_scan_id: i64,
+    pub to_scan_id: i64,
+    pub added: Vec<SnapshotChange>,
+    pub removed: Vec<SnapshotChange>,
+    pub modified: Vec<SnapshotChange>,
+    pub moved: Vec<SnapshotMove>,
+    pub added_bytes: i64,
+    pub removed_bytes: i64,
+    pub modified_bytes: i64,
+    pub moved_bytes: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct FileDigest {
+    pub algorithm: String,
+    pub digest: String,
+    pub source: String,
+    pub verified_at: Option<String>,
+}
+
+/// A line of an imported checksum file that couldn't be stored as-is.
+/// `kind` is one of `outside_device`, `not_catalogued`, `not_hashed`,
+/// `missing`, `size_mismatch`, `unreadable` (re-hashing failed),
+/// `digest_mismatch` or `conflict`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChecksumIssue {
+    pub file_path: String,
+    pub kind: String,
+    pub algorithm: Option<String>,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChecksumImportReport {
+    pub checksum_file: String,
+    pub device_id: String,
+    pub entries: u64,
+    pub matched: u64,
+    pub verified: u64,
+    pub stored: u64,
+    pub issues: Vec<ChecksumIssue>,
+}
+
+/// An entry of a checksum export worth a look before handing the file over.
+/// `kind` is `stale` (last verified before the threshold), `deferred` (no
+/// real hash yet), `missing_digest`, `missing` (gone from disk when
+/// re-verifying) or `changed` (content no longer matches the catalog).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChecksumExportFlag {
+    pub file_path: String,
+    pub kind: String,
+    pub last_verified: String,
+    pub included: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChecksumExportReport {
+    pub output_path: String,
+    pub format: String,
+    pub written: u64,
+    pub verified: u64,
+    pub flags: Vec<ChecksumExportFlag>,
+}
+
+/// A `files` row in a catalog export. `id` is local to the exporting
+/// catalog and only used for paging.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct CatalogFile {
+    #[serde(skip)]
+    pub id: i64,
+    pub blake3_hash: String,
+    pub file_size: i64,
+    pub representative_name: String,
+    pub extension: String,
+}
+
+/// A `file_locations` row in a catalog export, tombstones included.
+/// `path_raw` is hex-encoded so non-UTF-8 names survive the JSON.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct CatalogLocation {
+    #[serde(skip)]
+    pub id: i64,
+    pub blake3_hash: String,
+    pub device_id: String,
+    pub file_path: String,
+    pub path_raw: String,
+    pub file_name: String,
+    pub file_size: i64,
+    pub modified_at: Option<String>,
+    pub last_verified: String,
+    pub scan_mode: String,
+    pub removed_at: Option<String>,
+}
+
+/// A `digests` row in a catalog export.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct CatalogDigest {
+    #[serde(skip)]
+    pub id: i64,
+    pub blake3_hash: String,
+    pub algorithm: String,
+    pub digest: String,
+    pub source: String,
+    pub verified_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CatalogExportSummary {
+    pub devices: u64,
+    pub files: u64,
+    pub digests: u64,
+    pub locations: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CatalogImportSummary {
+    pub devices: u64,
+    pub files: u64,
+    pub digests: u64,
+    pub locations_added: u64,
+    pub locations_updated: u64,
+    pub locations_skipped: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestSummary {
+    pub device_id: String,
+    pub manifest_path: String,
+    pub entries: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScanSummary {
+    pub scanned: u64,
+    pub hashed: u64,
+    pub added: u64,
+    pub removed: u64,
+    pub cancelled: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ScanEvent {
-    Started { total_files: u64 },
-    Progress { scanned: u64, total: u64 },
+    Started { total_files: u64, total_bytes: u64 },
+    /// `bytes_per_sec` averages over the whole scan so far; `eta_secs` is
+    /// unknown until there's enough elapsed time to estimate from.
+    Progress {
+        scanned: u64,
+        total: u64,
+        bytes_processed: u64,
+        total_bytes: u64,
+        bytes_per_sec: u64,
+        eta_secs: Option<u64>,
+    },
     FileHashed { path: String, hash: String },
     Finished { scanned: u64, hashed: u64, added: u64, removed: u64 },
     Error { message: String },
     Cancelled,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IngestIssue {
+    pub file_path: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IngestReport {
+    pub source_device_id: String,
+    pub destination_device_id: String,
+    pub files: u64,
+    pub copied: u64,
+    pub bytes_copied: u64,
+    /// Content that already has a backup (a hashed copy on a fixed hot or
+    /// cold device); left on the card only
+    pub already_catalogued: u64,
+    /// Verified copies left at the destination by an earlier, interrupted ingest
+    pub already_present: u64,
+    pub issues: Vec<IngestIssue>,
+    pub cancelled: bool,
+    /// Every file on the card has a hashed copy on a fixed hot or cold device
+    pub safe_to_format: bool,
+}
+
+/// A live file on the device being wiped, with counts of its copies on
+/// other devices. See `db::get_wipe_candidates`.
+#[derive(Debug, Clone, FromRow)]
+pub struct WipeCandidate {
+    pub file_path: String,
+    pub blake3_hash: String,
+    pub file_size: i64,
+    pub other_copies: i64,
+    pub off_site_copies: i64,
+    pub verified_copies: i64,
+    /// Copies a camera card may be formatted against
+    pub backup_copies: i64,
+}
+
+/// A file on the device being wiped that isn't safely copied elsewhere.
+/// `kind` is one of no_other_copy, no_backup_copy, not_hashed, same_site_only,
+/// unverified_only.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WipeRiskFile {
+    pub file_path: String,
+    pub blake3_hash: String,
+    pub file_size: i64,
+    pub kind: String,
+    /// Live copies on other devices
+    pub other_copies: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WipeCategory {
+    pub kind: String,
+    pub files: i64,
+    pub bytes: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WipeReport {
+    pub device_id: String,
+    pub prefix: String,
+    pub site: Option<String>,
+    pub total_files: i64,
+    pub total_bytes: i64,
+    /// Files with a verified copy on another device at another site
+    pub safe: WipeCategory,
+    pub at_risk: Vec<WipeCategory>,
+    pub files: Vec<WipeRiskFile>,
+    /// "safe", "caution" (copies exist but only on-site or unverified) or
+    /// "unsafe" (some content exists nowhere else, or only on devices that
+    /// aren't backups)
+    pub verdict: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DeviceContentTotals {
+    pub device_id: String,
+    pub label: String,
+    pub device_type: String,
+    /// Distinct hashed contents with a live copy on the device
+    pub files: i64,
+    pub bytes: i64,
+    /// Of those, the ones no other device has
+    pub unique_files: i64,
+    pub unique_bytes: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DeviceOverlap {
+    pub device_a: String,
+    pub device_b: String,
+    pub shared_files: i64,
+    pub shared_bytes: i64,
+}
+
+/// Everything a device-by-device heatmap needs. `pairs` holds each pair
+/// once; "how much of A is on B" is `shared_bytes` over A's `bytes`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OverlapMatrix {
+    pub devices: Vec<DeviceContentTotals>,
+    pub pairs: Vec<DeviceOverlap>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompareCategory {
+    pub files: i64,
+    pub bytes: i64,
+}
+
+/// Counts per outcome; sizes are the left side's, except for `only_right`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompareSummary {
+    pub identical: CompareCategory,
+    pub moved: CompareCategory,
+    pub different: CompareCategory,
+    pub only_left: CompareCategory,
+    pub only_right: CompareCategory,
+}
+
+/// Paths are relative to the compared prefix.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompareEntry {
+    pub path: String,
+    pub blake3_hash: String,
+    pub file_size: i64,
+}
+
+/// Same content at a different relative path on the right.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MovedEntry {
+    pub left_path: String,
+    pub right_path: String,
+    pub blake3_hash: String,
+    pub file_size: i64,
+}
+
+/// Same relative path, different content.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathConflict {
+    pub path: String,
+    pub left_hash: String,
+    pub left_size: i64,
+    pub right_hash: String,
+    pub right_size: i64,
+}
+
+/// Identical files are only counted, not listed; there are usually far too many.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompareReport {
+    pub left_device: String,
+    pub left_prefix: String,
+    pub right_device: String,
+    pub right_prefix: String,
+    pub summary: CompareSummary,
+    pub moved: Vec<MovedEntry>,
+    pub different: Vec<PathConflict>,
+    pub only_left: Vec<CompareEntry>,
+    pub only_right: Vec<CompareEntry>,
+    /// Paths with a deferred placeholder on either side, which can't be compared
+    pub not_hashed: Vec<String>,
+    /// Everything on the left has an identical or moved copy on the right
+    pub in_sync: bool,
+}
+
+/// A live location with its file's storage-form hash, for building directory trees.
+#[derive(Debug, Clone, FromRow)]
+pub struct LiveEntry {
+    pub device_id: String,
+    pub path_raw: Vec<u8>,
+    pub file_id: i64,
+    pub hash: Vec<u8>,
+    pub file_size: i64,
+    /// Live copies of the content anywhere, and on cold devices
+    pub total_copies: i64,
+    pub cold_copies: i64,
+    /// Live copies of the content on this entry's device
+    pub device_copies: i64,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct DirectoryRow {
+    pub device_id: String,
+    pub path_raw: Vec<u8>,
+    pub path: String,
+    pub parent_raw: Option<Vec<u8>>,
+    pub content_hash: Option<Vec<u8>>,
+    pub file_count: i64,
+    pub total_bytes: i64,
+    pub unsafe_count: i64,
+    pub duplicate_bytes: i64,
+}
+
+/// A folder with recursive totals. `duplicate_bytes` counts files whose
+/// content is also stored elsewhere on the same device.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct FolderStats {
+    pub path: String,
+    pub name: String,
+    pub file_count: i64,
+    pub total_bytes: i64,
+    pub unsafe_count: i64,
+    pub duplicate_bytes: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderListing {
+    pub device_id: String,
+    /// None when nothing is catalogued at or below the path
+    pub folder: Option<FolderStats>,
+    pub children: Vec<FolderStats>,
+}
+
+/// One page of the files directly inside a folder. Pass `next_cursor` back
+/// to get the next page; it's None on the last one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderFilePage {
+    pub files: Vec<FileLocation>,
+    pub total: i64,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateFolder {
+    pub device_id: String,
+    pub path: String,
+    pub file_count: i64,
+    pub total_bytes: i64,
+}
+
+/// Folders with identical trees: same names, same contents.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateFolderGroup {
+    pub content_hash: String,
+    pub folders: Vec<DuplicateFolder>,
+    pub file_count: i64,
+    pub total_bytes: i64,
+    /// Everything but one copy
+    pub reclaimable_bytes: i64,
+}
+
+/// Two folders whose contents mostly overlap. `similarity` is the shared
+/// bytes over the larger folder's bytes, counting each content once.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimilarFolderPair {
+    pub left: DuplicateFolder,
+    pub right: DuplicateFolder,
+    pub similarity: f64,
+    pub shared_bytes: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DuplicateFolderReport {
+    pub exact: Vec<DuplicateFolderGroup>,
+    pub similar: Vec<SimilarFolderPair>,
+    /// From exact duplicates alone
+    pub reclaimable_bytes: i64,
+    /// Shared bytes across similar pairs; pairs can overlap, so this is an upper bound
+    pub similar_shared_bytes: i64,
+}
+
+/// A copy of a search hit, with whether its device is mounted right now.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchLocation {
+    pub location: FileLocation,
+    pub device_label: String,
+    pub device_type: String,
+    pub connected: bool,
+}
+
+/// A file whose name or path matched a search, with every live copy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub blake3_hash: String,
+    pub file_size: i64,
+    pub representative_name: String,
+    pub total_copies: i64,
+    pub cold_copies: i64,
+    pub is_safe: bool,
+    pub locations: Vec<SearchLocation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SavedQuery {
+    pub name: String,
+    pub query: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
@@ -0,0 +1,85 @@
+use std::path::{Path, PathBuf};
+
+use unicode_normalization::UnicodeNormalization;
+
+/// Returns the raw OS bytes of a path, so names that aren't valid UTF-8
+/// survive a round trip through the database.
+#[cfg(unix)]
+pub fn to_raw(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+pub fn to_raw(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+/// Renders raw path bytes for display, showing bytes that aren't valid
+/// UTF-8 as `\xNN` instead of collapsing them into U+FFFD.
+pub fn escape_raw(raw: &[u8]) -> String {
+    let mut out = String::with_capacity(raw.len());
+    for chunk in raw.utf8_chunks() {
+        out.push_str(chunk.valid());
+        for b in chunk.invalid() {
+            out.push_str(&format!("\\x{:02X}", b));
+        }
+    }
+    out
+}
+
+/// Rebuilds a path from bytes produced by `to_raw`.
+#[cfg(unix)]
+pub fn from_raw(raw: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(raw))
+}
+
+#[cfg(not(unix))]
+pub fn from_raw(raw: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(raw).into_owned())
+}
+
+/// Comparison key for a display path: lowercased and NFC-normalized, so the
+/// NFD names macOS writes match the NFC names Linux and exFAT keep, and names
+/// that would clash on a case-insensitive filesystem share a key.
+pub fn path_key(path: &str) -> String {
+    path.to_lowercase().nfc().collect()
+}
+
+/// Raw bytes matching everything under a folder given relative to the mount
+/// point. The trailing `/` keeps `Photos` from also matching `Photos 2`; an
+/// empty prefix matches the whole device.
+pub fn prefix_raw(prefix: &str) -> Vec<u8> {
+    inside_raw(&to_raw(Path::new(prefix.trim_matches('/'))))
+}
+
+/// The same for a folder already given as raw bytes.
+pub fn inside_raw(folder_raw: &[u8]) -> Vec<u8> {
+    let mut raw = folder_raw.to_vec();
+    if !raw.is_empty() && !raw.ends_with(b"/") {
+        raw.push(b'/');
+    }
+    raw
+}
+
+/// Whether a raw path is the folder `folder_raw` or lies inside it.
+pub fn is_within(path_raw: &[u8], folder_raw: &[u8]) -> bool {
+    path_raw == folder_raw || path_raw.starts_with(&inside_raw(folder_raw))
+}
+
+/// Lowercase hex of raw path bytes, for text formats such as catalog
+/// exports and device manifests; also how digests other than BLAKE3 are written.
+pub(crate) fn to_hex(raw: &[u8]) -> String {
+    raw.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
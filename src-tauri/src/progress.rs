@@ -0,0 +1,218 @@
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::models::ScanEvent;
+
+/// Progress goes out every this many files, or sooner while hashing large ones
+const PROGRESS_EVERY_FILES: u64 = 50;
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Receives scan events as they happen. The scanner only talks to this
+/// trait, so the same scan can report to the GUI, a terminal, a log file or
+/// a test.
+pub trait ProgressSink: Send + Sync {
+    fn emit(&self, event: ScanEvent);
+}
+
+/// Forwards events to the frontend over the IPC channel passed to `start_scan`.
+/// A closed channel (window gone) is ignored; the scan carries on.
+#[cfg(feature = "gui")]
+impl ProgressSink for tauri::ipc::Channel<ScanEvent> {
+    fn emit(&self, event: ScanEvent) {
+        let _ = self.send(event);
+    }
+}
+
+/// Keeps every event in order, for tests and for callers that want the
+/// whole record once the scan is over.
+#[derive(Default)]
+pub struct CollectingSink {
+    events: Mutex<Vec<ScanEvent>>,
+}
+
+impl CollectingSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn events(&self) -> Vec<ScanEvent> {
+        self.events.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+impl ProgressSink for CollectingSink {
+    fn emit(&self, event: ScanEvent) {
+        self.events.lock().unwrap_or_else(|e| e.into_inner()).push(event);
+    }
+}
+
+/// Writes events through the `log` crate. Per-file hash events go to debug
+/// so info stays readable for long scans.
+pub struct LogSink;
+
+impl ProgressSink for LogSink {
+    fn emit(&self, event: ScanEvent) {
+        match event {
+            ScanEvent::Started { total_files, total_bytes } => {
+                log::info!("scan started: {} files, {} bytes", total_files, total_bytes)
+            }
+            ScanEvent::Progress { scanned, total, bytes_processed, total_bytes, bytes_per_sec, eta_secs } => {
+                log::info!(
+                    "scan progress: {}/{} files, {}/{} bytes, {} B/s, eta {}",
+                    scanned,
+                    total,
+                    bytes_processed,
+                    total_bytes,
+                    bytes_per_sec,
+                    eta_secs.map(|s| format!("{}s", s)).unwrap_or_else(|| "unknown".to_string())
+                )
+            }
+            ScanEvent::FileHashed { path, hash } => log::debug!("hashed {} {}", hash, path),
+            ScanEvent::Finished { scanned, hashed, added, removed } => {
+                log::info!("scan finished: scanned {}, hashed {}, added {}, removed {}", scanned, hashed, added, removed)
+            }
+            ScanEvent::Error { message } => log::warn!("scan error: {}", message),
+            ScanEvent::Cancelled => log::info!("scan cancelled"),
+        }
+    }
+}
+
+/// Terminal output for the CLI: one JSON event per line on stdout, or a
+/// progress line on stderr with the result on stdout.
+pub struct StdoutSink {
+    pub json: bool,
+}
+
+impl ProgressSink for StdoutSink {
+    fn emit(&self, event: ScanEvent) {
+        if self.json {
+            if let Ok(line) = serde_json::to_string(&event) {
+                println!("{}", line);
+            }
+            return;
+        }
+        match event {
+            ScanEvent::Started { total_files, total_bytes } => {
+                eprintln!("Scanning {} files ({})", total_files, format_bytes(total_bytes))
+            }
+            ScanEvent::Progress { scanned, total, bytes_processed, total_bytes, bytes_per_sec, eta_secs } => {
+                let eta = eta_secs.map(|s| format!(", {} left", format_duration(s))).unwrap_or_default();
+                eprint!(
+                    "\r{}/{} files, {} of {} at {}/s{}\x1b[K",
+                    scanned,
+                    total,
+                    format_bytes(bytes_processed),
+                    format_bytes(total_bytes),
+                    format_bytes(bytes_per_sec),
+                    eta
+                );
+                let _ = std::io::stderr().flush();
+            }
+            ScanEvent::FileHashed { .. } => {}
+            ScanEvent::Error { message } => eprintln!("\r{}\x1b[K", message),
+            ScanEvent::Finished { scanned, hashed, added, removed } => {
+                eprintln!();
+                println!("Scanned {}, hashed {}, added {}, removed {}", scanned, hashed, added, removed);
+            }
+            ScanEvent::Cancelled => {
+                eprintln!();
+                println!("Scan cancelled");
+            }
+        }
+    }
+}
+
+/// Byte-based progress for a scan or ingest. Throughput averages over the
+/// whole run, which smooths out the jumps between quick-mode skips and large
+/// hashes.
+pub(crate) struct ProgressTracker {
+    started: Instant,
+    last_emit: Instant,
+    total: u64,
+    total_bytes: u64,
+    pub(crate) bytes_processed: u64,
+}
+
+impl ProgressTracker {
+    pub(crate) fn new(total: u64, total_bytes: u64) -> Self {
+        let now = Instant::now();
+        ProgressTracker {
+            started: now,
+            last_emit: now,
+            total,
+            total_bytes,
+            bytes_processed: 0,
+        }
+    }
+
+    /// A progress event if enough files or time have passed since the last one.
+    pub(crate) fn due(&mut self, scanned: u64) -> Option<ScanEvent> {
+        let by_count = scanned > 0 && scanned.is_multiple_of(PROGRESS_EVERY_FILES);
+        if by_count || self.last_emit.elapsed() >= PROGRESS_INTERVAL {
+            Some(self.event(scanned))
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn event(&mut self, scanned: u64) -> ScanEvent {
+        self.last_emit = Instant::now();
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let bytes_per_sec = if elapsed > 0.0 { (self.bytes_processed as f64 / elapsed) as u64 } else { 0 };
+        // Files can grow between enumeration and hashing; never report more than the total
+        let bytes_processed = self.bytes_processed.min(self.total_bytes);
+        let eta_secs = (elapsed >= 1.0 && bytes_per_sec > 0)
+            .then(|| (self.total_bytes - bytes_processed).div_ceil(bytes_per_sec));
+        ScanEvent::Progress {
+            scanned,
+            total: self.total,
+            bytes_processed,
+            total_bytes: self.total_bytes,
+            bytes_per_sec,
+            eta_secs,
+        }
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+fn format_duration(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m{:02}s", secs / 60, secs % 60),
+        _ => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eta_follows_average_throughput() {
+        let mut tracker = ProgressTracker::new(4, 4000);
+        assert!(matches!(tracker.event(0), ScanEvent::Progress { eta_secs: None, .. }));
+
+        tracker.started -= Duration::from_secs(2);
+        tracker.bytes_processed = 1000;
+        let ScanEvent::Progress { bytes_per_sec, eta_secs, .. } = tracker.event(1) else {
+            unreachable!()
+        };
+        assert!((490..=500).contains(&bytes_per_sec));
+        assert!(matches!(eta_secs, Some(6..=7)));
+    }
+}
@@ -0,0 +1,313 @@
+use crate::db::{self, DbPool};
+use crate::error::AppError;
+use crate::models::{FileSafety, ListQuery, Page};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cmp {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Cmp {
+    pub fn sql(self) -> &'static str {
+        match self {
+            Cmp::Eq => "=",
+            Cmp::Lt => "<",
+            Cmp::Le => "<=",
+            Cmp::Gt => ">",
+            Cmp::Ge => ">=",
+        }
+    }
+}
+
+/// One `field:value` (or `field>value`, ...) of a catalog query. Name, path,
+/// device and modified terms hold when any live copy matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Term {
+    Extension(Vec<String>),
+    Size(Cmp, i64),
+    Copies(Cmp, i64),
+    HotCopies(Cmp, i64),
+    ColdCopies(Cmp, i64),
+    /// A device type, label or id
+    Device(String),
+    /// A `YYYY`, `YYYY-MM` or `YYYY-MM-DD` prefix, compared at its own precision
+    Modified(Cmp, String),
+    Name(String),
+    Path(String),
+    HashPrefix(String),
+    Safe(bool),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    pub negated: bool,
+    pub term: Term,
+}
+
+/// Parses a catalog query such as `ext:cr3,nef size>20MB device:cold
+/// copies<3 modified:2023`. Terms are separated by spaces and must all
+/// hold; a leading `-` negates one, values with spaces go in quotes, and a
+/// word without a field matches names. Sizes take B, KB, MB, GB or TB
+/// (powers of 1024).
+pub fn parse(input: &str) -> Result<Vec<Condition>, AppError> {
+    let conditions = split_terms(input)?
+        .into_iter()
+        .map(|raw| parse_term(&raw))
+        .collect::<Result<Vec<_>, _>>()?;
+    if conditions.is_empty() {
+        return Err(AppError::General("Empty query".to_string()));
+    }
+    Ok(conditions)
+}
+
+/// Runs a catalog query, a page at a time.
+pub async fn run(pool: &DbPool, text: &str, page: &ListQuery) -> Result<Page<FileSafety>, AppError> {
+    db::run_catalog_query(pool, &parse(text)?, page).await
+}
+
+pub async fn run_saved(pool: &DbPool, name: &str, page: &ListQuery) -> Result<Page<FileSafety>, AppError> {
+    let saved = db::get_saved_query(pool, name)
+        .await?
+        .ok_or_else(|| AppError::General(format!("No saved query named {}", name)))?;
+    run(pool, &saved.query, page).await
+}
+
+/// Saves (or replaces) a named query, refusing one that doesn't parse.
+pub async fn save(pool: &DbPool, name: &str, text: &str) -> Result<(), AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::General("A saved query needs a name".to_string()));
+    }
+    parse(text)?;
+    db::save_query(pool, name, text.trim()).await
+}
+
+/// Splits on whitespace outside double quotes, dropping the quotes.
+fn split_terms(input: &str) -> Result<Vec<String>, AppError> {
+    let mut terms = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in input.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    terms.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if quoted {
+        return Err(AppError::General("Unterminated quote in query".to_string()));
+    }
+    if !current.is_empty() {
+        terms.push(current);
+    }
+    Ok(terms)
+}
+
+fn parse_term(raw: &str) -> Result<Condition, AppError> {
+    let (negated, body) = match raw.strip_prefix('-') {
+        Some(rest) if !rest.is_empty() => (true, rest),
+        _ => (false, raw),
+    };
+    let field_len = body.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(body.len());
+    let (field, rest) = body.split_at(field_len);
+    let (cmp, value) = if let Some(v) = rest.strip_prefix(">=") {
+        (Cmp::Ge, v)
+    } else if let Some(v) = rest.strip_prefix("<=") {
+        (Cmp::Le, v)
+    } else if let Some(v) = rest.strip_prefix('>') {
+        (Cmp::Gt, v)
+    } else if let Some(v) = rest.strip_prefix('<') {
+        (Cmp::Lt, v)
+    } else if let Some(v) = rest.strip_prefix(':').or_else(|| rest.strip_prefix('=')) {
+        (Cmp::Eq, v)
+    } else {
+        // No operator: a bare word
+        return Ok(Condition {
+            negated,
+            term: Term::Name(body.to_string()),
+        });
+    };
+
+    let invalid = |what: &str| AppError::General(format!("Invalid {} in query term '{}'", what, raw));
+    let text_only = |term: Term| if cmp == Cmp::Eq { Ok(term) } else { Err(invalid("comparison")) };
+    if value.is_empty() {
+        return Err(invalid("empty value"));
+    }
+    let term = match field.to_ascii_lowercase().as_str() {
+        "ext" => {
+            let exts: Vec<String> = value
+                .split(',')
+                .map(|e| e.trim_start_matches('.').to_lowercase())
+                .filter(|e| !e.is_empty())
+                .collect();
+            if exts.is_empty() {
+                return Err(invalid("extension"));
+            }
+            text_only(Term::Extension(exts))?
+        }
+        "size" => Term::Size(cmp, parse_size(value).ok_or_else(|| invalid("size"))?),
+        "copies" => Term::Copies(cmp, value.parse().map_err(|_| invalid("count"))?),
+        "hot" => Term::HotCopies(cmp, value.parse().map_err(|_| invalid("count"))?),
+        "cold" => Term::ColdCopies(cmp, value.parse().map_err(|_| invalid("count"))?),
+        "device" => text_only(Term::Device(value.to_string()))?,
+        "modified" => {
+            if !is_date_prefix(value) {
+                return Err(invalid("date (use YYYY, YYYY-MM or YYYY-MM-DD)"));
+            }
+            Term::Modified(cmp, value.to_string())
+        }
+        "name" => text_only(Term::Name(value.to_string()))?,
+        "path" => text_only(Term::Path(value.trim_matches('/').to_string()))?,
+        "hash" => {
+            if !value.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(invalid("hash"));
+            }
+            text_only(Term::HashPrefix(value.to_ascii_lowercase()))?
+        }
+        "is" => match value.to_ascii_lowercase().as_str() {
+            "safe" => text_only(Term::Safe(true))?,
+            "unsafe" => text_only(Term::Safe(false))?,
+            _ => return Err(invalid("value (use is:safe or is:unsafe)")),
+        },
+        _ => {
+            return Err(AppError::General(format!(
+                "Unknown field '{}' in query term '{}' (expected ext, size, copies, hot, cold, device, modified, name, path, hash or is)",
+                field, raw
+            )))
+        }
+    };
+    Ok(Condition { negated, term })
+}
+
+fn parse_size(value: &str) -> Option<i64> {
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let multiplier: i64 = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        "T" | "TB" => 1 << 40,
+        _ => return None,
+    };
+    Some((number * multiplier as f64) as i64)
+}
+
+fn is_date_prefix(value: &str) -> bool {
+    let digits = |s: &str, n: usize| s.len() == n && s.chars().all(|c| c.is_ascii_digit());
+    let parts: Vec<&str> = value.split('-').collect();
+    match parts.as_slice() {
+        [y] => digits(y, 4),
+        [y, m] => digits(y, 4) && digits(m, 2),
+        [y, m, d] => digits(y, 4) && digits(m, 2) && digits(d, 2),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::{add_device, temp_pool};
+
+    fn term(input: &str) -> Term {
+        let mut conditions = parse(input).unwrap();
+        assert_eq!(conditions.len(), 1);
+        conditions.remove(0).term
+    }
+
+    #[test]
+    fn terms_parse_with_units_comparisons_and_date_prefixes() {
+        assert_eq!(term("size>20MB"), Term::Size(Cmp::Gt, 20 << 20));
+        assert_eq!(term("size<=1.5gb"), Term::Size(Cmp::Le, 3 << 29));
+        assert_eq!(term("size:512"), Term::Size(Cmp::Eq, 512));
+        assert_eq!(term("size>=2T"), Term::Size(Cmp::Ge, 2 << 40));
+        assert_eq!(term("copies<3"), Term::Copies(Cmp::Lt, 3));
+        assert_eq!(term("cold>=1"), Term::ColdCopies(Cmp::Ge, 1));
+        assert_eq!(term("modified:2023"), Term::Modified(Cmp::Eq, "2023".to_string()));
+        assert_eq!(term("modified<2023-07-01"), Term::Modified(Cmp::Lt, "2023-07-01".to_string()));
+        assert_eq!(term("ext:.CR3,nef"), Term::Extension(vec!["cr3".to_string(), "nef".to_string()]));
+        assert_eq!(term(r#"path:"/Client Work/""#), Term::Path("Client Work".to_string()));
+        assert_eq!(term("beach"), Term::Name("beach".to_string()));
+
+        let conditions = parse("-device:cold is:unsafe").unwrap();
+        assert_eq!(
+            conditions,
+            [
+                Condition {
+                    negated: true,
+                    term: Term::Device("cold".to_string()),
+                },
+                Condition {
+                    negated: false,
+                    term: Term::Safe(false),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn bad_terms_are_rejected_with_the_term() {
+        for input in [
+            "",
+            "   ",
+            "size>20XB",
+            "size>",
+            "copies<three",
+            "modified:23",
+            "modified:2023-7",
+            "device>cold",
+            "ext:,",
+            "hash:xyz",
+            "is:maybe",
+            "colour:red",
+            r#"name:"open"#,
+        ] {
+            assert!(parse(input).is_err(), "{:?} should not parse", input);
+        }
+        let message = parse("ext:jpg size>big").unwrap_err().to_string();
+        assert!(message.contains("size>big"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn dates_compare_at_the_precision_given() {
+        let (_dir, pool) = temp_pool().await;
+        add_device(&pool, "d1", "hot").await;
+        let scan = db::begin_scan(&pool, "d1", "", b"", "full").await.unwrap();
+        for (name, modified) in [
+            ("a.jpg", "2022-12-31 23:59:59"),
+            ("b.jpg", "2023-06-01 12:00:00"),
+            ("c.jpg", "2024-01-01 00:00:00"),
+        ] {
+            let hash = blake3::hash(name.as_bytes()).to_hex().to_string();
+            let file_id = db::upsert_file(&pool, &hash, 1, name, "jpg").await.unwrap();
+            db::upsert_location(&pool, scan, file_id, "d1", name, name.as_bytes(), name, 1, Some(modified), "full")
+                .await
+                .unwrap();
+        }
+
+        let names = |text: &'static str| {
+            let pool = pool.clone();
+            async move {
+                let page = run(&pool, text, &ListQuery::default()).await.unwrap();
+                let mut names: Vec<String> = page.items.into_iter().map(|f| f.representative_name).collect();
+                names.sort();
+                names
+            }
+        };
+        assert_eq!(names("modified:2023").await, ["b.jpg"]);
+        assert_eq!(names("modified>=2023").await, ["b.jpg", "c.jpg"]);
+        assert_eq!(names("modified<2023-06").await, ["a.jpg"]);
+        assert_eq!(names("-modified:2023 copies<2").await, ["a.jpg", "c.jpg"]);
+    }
+}
@@ -1,23 +1,52 @@
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
-use tauri::ipc::Channel;
 use tokio_util::sync::CancellationToken;
 use walkdir::WalkDir;
 
 use crate::db::{self, DbPool};
 use crate::devices::{detect_volumes, device_for_path};
+use crate::directories;
 use crate::error::AppError;
 use crate::hasher;
-use crate::models::ScanEvent;
+use crate::manifest;
+use crate::paths;
+use crate::models::{ScanEvent, ScanSummary};
+use crate::progress::{ProgressSink, ProgressTracker};
+use crate::snapshot;
 
 const QUICK_HASH_THRESHOLD: u64 = 2 * 1024 * 1024 * 1024; // 2 GB
 
+pub struct ScanOptions {
+    pub mode: String, // "quick" or "full"
+    /// Write the device manifest after a successful scan
+    pub write_manifest: bool,
+    /// Digests to compute alongside BLAKE3, from `hasher::EXTRA_ALGORITHMS`
+    pub extra_digests: Vec<String>,
+}
+
+impl ScanOptions {
+    pub fn validate(&self) -> Result<(), AppError> {
+        if self.mode != "quick" && self.mode != "full" {
+            return Err(AppError::General(format!("Unknown scan mode: {}", self.mode)));
+        }
+        if let Some(unknown) = self
+            .extra_digests
+            .iter()
+            .find(|d| !hasher::EXTRA_ALGORITHMS.contains(&d.as_str()))
+        {
+            return Err(AppError::General(format!("Unsupported digest algorithm: {}", unknown)));
+        }
+        Ok(())
+    }
+}
+
 pub async fn run_scan(
     pool: DbPool,
     target: PathBuf,
-    mode: String, // "quick" or "full"
-    channel: Channel<ScanEvent>,
+    options: ScanOptions,
+    sink: &dyn ProgressSink,
     cancel_token: CancellationToken,
 ) -> Result<(), AppError> {
     // Determine which device this path belongs to
@@ -25,42 +54,133 @@ pub async fn run_scan(
     let target_str = target.to_string_lossy().to_string();
     let (device_id, mount_point) = device_for_path(&volumes, &target_str)
         .ok_or_else(|| AppError::General(format!("No device found for path: {}", target_str)))?;
+    scan_device(pool, device_id, mount_point, target, options, sink, cancel_token).await
+}
+
+/// Scans `target` on a known device mounted at `mount_point`, recording the
+/// scan and reporting it to `sink` from start to finish.
+async fn scan_device(
+    pool: DbPool,
+    device_id: String,
+    mount_point: String,
+    target: PathBuf,
+    options: ScanOptions,
+    sink: &dyn ProgressSink,
+    cancel_token: CancellationToken,
+) -> Result<(), AppError> {
+    let scan_prefix = target.strip_prefix(&mount_point).unwrap_or(&target).to_path_buf();
+    let scan_id = db::begin_scan(
+        &pool,
+        &device_id,
+        &scan_prefix.to_string_lossy(),
+        &paths::to_raw(&scan_prefix),
+        &options.mode,
+    )
+    .await?;
 
-    // Enumerate files first
-    let files: Vec<PathBuf> = WalkDir::new(&target)
+    let scope = ScanScope {
+        scan_id,
+        device_id,
+        mount_point,
+        target,
+        scan_prefix,
+    };
+    match scan_files(&pool, &scope, &options, sink, &cancel_token).await {
+        Ok(summary) if summary.cancelled => {
+            // Folder rows catch up on the next finished scan
+            db::finish_scan(&pool, scan_id, "cancelled", &summary).await?;
+            db::record_capacity_sample(&pool, &scope.device_id).await?;
+            sink.emit(ScanEvent::Cancelled);
+            Ok(())
+        }
+        Ok(summary) => {
+            db::finish_scan(&pool, scan_id, "finished", &summary).await?;
+            snapshot::record_snapshot(&pool, scan_id).await?;
+            directories::rebuild_scanned(&pool, &scope.device_id, &paths::to_raw(&scope.scan_prefix), scan_id).await?;
+            db::record_capacity_sample(&pool, &scope.device_id).await?;
+            if options.write_manifest {
+                // The scan itself succeeded, so a read-only or full device only warns
+                if let Err(e) = manifest::write_manifest(&pool, &scope.device_id, Path::new(&scope.mount_point)).await {
+                    sink.emit(ScanEvent::Error {
+                        message: format!("Could not write device manifest: {}", e),
+                    });
+                }
+            }
+            sink.emit(ScanEvent::Finished {
+                scanned: summary.scanned,
+                hashed: summary.hashed,
+                added: summary.added,
+                removed: summary.removed,
+            });
+            Ok(())
+        }
+        Err(e) => {
+            let _ = db::finish_scan(&pool, scan_id, "failed", &ScanSummary::default()).await;
+            Err(e)
+        }
+    }
+}
+
+struct ScanScope {
+    scan_id: i64,
+    device_id: String,
+    mount_point: String,
+    target: PathBuf,
+    /// Target relative to the mount point
+    scan_prefix: PathBuf,
+}
+
+async fn scan_files(
+    pool: &DbPool,
+    scope: &ScanScope,
+    options: &ScanOptions,
+    sink: &dyn ProgressSink,
+    cancel_token: &CancellationToken,
+) -> Result<ScanSummary, AppError> {
+    let ScanScope { scan_id, device_id, mount_point, target, scan_prefix } = scope;
+    let scan_id = *scan_id;
+    let mode = options.mode.as_str();
+    let extra_digests = &options.extra_digests;
+
+    // Enumerate files first, leaving out our own manifest folder
+    let manifest_dir = Path::new(mount_point).join(manifest::MANIFEST_DIR);
+    let files: Vec<(PathBuf, u64)> = WalkDir::new(target)
         .into_iter()
+        .filter_entry(|e| e.path() != manifest_dir)
         .filter_map(|e| e.ok())
         .filter(|e| e.file_type().is_file())
-        .map(|e| e.into_path())
+        .map(|e| {
+            let size = e.metadata().map(|m| m.len()).unwrap_or(0);
+            (e.into_path(), size)
+        })
         .collect();
 
     let total = files.len() as u64;
-    let _ = channel.send(ScanEvent::Started { total_files: total });
+    let total_bytes: u64 = files.iter().map(|(_, size)| size).sum();
+    sink.emit(ScanEvent::Started { total_files: total, total_bytes });
+    let mut progress = ProgressTracker::new(total, total_bytes);
 
-    let mut scanned: u64 = 0;
-    let mut hashed: u64 = 0;
-    let mut added: u64 = 0;
-    let mut seen_paths: Vec<String> = Vec::with_capacity(files.len());
+    let mut summary = ScanSummary::default();
+    let mut seen_paths: HashSet<Vec<u8>> = HashSet::with_capacity(files.len());
     let is_quick = mode == "quick";
 
-    for file_path in &files {
+    for (file_path, enumerated_size) in &files {
         if cancel_token.is_cancelled() {
-            let _ = channel.send(ScanEvent::Cancelled);
-            return Ok(());
+            summary.cancelled = true;
+            return Ok(summary);
         }
 
-        scanned += 1;
-        if scanned % 50 == 0 || scanned == total {
-            let _ = channel.send(ScanEvent::Progress {
-                scanned,
-                total,
-            });
+        // Report the files finished so far before starting on the next one
+        if let Some(event) = progress.due(summary.scanned) {
+            sink.emit(event);
         }
+        summary.scanned += 1;
+        progress.bytes_processed += enumerated_size;
 
         let metadata = match std::fs::metadata(file_path) {
             Ok(m) => m,
             Err(e) => {
-                let _ = channel.send(ScanEvent::Error {
+                sink.emit(ScanEvent::Error {
                     message: format!("{}: {}", file_path.display(), e),
                 });
                 continue;
@@ -72,17 +192,14 @@ pub async fn run_scan(
             .modified()
             .ok()
             .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
-            .map(|d| {
+            .and_then(|d| {
                 chrono::DateTime::from_timestamp(d.as_secs() as i64, 0)
                     .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
-            })
-            .flatten();
+            });
 
-        let relative_path = file_path
-            .strip_prefix(&mount_point)
-            .unwrap_or(file_path)
-            .to_string_lossy()
-            .to_string();
+        let relative = file_path.strip_prefix(mount_point).unwrap_or(file_path);
+        let relative_raw = paths::to_raw(relative);
+        let relative_path = relative.to_string_lossy().to_string();
         let file_name = file_path
             .file_name()
             .unwrap_or_default()
@@ -94,18 +211,34 @@ pub async fn run_scan(
             .to_string_lossy()
             .to_lowercase();
 
-        seen_paths.push(relative_path.clone());
+        let path_key = paths::path_key(&relative_path);
+        seen_paths.insert(relative_raw.clone());
 
-        // Check if location already exists in DB
-        let existing = db::get_existing_location(&pool, &device_id, &relative_path).await.ok().flatten();
+        // Check if location already exists in DB, falling back to the normalized
+        // key for names re-encoded since the last scan (e.g. NFC vs NFD)
+        let mut existing = db::get_existing_location(pool, device_id, &relative_raw).await.ok().flatten();
+        if existing.is_none() {
+            if let Some(ex) = db::get_location_by_key(pool, device_id, &path_key).await.ok().flatten() {
+                // Only take over the row if its old spelling is gone; both
+                // spellings can legitimately coexist on case-sensitive filesystems
+                if !Path::new(mount_point).join(paths::from_raw(&ex.path_raw)).exists() {
+                    db::rekey_location(pool, ex.id, &relative_path, &relative_raw, &file_name).await?;
+                    existing = Some(ex);
+                }
+            }
+        }
         let is_new = existing.is_none();
 
-        // Quick mode: skip if size+mtime match existing record
+        // Quick mode: skip if size+mtime match existing record, unless
+        // digests were requested that this file doesn't have yet
         if is_quick {
             if let Some(ref ex) = existing {
                 let size_matches = ex.file_size == file_size;
                 let mtime_matches = ex.modified_at.as_deref() == modified_at.as_deref();
-                if size_matches && mtime_matches {
+                if size_matches
+                    && mtime_matches
+                    && !db::is_missing_digests(pool, ex.file_id, extra_digests).await?
+                {
                     continue;
                 }
             }
@@ -119,29 +252,35 @@ pub async fn run_scan(
         };
 
         if should_hash {
-            match hasher::hash_file(file_path).await {
-                Ok(hash) => {
-                    db::upsert_file(&pool, &hash, file_size, &file_name, &extension).await?;
+            match hasher::hash_file_with(file_path, extra_digests).await {
+                Ok(hashes) => {
+                    let hash = hashes.blake3;
+                    let file_id = db::upsert_file(pool, &hash, file_size, &file_name, &extension).await?;
+                    for (algorithm, digest) in &hashes.extra {
+                        db::upsert_digest(pool, file_id, algorithm, digest, "scan", true).await?;
+                    }
                     db::upsert_location(
-                        &pool,
-                        &hash,
-                        &device_id,
+                        pool,
+                        scan_id,
+                        file_id,
+                        device_id,
                         &relative_path,
+                        &relative_raw,
                         &file_name,
                         file_size,
                         modified_at.as_deref(),
-                        &mode,
+                        mode,
                     )
                     .await?;
-                    hashed += 1;
-                    if is_new { added += 1; }
-                    let _ = channel.send(ScanEvent::FileHashed {
+                    summary.hashed += 1;
+                    if is_new { summary.added += 1; }
+                    sink.emit(ScanEvent::FileHashed {
                         path: relative_path,
                         hash,
                     });
                 }
                 Err(e) => {
-                    let _ = channel.send(ScanEvent::Error {
+                    sink.emit(ScanEvent::Error {
                         message: format!("{}: {}", file_path.display(), e),
                     });
                 }
@@ -149,33 +288,125 @@ pub async fn run_scan(
         } else {
             // Deferred: store with a placeholder hash based on metadata
             let placeholder = format!("deferred:{}:{}", file_size, modified_at.as_deref().unwrap_or(""));
-            db::upsert_file(&pool, &placeholder, file_size, &file_name, &extension).await?;
+            let file_id = db::upsert_file(pool, &placeholder, file_size, &file_name, &extension).await?;
             db::upsert_location(
-                &pool,
-                &placeholder,
-                &device_id,
+                pool,
+                scan_id,
+                file_id,
+                device_id,
                 &relative_path,
+                &relative_raw,
                 &file_name,
                 file_size,
                 modified_at.as_deref(),
                 "deferred",
             )
             .await?;
-            if is_new { added += 1; }
+            if is_new { summary.added += 1; }
         }
     }
 
-    // Remove locations for files that no longer exist under scanned path
-    let scan_prefix = target
-        .strip_prefix(&mount_point)
-        .unwrap_or(&target)
-        .to_string_lossy()
-        .to_string();
-    let removed = db::remove_stale_locations(&pool, &device_id, &scan_prefix, &seen_paths).await?;
-    if removed > 0 {
-        db::cleanup_orphaned_files(&pool).await?;
+    sink.emit(progress.event(summary.scanned));
+
+    // Tombstone locations for files that no longer exist under scanned path
+    summary.removed = db::remove_stale_locations(
+        pool,
+        scan_id,
+        device_id,
+        &paths::to_raw(scan_prefix),
+        &seen_paths,
+    )
+    .await?;
+    // Hashing a deferred file supersedes its placeholder without counting as a removal
+    if summary.removed > 0 || summary.hashed > 0 {
+        db::cleanup_orphaned_files(pool).await?;
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::{add_device, temp_pool};
+    use crate::progress::CollectingSink;
+
+    #[tokio::test]
+    async fn scan_reports_start_byte_progress_and_finish() {
+        let (_db_dir, pool) = temp_pool().await;
+        add_device(&pool, "d1", "hot").await;
+        let volume = tempfile::tempdir().unwrap();
+        std::fs::create_dir(volume.path().join("Photos")).unwrap();
+        std::fs::write(volume.path().join("Photos/a.jpg"), vec![1u8; 3000]).unwrap();
+        std::fs::write(volume.path().join("Photos/b.jpg"), vec![2u8; 1000]).unwrap();
+
+        let options = ScanOptions {
+            mode: "full".to_string(),
+            write_manifest: false,
+            extra_digests: Vec::new(),
+        };
+        let sink = CollectingSink::new();
+        let mount_point = volume.path().to_string_lossy().to_string();
+        scan_device(pool, "d1".to_string(), mount_point, volume.path().join("Photos"), options, &sink, CancellationToken::new())
+            .await
+            .unwrap();
+
+        let events: Vec<ScanEvent> =
+            sink.events().into_iter().filter(|e| !matches!(e, ScanEvent::FileHashed { .. })).collect();
+        assert!(matches!(events[0], ScanEvent::Started { total_files: 2, total_bytes: 4000 }));
+        let ScanEvent::Progress { scanned, total, bytes_processed, total_bytes, eta_secs, .. } = events[events.len() - 2] else {
+            panic!("expected progress before the finish, got {:?}", events);
+        };
+        assert_eq!((scanned, total, bytes_processed, total_bytes), (2, 2, 4000, 4000));
+        assert_eq!(eta_secs.unwrap_or(0), 0);
+        assert!(matches!(
+            events[events.len() - 1],
+            ScanEvent::Finished { scanned: 2, hashed: 2, added: 2, removed: 0 }
+        ));
     }
 
-    let _ = channel.send(ScanEvent::Finished { scanned, hashed, added, removed });
-    Ok(())
+    #[tokio::test]
+    async fn rescan_adds_newly_requested_digests_to_unchanged_files() {
+        let (_db_dir, pool) = temp_pool().await;
+        add_device(&pool, "d1", "hot").await;
+        let volume = tempfile::tempdir().unwrap();
+        std::fs::write(volume.path().join("a.jpg"), b"abc").unwrap();
+        let mount_point = volume.path().to_string_lossy().to_string();
+        let sink = CollectingSink::new();
+        let scan = |extra_digests: &[&str]| {
+            let options = ScanOptions {
+                mode: "full".to_string(),
+                write_manifest: false,
+                extra_digests: extra_digests.iter().map(|a| a.to_string()).collect(),
+            };
+            scan_device(
+                pool.clone(),
+                "d1".to_string(),
+                mount_point.clone(),
+                volume.path().to_path_buf(),
+                options,
+                &sink,
+                CancellationToken::new(),
+            )
+        };
+        let hash = blake3::hash(b"abc").to_hex().to_string();
+
+        scan(&[]).await.unwrap();
+        assert!(db::get_file_digests(&pool, &hash).await.unwrap().is_empty());
+
+        scan(&["sha1", "md5"]).await.unwrap();
+        let digests: Vec<(String, String)> = db::get_file_digests(&pool, &hash)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|d| (d.algorithm, d.digest))
+            .collect();
+        assert_eq!(
+            digests,
+            [
+                ("md5".to_string(), "900150983cd24fb0d6963f7d28e17f72".to_string()),
+                ("sha1".to_string(), "a9993e364706816aba3e25717850c26c9cd0d89d".to_string()),
+            ]
+        );
+    }
 }
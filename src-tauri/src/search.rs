@@ -0,0 +1,151 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::db::{self, DbPool};
+use crate::devices::detect_volumes;
+use crate::error::AppError;
+use crate::models::{FileLocation, SearchLocation, SearchResult};
+
+pub const DEFAULT_LIMIT: i64 = 50;
+
+/// Turns what a user types into an FTS5 query. Words must all match, in
+/// the name or anywhere in the path; `"..."` matches a phrase and a
+/// trailing `*` a prefix. Every term is passed as a quoted string, so
+/// characters FTS5 would read as syntax (`-`, `.`, `:`) are just text.
+pub fn to_fts_query(input: &str) -> Result<String, AppError> {
+    let mut terms = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let mut term = String::new();
+        if c == '"' {
+            chars.next();
+            for c in chars.by_ref() {
+                if c == '"' {
+                    break;
+                }
+                term.push(c);
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '"' {
+                    break;
+                }
+                term.push(c);
+                chars.next();
+            }
+        }
+        let mut prefix = chars.next_if_eq(&'*').is_some();
+        if let Some(stripped) = term.strip_suffix('*') {
+            term = stripped.to_string();
+            prefix = true;
+        }
+        if term.trim().is_empty() {
+            continue;
+        }
+        terms.push(format!("\"{}\"{}", term.replace('"', "\"\""), if prefix { "*" } else { "" }));
+    }
+    if terms.is_empty() {
+        return Err(AppError::General("Nothing to search for".to_string()));
+    }
+    Ok(terms.join(" "))
+}
+
+/// Finds files by name or path across every catalogued device, connected or
+/// not. Results are grouped by content, best match first, and list every
+/// live copy so an offline drive's file can be fetched from a mounted one.
+pub async fn search_files(pool: &DbPool, input: &str, limit: i64) -> Result<Vec<SearchResult>, AppError> {
+    if !(1..=db::MAX_PAGE_SIZE).contains(&limit) {
+        return Err(AppError::General(format!("Limit must be between 1 and {}", db::MAX_PAGE_SIZE)));
+    }
+    let files = db::search_files(pool, &to_fts_query(input)?, limit).await?;
+
+    let ids: Vec<i64> = files.iter().map(|(id, ..)| *id).collect();
+    let mut locations: HashMap<i64, Vec<FileLocation>> = HashMap::new();
+    for loc in db::get_locations_for_files(pool, &ids).await? {
+        locations.entry(loc.file_id).or_default().push(loc);
+    }
+    let devices: HashMap<String, (String, String)> = db::get_all_devices(pool)
+        .await?
+        .into_iter()
+        .map(|d| (d.id, (d.label, d.device_type)))
+        .collect();
+    let connected: HashSet<String> = detect_volumes().into_iter().map(|d| d.id).collect();
+
+    Ok(files
+        .into_iter()
+        .map(|(id, blake3_hash, file_size, representative_name)| {
+            let locations: Vec<SearchLocation> = locations
+                .remove(&id)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|location| {
+                    let (device_label, device_type) = devices.get(&location.device_id).cloned().unwrap_or_default();
+                    SearchLocation {
+                        connected: connected.contains(&location.device_id),
+                        device_label,
+                        device_type,
+                        location,
+                    }
+                })
+                .collect();
+            let total_copies = locations.len() as i64;
+            let cold_copies = locations.iter().filter(|l| l.device_type == "cold").count() as i64;
+            SearchResult {
+                blake3_hash,
+                file_size,
+                representative_name,
+                total_copies,
+                cold_copies,
+                is_safe: cold_copies >= 1 && total_copies >= 2,
+                locations,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::{add_device, add_location, temp_pool};
+
+    #[test]
+    fn every_term_is_quoted_so_fts_syntax_is_just_text() {
+        assert_eq!(to_fts_query("beach  2023").unwrap(), r#""beach" "2023""#);
+        assert_eq!(to_fts_query(r#""summer trip" img*"#).unwrap(), r#""summer trip" "img"*"#);
+        assert_eq!(to_fts_query(r#""sum"* DSC_*"#).unwrap(), r#""sum"* "DSC_"*"#);
+        assert_eq!(
+            to_fts_query("a OR b NEAR(c) -d name:e").unwrap(),
+            r#""a" "OR" "b" "NEAR(c)" "-d" "name:e""#
+        );
+        // A stray quote ends a word and opens a phrase that runs to the end
+        assert_eq!(to_fts_query(r#"it"s here"#).unwrap(), r#""it" "s here""#);
+        assert_eq!(to_fts_query(r#"say ""hi"""#).unwrap(), r#""say" "hi""#);
+        for input in ["", "   ", "*", r#""""#, r#"" ""#] {
+            assert!(to_fts_query(input).is_err(), "{:?}", input);
+        }
+    }
+
+    #[tokio::test]
+    async fn names_with_fts_operators_are_found_literally() {
+        let (_dir, pool) = temp_pool().await;
+        add_device(&pool, "d1", "hot").await;
+        let scan = db::begin_scan(&pool, "d1", "", b"", "full").await.unwrap();
+        add_location(&pool, scan, "d1", "Trip-2023/IMG_0001.JPG").await;
+        add_location(&pool, scan, "d1", "Docs/OR notes.txt").await;
+
+        let names = |input: &'static str| {
+            let pool = pool.clone();
+            async move {
+                let results = search_files(&pool, input, 10).await.unwrap();
+                results.into_iter().map(|r| r.representative_name).collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(names("trip-2023").await, ["Trip-2023/IMG_0001.JPG"]);
+        assert_eq!(names("img_00*").await, ["Trip-2023/IMG_0001.JPG"]);
+        assert_eq!(names("OR").await, ["Docs/OR notes.txt"]);
+        assert!(names("NEAR(trip").await.is_empty());
+    }
+}
@@ -0,0 +1,428 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::db::{self, DbPool};
+use crate::error::AppError;
+use crate::hasher;
+use crate::models::{SnapshotChange, SnapshotDiff, SnapshotMove};
+use crate::paths;
+
+const FORMAT_VERSION: u8 = 1;
+const DELTA_FORMAT_VERSION: u8 = 2;
+
+/// Deltas stored in a row before a snapshot is stored whole again, so
+/// loading one never replays more than this many.
+pub const MAX_DELTA_CHAIN: usize = 16;
+
+/// One file in a snapshot. `hash` is the storage form from `hasher::hash_key`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotEntry {
+    pub path_raw: Vec<u8>,
+    pub hash: Vec<u8>,
+    pub size: i64,
+}
+
+/// Encodes entries sorted by `path_raw`. Paths are front-coded against the
+/// previous entry, which keeps deep folder trees small; each record is
+/// `shared prefix len, suffix len, suffix, hash len, hash, size` as varints.
+pub fn encode(entries: &[SnapshotEntry]) -> Vec<u8> {
+    let mut out = vec![FORMAT_VERSION];
+    let mut prev: &[u8] = &[];
+    for entry in entries {
+        write_entry(&mut out, prev, entry);
+        prev = &entry.path_raw;
+    }
+    out
+}
+
+/// Encodes how `entries` differ from `base`, both sorted by `path_raw`: the
+/// number of paths gone since, those paths front-coded, then the entries
+/// added or changed as records like `encode`'s.
+pub fn encode_delta(base: &[SnapshotEntry], entries: &[SnapshotEntry]) -> Vec<u8> {
+    let mut removed: Vec<&[u8]> = Vec::new();
+    let mut changed: Vec<&SnapshotEntry> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    loop {
+        match (base.get(i), entries.get(j)) {
+            (Some(old), Some(new)) if old.path_raw == new.path_raw => {
+                if old.hash != new.hash || old.size != new.size {
+                    changed.push(new);
+                }
+                i += 1;
+                j += 1;
+            }
+            (Some(old), Some(new)) if old.path_raw < new.path_raw => {
+                removed.push(&old.path_raw);
+                i += 1;
+            }
+            (Some(old), None) => {
+                removed.push(&old.path_raw);
+                i += 1;
+            }
+            (_, Some(new)) => {
+                changed.push(new);
+                j += 1;
+            }
+            (None, None) => break,
+        }
+    }
+
+    let mut out = vec![DELTA_FORMAT_VERSION];
+    write_varint(&mut out, removed.len() as u64);
+    let mut prev: &[u8] = &[];
+    for path in removed {
+        write_path(&mut out, prev, path);
+        prev = path;
+    }
+    let mut prev: &[u8] = &[];
+    for entry in changed {
+        write_entry(&mut out, prev, entry);
+        prev = &entry.path_raw;
+    }
+    out
+}
+
+pub fn decode(data: &[u8]) -> Result<Vec<SnapshotEntry>, AppError> {
+    check_version(data, FORMAT_VERSION)?;
+    let mut pos = 1;
+    let mut entries: Vec<SnapshotEntry> = Vec::new();
+    while pos < data.len() {
+        let prev = entries.last().map(|e| e.path_raw.as_slice()).unwrap_or(&[]);
+        entries.push(read_entry(data, &mut pos, prev).ok_or_else(corrupt)?);
+    }
+    Ok(entries)
+}
+
+/// Applies a delta from `encode_delta` to the entries it was taken against.
+pub fn apply_delta(base: Vec<SnapshotEntry>, data: &[u8]) -> Result<Vec<SnapshotEntry>, AppError> {
+    check_version(data, DELTA_FORMAT_VERSION)?;
+    let mut pos = 1;
+    let removed_count = read_varint(data, &mut pos).ok_or_else(corrupt)?;
+    let mut gone: HashSet<Vec<u8>> = HashSet::new();
+    let mut prev: Vec<u8> = Vec::new();
+    for _ in 0..removed_count {
+        let path = read_path(data, &mut pos, &prev).ok_or_else(corrupt)?;
+        prev = path.clone();
+        gone.insert(path);
+    }
+    let mut changed: Vec<SnapshotEntry> = Vec::new();
+    while pos < data.len() {
+        let prev = changed.last().map(|e| e.path_raw.as_slice()).unwrap_or(&[]);
+        let entry = read_entry(data, &mut pos, prev).ok_or_else(corrupt)?;
+        gone.insert(entry.path_raw.clone());
+        changed.push(entry);
+    }
+
+    let mut entries: Vec<SnapshotEntry> = base.into_iter().filter(|e| !gone.contains(&e.path_raw)).collect();
+    entries.extend(changed);
+    entries.sort_by(|a, b| a.path_raw.cmp(&b.path_raw));
+    Ok(entries)
+}
+
+/// Stores the live contents under a finished scan's prefix as its snapshot:
+/// as a delta against the device's previous snapshot of the same folder
+/// when that's smaller and the chain of deltas is still short, whole otherwise.
+pub async fn record_snapshot(pool: &DbPool, scan_id: i64) -> Result<(), AppError> {
+    let (device_id, prefix_raw, entries) = db::get_live_snapshot_entries(pool, scan_id).await?;
+    let mut data = encode(&entries);
+    let mut base_scan_id = None;
+    if let Some(previous) = db::get_previous_snapshot_id(pool, &device_id, &prefix_raw, scan_id).await? {
+        if let Some((_, _, base, depth)) = load(pool, previous).await? {
+            let delta = encode_delta(&base, &entries);
+            if depth < MAX_DELTA_CHAIN && delta.len() < data.len() {
+                data = delta;
+                base_scan_id = Some(previous);
+            }
+        }
+    }
+    let total_bytes: i64 = entries.iter().map(|e| e.size).sum();
+    db::insert_snapshot(pool, scan_id, &device_id, entries.len() as i64, total_bytes, base_scan_id, &data).await
+}
+
+/// Device id, raw scan prefix and entries of a stored snapshot, replaying
+/// deltas from the nearest whole snapshot, and how many deltas that took.
+pub async fn load(pool: &DbPool, scan_id: i64) -> Result<Option<(String, Vec<u8>, Vec<SnapshotEntry>, usize)>, AppError> {
+    let Some((device_id, prefix_raw, mut base_scan_id, data)) = db::get_snapshot_data(pool, scan_id).await? else {
+        return Ok(None);
+    };
+    let mut deltas = Vec::new();
+    let mut current = scan_id;
+    let mut data = data;
+    while let Some(base) = base_scan_id {
+        // Deltas only ever point back, so this can't go round in circles
+        if base >= current {
+            return Err(corrupt());
+        }
+        deltas.push(data);
+        let (_, _, next_base, next_data) = db::get_snapshot_data(pool, base)
+            .await?
+            .ok_or_else(|| AppError::General(format!("Snapshot {} is missing its base {}", current, base)))?;
+        (current, base_scan_id, data) = (base, next_base, next_data);
+    }
+    let depth = deltas.len();
+    let mut entries = decode(&data)?;
+    for delta in deltas.iter().rev() {
+        entries = apply_delta(entries, delta)?;
+    }
+    Ok(Some((device_id, prefix_raw, entries, depth)))
+}
+
+/// Compares two snapshots of the same tree. A path whose hash changed is
+/// modified; a removed path whose content shows up at an added path is a move.
+pub fn diff(from_scan_id: i64, to_scan_id: i64, old: &[SnapshotEntry], new: &[SnapshotEntry]) -> SnapshotDiff {
+    let old_by_path: HashMap<&[u8], &SnapshotEntry> = old.iter().map(|e| (e.path_raw.as_slice(), e)).collect();
+    let new_by_path: HashMap<&[u8], &SnapshotEntry> = new.iter().map(|e| (e.path_raw.as_slice(), e)).collect();
+
+    let mut result = SnapshotDiff {
+        from_scan_id,
+        to_scan_id,
+        ..Default::default()
+    };
+
+    let mut added: Vec<&SnapshotEntry> = Vec::new();
+    for entry in new {
+        match old_by_path.get(entry.path_raw.as_slice()) {
+            None => added.push(entry),
+            Some(prev) if prev.hash != entry.hash => {
+                result.modified_bytes += entry.size;
+                result.modified.push(SnapshotChange {
+                    file_path: display(&entry.path_raw),
+                    blake3_hash: hasher::hash_hex(&entry.hash),
+                    file_size: entry.size,
+                    previous_hash: Some(hasher::hash_hex(&prev.hash)),
+                    previous_size: Some(prev.size),
+                });
+            }
+            Some(_) => {}
+        }
+    }
+
+    // Removed entries keyed by content, so added ones can claim them as moves
+    let mut removed_by_hash: HashMap<&[u8], Vec<&SnapshotEntry>> = HashMap::new();
+    for entry in old {
+        if !new_by_path.contains_key(entry.path_raw.as_slice()) {
+            removed_by_hash.entry(entry.hash.as_slice()).or_default().push(entry);
+        }
+    }
+
+    for entry in added {
+        let source = removed_by_hash.get_mut(entry.hash.as_slice()).and_then(|v| v.pop());
+        match source {
+            Some(from) => {
+                result.moved_bytes += entry.size;
+                result.moved.push(SnapshotMove {
+                    from_path: display(&from.path_raw),
+                    to_path: display(&entry.path_raw),
+                    blake3_hash: hasher::hash_hex(&entry.hash),
+                    file_size: entry.size,
+                });
+            }
+            None => {
+                result.added_bytes += entry.size;
+                result.added.push(change(entry));
+            }
+        }
+    }
+
+    let mut removed: Vec<&SnapshotEntry> = removed_by_hash.into_values().flatten().collect();
+    removed.sort_by(|a, b| a.path_raw.cmp(&b.path_raw));
+    for entry in removed {
+        result.removed_bytes += entry.size;
+        result.removed.push(change(entry));
+    }
+
+    result
+}
+
+/// Loads two snapshots of a device and diffs them. If they were taken with
+/// different scan prefixes, only the deeper prefix's subtree is compared.
+pub async fn diff_snapshots(pool: &DbPool, from_scan_id: i64, to_scan_id: i64) -> Result<SnapshotDiff, AppError> {
+    let missing = |id: i64| AppError::General(format!("No snapshot for scan {}", id));
+    let (from_device, from_prefix, mut old, _) = load(pool, from_scan_id).await?.ok_or_else(|| missing(from_scan_id))?;
+    let (to_device, to_prefix, mut new, _) = load(pool, to_scan_id).await?.ok_or_else(|| missing(to_scan_id))?;
+
+    if from_device != to_device {
+        return Err(AppError::General("Snapshots belong to different devices".to_string()));
+    }
+    let prefix = if paths::is_within(&from_prefix, &to_prefix) {
+        from_prefix
+    } else if paths::is_within(&to_prefix, &from_prefix) {
+        to_prefix
+    } else {
+        return Err(AppError::General("Snapshots cover unrelated folders".to_string()));
+    };
+
+    old.retain(|e| paths::is_within(&e.path_raw, &prefix));
+    new.retain(|e| paths::is_within(&e.path_raw, &prefix));
+    Ok(diff(from_scan_id, to_scan_id, &old, &new))
+}
+
+fn change(entry: &SnapshotEntry) -> SnapshotChange {
+    SnapshotChange {
+        file_path: display(&entry.path_raw),
+        blake3_hash: hasher::hash_hex(&entry.hash),
+        file_size: entry.size,
+        previous_hash: None,
+        previous_size: None,
+    }
+}
+
+fn display(path_raw: &[u8]) -> String {
+    String::from_utf8_lossy(path_raw).into_owned()
+}
+
+fn corrupt() -> AppError {
+    AppError::General("Snapshot data is corrupt".to_string())
+}
+
+fn check_version(data: &[u8], expected: u8) -> Result<(), AppError> {
+    match data.first() {
+        Some(&v) if v == expected => Ok(()),
+        Some(v) => Err(AppError::General(format!("Unsupported snapshot format {}", v))),
+        None => Err(corrupt()),
+    }
+}
+
+fn write_path(out: &mut Vec<u8>, prev: &[u8], path: &[u8]) {
+    let shared = prev.iter().zip(path).take_while(|(a, b)| a == b).count();
+    let suffix = &path[shared..];
+    write_varint(out, shared as u64);
+    write_varint(out, suffix.len() as u64);
+    out.extend_from_slice(suffix);
+}
+
+fn write_entry(out: &mut Vec<u8>, prev: &[u8], entry: &SnapshotEntry) {
+    write_path(out, prev, &entry.path_raw);
+    write_varint(out, entry.hash.len() as u64);
+    out.extend_from_slice(&entry.hash);
+    write_varint(out, entry.size as u64);
+}
+
+fn read_path(data: &[u8], pos: &mut usize, prev: &[u8]) -> Option<Vec<u8>> {
+    let shared = read_varint(data, pos)? as usize;
+    let suffix_len = read_varint(data, pos)? as usize;
+    let suffix = take(data, pos, suffix_len)?;
+    let mut path = prev.get(..shared)?.to_vec();
+    path.extend_from_slice(suffix);
+    Some(path)
+}
+
+fn read_entry(data: &[u8], pos: &mut usize, prev: &[u8]) -> Option<SnapshotEntry> {
+    let path_raw = read_path(data, pos, prev)?;
+    let hash_len = read_varint(data, pos)? as usize;
+    let hash = take(data, pos, hash_len)?.to_vec();
+    let size = read_varint(data, pos)? as i64;
+    Some(SnapshotEntry { path_raw, hash, size })
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut value: u64 = 0;
+    let mut shift = 0;
+    loop {
+        let byte = *data.get(*pos)?;
+        *pos += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+        shift += 7;
+        if shift >= 64 {
+            return None;
+        }
+    }
+}
+
+fn take<'a>(data: &'a [u8], pos: &mut usize, len: usize) -> Option<&'a [u8]> {
+    let slice = data.get(*pos..pos.checked_add(len)?)?;
+    *pos += len;
+    Some(slice)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::{add_device, add_location, temp_pool};
+
+    #[tokio::test]
+    async fn scoped_snapshots_leave_sibling_folders_out() {
+        let (_dir, pool) = temp_pool().await;
+        add_device(&pool, "d1", "hot").await;
+        let whole = db::begin_scan(&pool, "d1", "", b"", "full").await.unwrap();
+        for path in ["Photos/a.jpg", "Photos 2/b.jpg", "Photos.bak/c.jpg"] {
+            add_location(&pool, whole, "d1", path).await;
+        }
+        record_snapshot(&pool, whole).await.unwrap();
+
+        // A later scan of Photos only, after a sibling folder changed
+        add_location(&pool, whole, "d1", "Photos 2/new.jpg").await;
+        let scoped = db::begin_scan(&pool, "d1", "Photos", b"Photos", "full").await.unwrap();
+        record_snapshot(&pool, scoped).await.unwrap();
+
+        let (_, _, entries, _) = load(&pool, scoped).await.unwrap().unwrap();
+        let paths: Vec<Vec<u8>> = entries.into_iter().map(|e| e.path_raw).collect();
+        assert_eq!(paths, [b"Photos/a.jpg".to_vec()]);
+
+        let diff = diff_snapshots(&pool, whole, scoped).await.unwrap();
+        assert!(diff.added.is_empty() && diff.removed.is_empty(), "{:?}", diff);
+    }
+
+    fn entry(path: &str, content: &str) -> SnapshotEntry {
+        SnapshotEntry {
+            path_raw: path.as_bytes().to_vec(),
+            hash: hasher::hash_key(&blake3::hash(content.as_bytes()).to_hex()),
+            size: content.len() as i64,
+        }
+    }
+
+    #[test]
+    fn deltas_replay_removals_changes_and_additions() {
+        let base = vec![entry("a/1.jpg", "1"), entry("a/2.jpg", "2"), entry("b/3.jpg", "3"), entry("c.jpg", "c")];
+        let next = vec![entry("a/1.jpg", "1"), entry("a/2.jpg", "edited"), entry("a/4.jpg", "4"), entry("c.jpg", "c")];
+        let delta = encode_delta(&base, &next);
+        assert!(delta.len() < encode(&next).len());
+        assert_eq!(apply_delta(base.clone(), &delta).unwrap(), next);
+        assert_eq!(apply_delta(next.clone(), &encode_delta(&next, &[])).unwrap(), []);
+        // Neither format reads as the other
+        assert!(decode(&delta).is_err());
+        assert!(apply_delta(base, &encode(&next)).is_err());
+    }
+
+    #[tokio::test]
+    async fn rescans_store_deltas_up_to_the_chain_limit() {
+        let (_dir, pool) = temp_pool().await;
+        add_device(&pool, "d1", "hot").await;
+        let first = db::begin_scan(&pool, "d1", "", b"", "full").await.unwrap();
+        for n in 0..20 {
+            add_location(&pool, first, "d1", &format!("Photos/{:02}.jpg", n)).await;
+        }
+        record_snapshot(&pool, first).await.unwrap();
+
+        let mut scans = vec![first];
+        for n in 0..=MAX_DELTA_CHAIN {
+            let scan = db::begin_scan(&pool, "d1", "", b"", "full").await.unwrap();
+            add_location(&pool, scan, "d1", &format!("New/{:02}.jpg", n)).await;
+            record_snapshot(&pool, scan).await.unwrap();
+            scans.push(scan);
+        }
+        let depth = |scan_id: i64| {
+            let pool = pool.clone();
+            async move { load(&pool, scan_id).await.unwrap().unwrap().3 }
+        };
+        assert_eq!(depth(first).await, 0);
+        assert_eq!(depth(scans[MAX_DELTA_CHAIN]).await, MAX_DELTA_CHAIN);
+        // The next one is stored whole again
+        assert_eq!(depth(scans[MAX_DELTA_CHAIN + 1]).await, 0);
+
+        let (_, _, entries, _) = load(&pool, scans[3]).await.unwrap().unwrap();
+        assert_eq!(entries.len(), 23);
+        let diff = diff_snapshots(&pool, first, scans[MAX_DELTA_CHAIN + 1]).await.unwrap();
+        assert_eq!((diff.added.len(), diff.removed.len()), (MAX_DELTA_CHAIN + 1, 0));
+    }
+}
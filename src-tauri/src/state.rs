@@ -0,0 +1,177 @@
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+use tokio::task::{AbortHandle, JoinHandle};
+use tokio_util::sync::CancellationToken;
+
+use crate::db::DbPool;
+use crate::error::AppError;
+use crate::ingest::{self, IngestOptions};
+use crate::models::{IngestReport, ScanEvent};
+use crate::progress::ProgressSink;
+use crate::scanner::{self, ScanOptions};
+
+/// State shared by every front end: the Tauri commands, the CLI and the
+/// HTTP API see the same pool and the same running scan.
+#[derive(Clone)]
+pub struct AppState {
+    pub pool: DbPool,
+    pub cancel_token: Arc<Mutex<Option<CancellationToken>>>,
+    scan: Arc<Mutex<Option<AbortHandle>>>,
+    last_event: Arc<std::sync::Mutex<Option<ScanEvent>>>,
+}
+
+impl AppState {
+    pub fn new(pool: DbPool) -> Self {
+        AppState {
+            pool,
+            cancel_token: Arc::new(Mutex::new(None)),
+            scan: Arc::new(Mutex::new(None)),
+            last_event: Arc::new(std::sync::Mutex::new(None)),
+        }
+    }
+
+    /// Starts a scan in the background and returns once it's running, or
+    /// `AppError::ScanRunning` if one already is. A failure inside the scan
+    /// (e.g. no device for the path) is reported to `sink` as an error event
+    /// and is also the task's result.
+    pub async fn start_scan(
+        &self,
+        target: PathBuf,
+        options: ScanOptions,
+        sink: impl ProgressSink + 'static,
+    ) -> Result<JoinHandle<Result<(), AppError>>, AppError> {
+        options.validate()?;
+        self.spawn_in_slot(sink, move |pool, sink, cancel_token| async move {
+            let result = scanner::run_scan(pool, target, options, &sink, cancel_token).await;
+            if let Err(e) = &result {
+                sink.emit(ScanEvent::Error {
+                    message: e.to_string(),
+                });
+            }
+            result
+        })
+        .await
+    }
+
+    /// Starts offloading a card in the background. An ingest takes the same
+    /// slot as a scan: it catalogs both devices, so it can't run beside one.
+    pub async fn start_ingest(
+        &self,
+        mut options: IngestOptions,
+        sink: impl ProgressSink + 'static,
+    ) -> Result<JoinHandle<Result<IngestReport, AppError>>, AppError> {
+        options.source = std::fs::canonicalize(&options.source)?;
+        options.destination = std::fs::canonicalize(&options.destination)?;
+        self.spawn_in_slot(sink, move |pool, sink, cancel_token| async move {
+            ingest::ingest(&pool, &options, &sink, &cancel_token).await
+        })
+        .await
+    }
+
+    async fn spawn_in_slot<S, T, F>(
+        &self,
+        sink: S,
+        task: impl FnOnce(DbPool, RecordingSink<S>, CancellationToken) -> F,
+    ) -> Result<JoinHandle<Result<T, AppError>>, AppError>
+    where
+        S: ProgressSink + 'static,
+        T: Send + 'static,
+        F: Future<Output = Result<T, AppError>> + Send + 'static,
+    {
+        // Held until the new task is registered, so two callers can't both
+        // see an idle slot and start a task each
+        let mut running = self.scan.lock().await;
+        if running.as_ref().is_some_and(|t| !t.is_finished()) {
+            return Err(AppError::ScanRunning);
+        }
+        *self.last_event.lock().unwrap_or_else(|e| e.into_inner()) = None;
+
+        let cancel_token = self.new_cancel_token().await;
+        let sink = RecordingSink {
+            inner: sink,
+            last_event: self.last_event.clone(),
+        };
+        let task = tokio::spawn(task(self.pool.clone(), sink, cancel_token));
+        *running = Some(task.abort_handle());
+        Ok(task)
+    }
+
+    /// Occupies the scan slot with `task`, as a long scan would.
+    #[cfg(test)]
+    pub(crate) async fn occupy_scan_slot(&self, task: AbortHandle) {
+        *self.scan.lock().await = Some(task);
+    }
+
+    /// Whether a scan or ingest is running, and the last event it reported.
+    pub async fn scan_status(&self) -> (bool, Option<ScanEvent>) {
+        let running = self.scan.lock().await.as_ref().is_some_and(|t| !t.is_finished());
+        let last_event = self.last_event.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        (running, last_event)
+    }
+
+    /// Token for a new scan or ingest; `cancel_scan` cancels whichever started last.
+    pub async fn new_cancel_token(&self) -> CancellationToken {
+        let cancel_token = CancellationToken::new();
+        let mut guard = self.cancel_token.lock().await;
+        *guard = Some(cancel_token.clone());
+        cancel_token
+    }
+
+    pub async fn cancel_scan(&self) {
+        let guard = self.cancel_token.lock().await;
+        if let Some(token) = guard.as_ref() {
+            token.cancel();
+        }
+    }
+}
+
+/// Passes events on and keeps the latest one for `scan_status`.
+struct RecordingSink<S> {
+    inner: S,
+    last_event: Arc<std::sync::Mutex<Option<ScanEvent>>>,
+}
+
+impl<S: ProgressSink> ProgressSink for RecordingSink<S> {
+    fn emit(&self, event: ScanEvent) {
+        // Per-file events would only churn the lock; progress covers them
+        if !matches!(event, ScanEvent::FileHashed { .. }) {
+            *self.last_event.lock().unwrap_or_else(|e| e.into_inner()) = Some(event.clone());
+        }
+        self.inner.emit(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::temp_pool;
+    use crate::progress::CollectingSink;
+
+    #[tokio::test]
+    async fn ingest_waits_for_a_running_scan() {
+        let (dir, pool) = temp_pool().await;
+        let state = AppState::new(pool);
+        let long_scan = tokio::spawn(std::future::pending::<()>());
+        state.occupy_scan_slot(long_scan.abort_handle()).await;
+        let scan_token = state.new_cancel_token().await;
+
+        let options = |source: &str| IngestOptions {
+            source: dir.path().join(source),
+            destination: dir.path().to_path_buf(),
+            template: ingest::DEFAULT_TEMPLATE.to_string(),
+        };
+        let result = state.start_ingest(options("."), CollectingSink::default()).await;
+        assert!(matches!(result, Err(AppError::ScanRunning)));
+        // Cancelling still reaches the running scan
+        state.cancel_scan().await;
+        assert!(scan_token.is_cancelled());
+
+        // Paths are resolved before anything starts
+        let result = state.start_ingest(options("missing"), CollectingSink::default()).await;
+        assert!(matches!(result, Err(AppError::Io(_))));
+        long_scan.abort();
+    }
+}
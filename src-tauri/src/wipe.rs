@@ -0,0 +1,181 @@
+use crate::db::{self, DbPool};
+use crate::error::AppError;
+use crate::models::{WipeCategory, WipeReport, WipeRiskFile};
+use crate::paths;
+
+const RISK_KINDS: [&str; 5] = [
+    "no_other_copy",
+    "no_backup_copy",
+    "not_hashed",
+    "same_site_only",
+    "unverified_only",
+];
+
+/// Answers "can this device (or folder on it) be wiped?" from the catalog
+/// alone. Each live file under `prefix` is judged by its copies on *other*
+/// devices: with none, with a placeholder hash whose content was never read,
+/// or with copies only where they don't count as backups (cards, archive
+/// devices, manifests; see `db::BACKUP_COPY`), wiping loses data; with
+/// copies only at the same site, or none verified in the last
+/// `stale_after_days`, it loses redundancy.
+pub async fn wipe_report(
+    pool: &DbPool,
+    device_id: &str,
+    prefix: &str,
+    stale_after_days: Option<i64>,
+) -> Result<WipeReport, AppError> {
+    let device = db::get_all_devices(pool)
+        .await?
+        .into_iter()
+        .find(|d| d.id == device_id)
+        .ok_or_else(|| AppError::General(format!("Unknown device: {}", device_id)))?;
+    let verified_since = stale_after_days.map(|days| {
+        (chrono::Utc::now() - chrono::Duration::days(days))
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string()
+    });
+
+    let candidates = db::get_wipe_candidates(
+        pool,
+        device_id,
+        &paths::prefix_raw(prefix),
+        device.site.as_deref(),
+        verified_since.as_deref(),
+    )
+    .await?;
+
+    let mut report = WipeReport {
+        device_id: device_id.to_string(),
+        prefix: prefix.trim_matches('/').to_string(),
+        site: device.site,
+        total_files: 0,
+        total_bytes: 0,
+        safe: WipeCategory {
+            kind: "safe".to_string(),
+            ..Default::default()
+        },
+        at_risk: RISK_KINDS
+            .iter()
+            .map(|kind| WipeCategory {
+                kind: kind.to_string(),
+                ..Default::default()
+            })
+            .collect(),
+        files: Vec::new(),
+        verdict: String::new(),
+    };
+
+    for c in candidates {
+        report.total_files += 1;
+        report.total_bytes += c.file_size;
+        // A deferred placeholder only matches files of the same size and
+        // mtime, so its "copies" say nothing about content
+        let kind = if c.blake3_hash.starts_with("deferred:") {
+            "not_hashed"
+        } else if c.other_copies == 0 {
+            "no_other_copy"
+        } else if c.backup_copies == 0 {
+            "no_backup_copy"
+        } else if c.off_site_copies == 0 {
+            "same_site_only"
+        } else if c.verified_copies == 0 {
+            "unverified_only"
+        } else {
+            report.safe.files += 1;
+            report.safe.bytes += c.file_size;
+            continue;
+        };
+
+        if let Some(category) = report.at_risk.iter_mut().find(|cat| cat.kind == kind) {
+            category.files += 1;
+            category.bytes += c.file_size;
+        }
+        report.files.push(WipeRiskFile {
+            file_path: c.file_path,
+            blake3_hash: c.blake3_hash,
+            file_size: c.file_size,
+            kind: kind.to_string(),
+            other_copies: c.other_copies,
+        });
+    }
+
+    let count = |kind: &str| report.at_risk.iter().find(|c| c.kind == kind).map_or(0, |c| c.files);
+    report.verdict = if count("no_other_copy") + count("no_backup_copy") + count("not_hashed") > 0 {
+        "unsafe"
+    } else if count("same_site_only") + count("unverified_only") > 0 {
+        "caution"
+    } else {
+        "safe"
+    }
+    .to_string();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::{add_device, add_location, temp_pool};
+
+    async fn kinds(pool: &DbPool, prefix: &str) -> (String, Vec<(String, String)>) {
+        let report = wipe_report(pool, "card", prefix, None).await.unwrap();
+        let mut files: Vec<_> = report.files.into_iter().map(|f| (f.file_path, f.kind)).collect();
+        files.sort();
+        (report.verdict, files)
+    }
+
+    #[tokio::test]
+    async fn copies_that_are_not_backups_leave_a_card_unsafe() {
+        let (_dir, pool) = temp_pool().await;
+        add_device(&pool, "card", "unknown").await;
+        add_device(&pool, "card2", "unknown").await;
+        add_device(&pool, "office", "hot").await;
+        add_device(&pool, "vault", "cold").await;
+        sqlx::query(
+            "UPDATE storage_devices SET
+                 is_removable = id LIKE 'card%',
+                 site = CASE id WHEN 'vault' THEN 'home' ELSE 'office' END",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let scan = |device: &'static str| db::begin_scan(&pool, device, "", b"", "full");
+        let (card, card2, office, vault) = (
+            scan("card").await.unwrap(),
+            scan("card2").await.unwrap(),
+            scan("office").await.unwrap(),
+            scan("vault").await.unwrap(),
+        );
+        for path in ["a/only.mov", "a/other_card.mov", "a/manifest.mov", "b/on_site.mov", "c/off_site.mov"] {
+            add_location(&pool, card, "card", path).await;
+        }
+        add_location(&pool, card2, "card2", "a/other_card.mov").await;
+        add_location(&pool, vault, "vault", "a/manifest.mov").await;
+        sqlx::query("UPDATE file_locations SET scan_mode = 'manifest' WHERE device_id = 'vault'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        add_location(&pool, office, "office", "b/on_site.mov").await;
+        add_location(&pool, vault, "vault", "c/off_site.mov").await;
+
+        let (verdict, files) = kinds(&pool, "a").await;
+        assert_eq!(verdict, "unsafe");
+        assert_eq!(
+            files,
+            [
+                ("a/manifest.mov".to_string(), "no_backup_copy".to_string()),
+                ("a/only.mov".to_string(), "no_other_copy".to_string()),
+                ("a/other_card.mov".to_string(), "no_backup_copy".to_string()),
+            ]
+        );
+        assert_eq!(
+            kinds(&pool, "b").await,
+            ("caution".to_string(), vec![("b/on_site.mov".to_string(), "same_site_only".to_string())])
+        );
+        assert_eq!(kinds(&pool, "c").await, ("safe".to_string(), vec![]));
+
+        // One backup copy is enough, whatever else holds the content
+        add_location(&pool, vault, "vault", "a/other_card.mov").await;
+        let (_, files) = kinds(&pool, "a").await;
+        assert!(!files.iter().any(|(path, _)| path == "a/other_card.mov"));
+    }
+}
@@ -2,11 +2,36 @@ import { invoke, Channel } from "@tauri-apps/api/core";
 import type {
   StorageDevice,
   FileLocation,
+  NonUtf8File,
+  NameConflict,
   FileSafety,
   WasteCandidate,
   DashboardStats,
+  StatsBreakdown,
+  CapacityReport,
+  CapacitySample,
+  LostFile,
+  SnapshotInfo,
+  SnapshotDiff,
+  CatalogExportSummary,
+  CatalogImportSummary,
+  ManifestSummary,
+  FileDigest,
+  ChecksumImportReport,
+  ChecksumExportReport,
+  IngestReport,
+  WipeReport,
+  OverlapMatrix,
+  CompareReport,
+  DuplicateFolderReport,
+  FolderListing,
+  FolderFilePage,
   DirEntry,
   ScanEvent,
+  ListQuery,
+  SearchResult,
+  SavedQuery,
+  Page,
 } from "../types";
 
 export async function detectDevices(): Promise<StorageDevice[]> {
@@ -27,14 +52,48 @@ export async function setDeviceType(
   });
 }
 
+export async function setDeviceSite(
+  deviceId: string,
+  site: string | null
+): Promise<void> {
+  return invoke("set_device_site", {
+    deviceId,
+    site,
+  });
+}
+
 export async function startScan(
   target: string,
   mode: string,
-  onEvent: (event: ScanEvent) => void
+  onEvent: (event: ScanEvent) => void,
+  writeManifest = false,
+  digests: string[] = []
 ): Promise<void> {
   const channel = new Channel<ScanEvent>();
   channel.onmessage = onEvent;
-  return invoke("start_scan", { target, mode, onEvent: channel });
+  return invoke("start_scan", {
+    target,
+    mode,
+    writeManifest,
+    digests,
+    onEvent: channel,
+  });
+}
+
+export async function ingestCard(
+  source: string,
+  destination: string,
+  onEvent: (event: ScanEvent) => void,
+  template?: string
+): Promise<IngestReport> {
+  const channel = new Channel<ScanEvent>();
+  channel.onmessage = onEvent;
+  return invoke("ingest_card", {
+    source,
+    destination,
+    template: template ?? null,
+    onEvent: channel,
+  });
 }
 
 export async function cancelScan(): Promise<void> {
@@ -42,9 +101,23 @@ export async function cancelScan(): Promise<void> {
 }
 
 export async function getFilesOnDevice(
-  deviceId: string
-): Promise<FileLocation[]> {
-  return invoke("get_files_on_device", { deviceId });
+  deviceId: string,
+  query?: ListQuery
+): Promise<Page<FileLocation>> {
+  return invoke("get_files_on_device", { deviceId, query });
+}
+
+export async function getNonUtf8Files(
+  deviceId?: string
+): Promise<NonUtf8File[]> {
+  return invoke("get_non_utf8_files", { deviceId });
+}
+
+export async function getNameConflicts(
+  deviceId?: string,
+  acrossDevices?: boolean
+): Promise<NameConflict[]> {
+  return invoke("get_name_conflicts", { deviceId, acrossDevices });
 }
 
 export async function getFileSafety(
@@ -53,14 +126,17 @@ export async function getFileSafety(
   return invoke("get_file_safety", { hash });
 }
 
-export async function getUnsafeFiles(): Promise<FileSafety[]> {
-  return invoke("get_unsafe_files");
+export async function getUnsafeFiles(
+  query?: ListQuery
+): Promise<Page<FileSafety>> {
+  return invoke("get_unsafe_files", { query });
 }
 
 export async function getWasteCandidates(
-  threshold?: number
-): Promise<WasteCandidate[]> {
-  return invoke("get_waste_candidates", { threshold });
+  threshold?: number,
+  query?: ListQuery
+): Promise<Page<WasteCandidate>> {
+  return invoke("get_waste_candidates", { threshold, query });
 }
 
 export async function browseDirectory(path: string): Promise<DirEntry[]> {
@@ -73,6 +149,182 @@ export async function getFileLocations(
   return invoke("get_file_locations", { hash });
 }
 
+export async function getLocationHistory(
+  hash: string
+): Promise<FileLocation[]> {
+  return invoke("get_location_history", { hash });
+}
+
+export async function getLostFiles(): Promise<LostFile[]> {
+  return invoke("get_lost_files");
+}
+
+export async function getSnapshots(
+  deviceId: string
+): Promise<SnapshotInfo[]> {
+  return invoke("get_snapshots", { deviceId });
+}
+
+export async function diffSnapshots(
+  fromScanId: number,
+  toScanId: number
+): Promise<SnapshotDiff> {
+  return invoke("diff_snapshots", { fromScanId, toScanId });
+}
+
+export async function exportCatalog(
+  path: string
+): Promise<CatalogExportSummary> {
+  return invoke("export_catalog", { path });
+}
+
+export async function importCatalog(
+  path: string
+): Promise<CatalogImportSummary> {
+  return invoke("import_catalog", { path });
+}
+
+export async function writeDeviceManifest(
+  deviceId: string,
+  mountPoint: string
+): Promise<ManifestSummary> {
+  return invoke("write_device_manifest", { deviceId, mountPoint });
+}
+
+export async function rebuildFromManifest(
+  mountPoint: string
+): Promise<CatalogImportSummary> {
+  return invoke("rebuild_from_manifest", { mountPoint });
+}
+
+export async function importChecksumFile(
+  path: string,
+  algorithm?: string,
+  rehash = false
+): Promise<ChecksumImportReport> {
+  return invoke("import_checksum_file", { path, algorithm, rehash });
+}
+
+export async function exportChecksumFile(
+  deviceId: string,
+  prefix: string,
+  format: "b3sum" | "sha256sum" | "hashdeep",
+  path: string,
+  staleAfterDays?: number,
+  reverify = false
+): Promise<ChecksumExportReport> {
+  return invoke("export_checksum_file", {
+    deviceId,
+    prefix,
+    format,
+    path,
+    staleAfterDays,
+    reverify,
+  });
+}
+
+export async function getFileDigests(hash: string): Promise<FileDigest[]> {
+  return invoke("get_file_digests", { hash });
+}
+
 export async function getDashboardStats(): Promise<DashboardStats> {
   return invoke("get_dashboard_stats");
 }
+
+export async function getStatsBreakdown(): Promise<StatsBreakdown> {
+  return invoke("get_stats_breakdown");
+}
+
+export async function getCapacityReport(): Promise<CapacityReport> {
+  return invoke("get_capacity_report");
+}
+
+export async function getCapacityHistory(deviceId: string): Promise<CapacitySample[]> {
+  return invoke("get_capacity_history", { deviceId });
+}
+
+export async function getWipeReport(
+  deviceId: string,
+  prefix?: string,
+  staleAfterDays?: number
+): Promise<WipeReport> {
+  return invoke("get_wipe_report", {
+    deviceId,
+    prefix: prefix ?? null,
+    staleAfterDays: staleAfterDays ?? null,
+  });
+}
+
+export async function getOverlapMatrix(): Promise<OverlapMatrix> {
+  return invoke("get_overlap_matrix");
+}
+
+export async function compareFolders(
+  leftDevice: string,
+  leftPrefix: string,
+  rightDevice: string,
+  rightPrefix: string
+): Promise<CompareReport> {
+  return invoke("compare_folders", {
+    leftDevice,
+    leftPrefix,
+    rightDevice,
+    rightPrefix,
+  });
+}
+
+export async function findDuplicateFolders(
+  minBytes: number,
+  similarity?: number
+): Promise<DuplicateFolderReport> {
+  return invoke("find_duplicate_folders", { minBytes, similarity });
+}
+
+export async function getFolderListing(
+  deviceId: string,
+  path: string
+): Promise<FolderListing> {
+  return invoke("get_folder_listing", { deviceId, path });
+}
+
+export async function getFolderFiles(
+  deviceId: string,
+  path: string,
+  limit: number,
+  cursor?: string
+): Promise<FolderFilePage> {
+  return invoke("get_folder_files", { deviceId, path, cursor, limit });
+}
+
+export async function searchFiles(
+  query: string,
+  limit?: number
+): Promise<SearchResult[]> {
+  return invoke("search_files", { query, limit });
+}
+
+export async function runCatalogQuery(
+  query: string,
+  page?: ListQuery
+): Promise<Page<FileSafety>> {
+  return invoke("run_catalog_query", { query, page });
+}
+
+export async function runSavedQuery(
+  name: string,
+  page?: ListQuery
+): Promise<Page<FileSafety>> {
+  return invoke("run_saved_query", { name, page });
+}
+
+export async function saveQuery(name: string, query: string): Promise<void> {
+  return invoke("save_query", { name, query });
+}
+
+export async function getSavedQueries(): Promise<SavedQuery[]> {
+  return invoke("get_saved_queries");
+}
+
+export async function deleteSavedQuery(name: string): Promise<boolean> {
+  return invoke("delete_saved_query", { name });
+}
@@ -1,12 +1,45 @@
+function formatBytes(bytes: number): string {
+  if (bytes === 0) return "0 B";
+  const k = 1024;
+  const sizes = ["B", "KB", "MB", "GB", "TB"];
+  const i = Math.floor(Math.log(bytes) / Math.log(k));
+  return parseFloat((bytes / Math.pow(k, i)).toFixed(1)) + " " + sizes[i];
+}
+
+function formatDuration(secs: number): string {
+  if (secs < 60) return `${secs}s`;
+  if (secs < 3600) return `${Math.floor(secs / 60)}m ${secs % 60}s`;
+  return `${Math.floor(secs / 3600)}h ${Math.floor((secs % 3600) / 60)}m`;
+}
+
 interface Props {
   scanned: number;
   total: number;
+  bytesProcessed: number;
+  totalBytes: number;
+  bytesPerSec: number;
+  etaSecs: number | null;
   hashed: number;
   lastFile: string;
 }
 
-export function ProgressBar({ scanned, total, hashed, lastFile }: Props) {
-  const pct = total > 0 ? (scanned / total) * 100 : 0;
+export function ProgressBar({
+  scanned,
+  total,
+  bytesProcessed,
+  totalBytes,
+  bytesPerSec,
+  etaSecs,
+  hashed,
+  lastFile,
+}: Props) {
+  // Bytes track hashing time far better than file counts
+  const pct =
+    totalBytes > 0
+      ? (bytesProcessed / totalBytes) * 100
+      : total > 0
+        ? (scanned / total) * 100
+        : 0;
 
   return (
     <div className="progress-container">
@@ -17,6 +50,11 @@ export function ProgressBar({ scanned, total, hashed, lastFile }: Props) {
         <span>
           {scanned} / {total} files scanned
         </span>
+        <span>
+          {formatBytes(bytesProcessed)} / {formatBytes(totalBytes)}
+        </span>
+        {bytesPerSec > 0 && <span>{formatBytes(bytesPerSec)}/s</span>}
+        {etaSecs !== null && <span>{formatDuration(etaSecs)} left</span>}
         <span>{hashed} hashed</span>
       </div>
       {lastFile && <div className="progress-file">{lastFile}</div>}
@@ -1,5 +1,10 @@
 import { useState, useCallback } from "react";
-import type { FileLocation, FileSafety, WasteCandidate } from "../types";
+import type {
+  FileLocation,
+  FileSafety,
+  ListQuery,
+  WasteCandidate,
+} from "../types";
 import {
   getFilesOnDevice,
   getUnsafeFiles,
@@ -12,37 +17,63 @@ export function useFiles() {
   const [files, setFiles] = useState<FileLocation[]>([]);
   const [unsafeFiles, setUnsafeFiles] = useState<FileSafety[]>([]);
   const [waste, setWaste] = useState<WasteCandidate[]>([]);
+  const [total, setTotal] = useState(0);
+  // Fetches the page after the last one loaded, or null when there is none
+  const [nextPage, setNextPage] = useState<(() => Promise<void>) | null>(null);
   const [loading, setLoading] = useState(false);
 
-  const loadDeviceFiles = useCallback(async (deviceId: string) => {
-    setLoading(true);
-    try {
-      const f = await getFilesOnDevice(deviceId);
-      setFiles(f);
-    } finally {
-      setLoading(false);
-    }
-  }, []);
+  const loadDeviceFiles = useCallback(
+    async (deviceId: string, query: ListQuery = {}) => {
+      setLoading(true);
+      try {
+        const page = await getFilesOnDevice(deviceId, query);
+        setFiles((prev) => (query.cursor ? [...prev, ...page.items] : page.items));
+        setTotal(page.total);
+        const cursor = page.next_cursor;
+        setNextPage(() =>
+          cursor ? () => loadDeviceFiles(deviceId, { ...query, cursor }) : null
+        );
+      } finally {
+        setLoading(false);
+      }
+    },
+    []
+  );
 
-  const loadUnsafeFiles = useCallback(async () => {
+  const loadUnsafeFiles = useCallback(async (query: ListQuery = {}) => {
     setLoading(true);
     try {
-      const f = await getUnsafeFiles();
-      setUnsafeFiles(f);
+      const page = await getUnsafeFiles(query);
+      setUnsafeFiles((prev) =>
+        query.cursor ? [...prev, ...page.items] : page.items
+      );
+      setTotal(page.total);
+      const cursor = page.next_cursor;
+      setNextPage(() =>
+        cursor ? () => loadUnsafeFiles({ ...query, cursor }) : null
+      );
     } finally {
       setLoading(false);
     }
   }, []);
 
-  const loadWaste = useCallback(async (threshold?: number) => {
-    setLoading(true);
-    try {
-      const w = await getWasteCandidates(threshold);
-      setWaste(w);
-    } finally {
-      setLoading(false);
-    }
-  }, []);
+  const loadWaste = useCallback(
+    async (threshold?: number, query: ListQuery = {}) => {
+      setLoading(true);
+      try {
+        const page = await getWasteCandidates(threshold, query);
+        setWaste((prev) => (query.cursor ? [...prev, ...page.items] : page.items));
+        setTotal(page.total);
+        const cursor = page.next_cursor;
+        setNextPage(() =>
+          cursor ? () => loadWaste(threshold, { ...query, cursor }) : null
+        );
+      } finally {
+        setLoading(false);
+      }
+    },
+    []
+  );
 
   const loadFileSafety = useCallback(async (hash: string) => {
     return getFileSafety(hash);
@@ -56,10 +87,12 @@ export function useFiles() {
     files,
     unsafeFiles,
     waste,
+    total,
     loading,
     loadDeviceFiles,
     loadUnsafeFiles,
     loadWaste,
+    loadMore: nextPage,
     loadFileSafety,
     loadLocations,
   };
@@ -6,6 +6,10 @@ export interface ScanState {
   scanning: boolean;
   total: number;
   scanned: number;
+  bytesProcessed: number;
+  totalBytes: number;
+  bytesPerSec: number;
+  etaSecs: number | null;
   hashed: number;
   added: number;
   removed: number;
@@ -18,6 +22,10 @@ const initialState: ScanState = {
   scanning: false,
   total: 0,
   scanned: 0,
+  bytesProcessed: 0,
+  totalBytes: 0,
+  bytesPerSec: 0,
+  etaSecs: null,
   hashed: 0,
   added: 0,
   removed: 0,
@@ -41,12 +49,20 @@ export function useScanProgress() {
         return;
       }
       if ("Started" in event) {
-        setState((s) => ({ ...s, total: event.Started.total_files }));
+        setState((s) => ({
+          ...s,
+          total: event.Started.total_files,
+          totalBytes: event.Started.total_bytes,
+        }));
       } else if ("Progress" in event) {
         setState((s) => ({
           ...s,
           scanned: event.Progress.scanned,
           total: event.Progress.total,
+          bytesProcessed: event.Progress.bytes_processed,
+          totalBytes: event.Progress.total_bytes,
+          bytesPerSec: event.Progress.bytes_per_sec,
+          etaSecs: event.Progress.eta_secs,
         }));
       } else if ("FileHashed" in event) {
         setState((s) => ({
@@ -59,6 +75,7 @@ export function useScanProgress() {
           ...s,
           scanning: false,
           finished: true,
+          etaSecs: null,
           scanned: event.Finished.scanned,
           hashed: event.Finished.hashed,
           added: event.Finished.added,
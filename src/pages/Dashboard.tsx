@@ -1,6 +1,6 @@
 import { useEffect, useState } from "react";
-import type { DashboardStats } from "../types";
-import { getDashboardStats } from "../api/commands";
+import type { CapacityReport, DashboardStats, StatBucket, StatsBreakdown } from "../types";
+import { getCapacityReport, getDashboardStats, getStatsBreakdown } from "../api/commands";
 
 function formatBytes(bytes: number): string {
   if (bytes === 0) return "0 B";
@@ -10,11 +10,94 @@ function formatBytes(bytes: number): string {
   return parseFloat((bytes / Math.pow(k, i)).toFixed(1)) + " " + sizes[i];
 }
 
+const BREAKDOWNS: { key: keyof StatsBreakdown; label: string }[] = [
+  { key: "by_category", label: "Media type" },
+  { key: "by_year", label: "Year" },
+  { key: "by_device_type", label: "Device type" },
+  { key: "by_extension", label: "Extension" },
+];
+
+function BreakdownTable({ buckets }: { buckets: StatBucket[] }) {
+  return (
+    <table className="file-table">
+      <thead>
+        <tr>
+          <th></th>
+          <th>Files</th>
+          <th>Unique</th>
+          <th>Duplicate</th>
+          <th>Unsafe</th>
+          <th>Not hashed</th>
+        </tr>
+      </thead>
+      <tbody>
+        {buckets.map((b) => (
+          <tr key={b.key}>
+            <td>{b.key || "(none)"}</td>
+            <td>{b.files}</td>
+            <td>{formatBytes(b.unique_bytes)}</td>
+            <td>{formatBytes(b.duplicate_bytes)}</td>
+            <td className={b.unsafe_bytes > 0 ? "unsafe-bytes" : ""}>{formatBytes(b.unsafe_bytes)}</td>
+            <td>{formatBytes(b.not_hashed_bytes)}</td>
+          </tr>
+        ))}
+      </tbody>
+    </table>
+  );
+}
+
+function CapacityTable({ report }: { report: CapacityReport }) {
+  const { cold } = report;
+  return (
+    <div className="breakdown">
+      <h2>Capacity</h2>
+      <table className="file-table">
+        <thead>
+          <tr>
+            <th>Device</th>
+            <th>Type</th>
+            <th>Free</th>
+            <th>Growth</th>
+            <th>Full by</th>
+          </tr>
+        </thead>
+        <tbody>
+          {report.devices.map((d) => (
+            <tr key={d.device_id}>
+              <td>{d.label}</td>
+              <td>{d.device_type}</td>
+              <td>
+                {formatBytes(d.available_bytes)} of {formatBytes(d.total_bytes)}
+              </td>
+              <td>
+                {d.growth_bytes_per_day === null
+                  ? "—"
+                  : `${d.growth_bytes_per_day < 0 ? "-" : "+"}${formatBytes(Math.round(Math.abs(d.growth_bytes_per_day)))}/day`}
+              </td>
+              <td>{d.full_on ?? "—"}</td>
+            </tr>
+          ))}
+        </tbody>
+      </table>
+      <p className={cold.enough_room ? "" : "unsafe-bytes"}>
+        {formatBytes(cold.backlog_bytes)} in {cold.backlog_files} files has no cold copy;{" "}
+        {cold.cold_devices} cold devices have {formatBytes(cold.available_bytes)} free
+        {cold.enough_room || cold.cold_devices === 0 ? "." : ` (short by ${formatBytes(cold.shortfall_bytes)}).`}
+      </p>
+    </div>
+  );
+}
+
 export function Dashboard() {
   const [stats, setStats] = useState<DashboardStats | null>(null);
+  const [breakdown, setBreakdown] = useState<StatsBreakdown | null>(null);
+  const [view, setView] = useState<keyof StatsBreakdown>("by_category");
+  const [capacity, setCapacity] = useState<CapacityReport | null>(null);
 
   useEffect(() => {
     getDashboardStats().then(setStats).catch(console.error);
+    getStatsBreakdown().then(setBreakdown).catch(console.error);
+    getCapacityReport().then(setCapacity).catch(console.error);
   }, []);
 
   if (!stats) return <div className="page">Loading...</div>;
@@ -35,6 +118,10 @@ export function Dashboard() {
           <div className="stat-value">{stats.unsafe_files}</div>
           <div className="stat-label">Unsafe Files</div>
         </div>
+        <div className={`stat-card ${stats.lost_files > 0 ? "stat-danger" : ""}`}>
+          <div className="stat-value">{stats.lost_files}</div>
+          <div className="stat-label">Lost Files</div>
+        </div>
         <div className="stat-card">
           <div className="stat-value">{stats.total_devices}</div>
           <div className="stat-label">Devices</div>
@@ -44,6 +131,23 @@ export function Dashboard() {
           <div className="stat-label">Total Size</div>
         </div>
       </div>
+      {breakdown && (
+        <div className="breakdown">
+          <div className="breakdown-tabs">
+            {BREAKDOWNS.map((b) => (
+              <button
+                key={b.key}
+                className={view === b.key ? "btn-primary" : ""}
+                onClick={() => setView(b.key)}
+              >
+                {b.label}
+              </button>
+            ))}
+          </div>
+          <BreakdownTable buckets={breakdown[view]} />
+        </div>
+      )}
+      {capacity && <CapacityTable report={capacity} />}
     </div>
   );
 }
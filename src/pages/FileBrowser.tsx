@@ -5,8 +5,16 @@ import { FileTable } from "../components/FileTable";
 
 export function FileBrowser() {
   const { devices } = useDevices();
-  const { files, unsafeFiles, loading, loadDeviceFiles, loadUnsafeFiles, loadFileSafety } =
-    useFiles();
+  const {
+    files,
+    unsafeFiles,
+    total,
+    loading,
+    loadDeviceFiles,
+    loadUnsafeFiles,
+    loadMore,
+    loadFileSafety,
+  } = useFiles();
   const [selectedDevice, setSelectedDevice] = useState("");
   const [filter, setFilter] = useState<"all" | "unsafe">("all");
 
@@ -62,11 +70,18 @@ export function FileBrowser() {
         )}
       </div>
 
-      {loading ? (
-        <div>Loading...</div>
-      ) : (
+      {displayFiles.length > 0 && (
         <FileTable files={displayFiles} onGetSafety={loadFileSafety} />
       )}
+      {loading && <div>Loading...</div>}
+      {!loading && loadMore && (
+        <div className="load-more">
+          <span>
+            {filter === "unsafe" ? unsafeFiles.length : files.length} of {total}
+          </span>
+          <button onClick={loadMore}>Load more</button>
+        </div>
+      )}
 
       {!loading && displayFiles.length === 0 && (
         <p className="empty">
@@ -106,6 +106,10 @@ export function Scanner({ initialDevice }: Props) {
         <ProgressBar
           scanned={progress.scanned}
           total={progress.total}
+          bytesProcessed={progress.bytesProcessed}
+          totalBytes={progress.totalBytes}
+          bytesPerSec={progress.bytesPerSec}
+          etaSecs={progress.etaSecs}
           hashed={progress.hashed}
           lastFile={progress.lastFile}
         />
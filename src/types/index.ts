@@ -8,19 +8,34 @@ export interface StorageDevice {
   is_removable: boolean;
   first_seen: string;
   last_seen: string;
+  site: string | null;
   is_connected: boolean;
 }
 
 export interface FileLocation {
   id: number;
+  file_id: number;
   blake3_hash: string;
   device_id: string;
   file_path: string;
+  path_key: string;
   file_name: string;
   file_size: number;
   modified_at: string | null;
   last_verified: string;
   scan_mode: string;
+  removed_at: string | null;
+  removed_by_scan: number | null;
+}
+
+export interface NonUtf8File {
+  escaped_path: string;
+  location: FileLocation;
+}
+
+export interface NameConflict {
+  path_key: string;
+  variants: FileLocation[];
 }
 
 export interface FileSafety {
@@ -32,6 +47,7 @@ export interface FileSafety {
   cold_copies: number;
   is_safe: boolean;
   locations: FileLocation[];
+  digests: FileDigest[];
 }
 
 export interface WasteCandidate {
@@ -48,6 +64,176 @@ export interface DashboardStats {
   unsafe_files: number;
   total_devices: number;
   total_size_bytes: number;
+  lost_files: number;
+}
+
+/** Duplicate bytes are copies beyond the first; unsafe bytes are files
+ * with no cold copy or fewer than two copies. Files with only a placeholder
+ * hash are counted as not hashed instead. */
+export interface StatBucket {
+  key: string;
+  files: number;
+  unique_bytes: number;
+  duplicate_bytes: number;
+  unsafe_bytes: number;
+  not_hashed_files: number;
+  not_hashed_bytes: number;
+}
+
+export interface StatsBreakdown {
+  by_category: StatBucket[];
+  by_extension: StatBucket[];
+  by_year: StatBucket[];
+  by_device_type: StatBucket[];
+}
+
+export interface CapacitySample {
+  device_id: string;
+  sampled_at: string;
+  total_bytes: number;
+  available_bytes: number;
+  catalogued_bytes: number;
+}
+
+/** Growth is per day over the last 90 days; null until samples span a day.
+ * No fill date when usage is flat or shrinking. */
+export interface DeviceForecast {
+  device_id: string;
+  label: string;
+  device_type: string;
+  total_bytes: number;
+  available_bytes: number;
+  catalogued_bytes: number;
+  samples: number;
+  growth_bytes_per_day: number | null;
+  catalogued_growth_bytes_per_day: number | null;
+  days_until_full: number | null;
+  full_on: string | null;
+}
+
+/** Backlog is live content with no copy on a cold device. */
+export interface ColdCapacity {
+  cold_devices: number;
+  available_bytes: number;
+  backlog_files: number;
+  backlog_bytes: number;
+  shortfall_bytes: number;
+  enough_room: boolean;
+}
+
+export interface CapacityReport {
+  devices: DeviceForecast[];
+  cold: ColdCapacity;
+}
+
+export interface LostFile {
+  blake3_hash: string;
+  file_size: number;
+  representative_name: string;
+  last_device_id: string | null;
+  last_file_path: string | null;
+  removed_at: string | null;
+  removed_by_scan: number | null;
+}
+
+export interface SnapshotInfo {
+  scan_id: number;
+  device_id: string;
+  scan_prefix: string;
+  scan_mode: string;
+  entry_count: number;
+  total_bytes: number;
+  created_at: string;
+}
+
+export interface SnapshotChange {
+  file_path: string;
+  blake3_hash: string;
+  file_size: number;
+  previous_hash: string | null;
+  previous_size: number | null;
+}
+
+export interface SnapshotMove {
+  from_path: string;
+  to_path: string;
+  blake3_hash: string;
+  file_size: number;
+}
+
+export interface SnapshotDiff {
+  from_scan_id: number;
+  to_scan_id: number;
+  added: SnapshotChange[];
+  removed: SnapshotChange[];
+  modified: SnapshotChange[];
+  moved: SnapshotMove[];
+  added_bytes: number;
+  removed_bytes: number;
+  modified_bytes: number;
+  moved_bytes: number;
+}
+
+export interface FileDigest {
+  algorithm: string;
+  digest: string;
+  source: string;
+  verified_at: string | null;
+}
+
+export interface ChecksumIssue {
+  file_path: string;
+  kind: string; // "outside_device" | "not_catalogued" | "not_hashed" | "missing" | "size_mismatch" | "unreadable" | "digest_mismatch" | "conflict"
+  algorithm: string | null;
+  expected: string | null;
+  actual: string | null;
+}
+
+export interface ChecksumImportReport {
+  checksum_file: string;
+  device_id: string;
+  entries: number;
+  matched: number;
+  verified: number;
+  stored: number;
+  issues: ChecksumIssue[];
+}
+
+export interface ChecksumExportFlag {
+  file_path: string;
+  kind: string; // "stale" | "deferred" | "missing_digest" | "missing" | "changed"
+  last_verified: string;
+  included: boolean;
+}
+
+export interface ChecksumExportReport {
+  output_path: string;
+  format: string;
+  written: number;
+  verified: number;
+  flags: ChecksumExportFlag[];
+}
+
+export interface CatalogExportSummary {
+  devices: number;
+  files: number;
+  digests: number;
+  locations: number;
+}
+
+export interface CatalogImportSummary {
+  devices: number;
+  files: number;
+  digests: number;
+  locations_added: number;
+  locations_updated: number;
+  locations_skipped: number;
+}
+
+export interface ManifestSummary {
+  device_id: string;
+  manifest_path: string;
+  entries: number;
 }
 
 export interface DirEntry {
@@ -58,9 +244,227 @@ export interface DirEntry {
 }
 
 export type ScanEvent =
-  | { Started: { total_files: number } }
-  | { Progress: { scanned: number; total: number } }
+  | { Started: { total_files: number; total_bytes: number } }
+  | {
+      Progress: {
+        scanned: number;
+        total: number;
+        bytes_processed: number;
+        total_bytes: number;
+        bytes_per_sec: number;
+        eta_secs: number | null;
+      };
+    }
   | { FileHashed: { path: string; hash: string } }
   | { Finished: { scanned: number; hashed: number; added: number; removed: number } }
   | { Error: { message: string } }
   | "Cancelled";
+
+export interface IngestIssue {
+  file_path: string;
+  message: string;
+}
+
+export interface IngestReport {
+  source_device_id: string;
+  destination_device_id: string;
+  files: number;
+  copied: number;
+  bytes_copied: number;
+  already_catalogued: number;
+  already_present: number;
+  issues: IngestIssue[];
+  cancelled: boolean;
+  safe_to_format: boolean;
+}
+
+export interface WipeRiskFile {
+  file_path: string;
+  blake3_hash: string;
+  file_size: number;
+  kind: string; // "no_other_copy" | "no_backup_copy" | "not_hashed" | "same_site_only" | "unverified_only"
+  other_copies: number;
+}
+
+export interface WipeCategory {
+  kind: string;
+  files: number;
+  bytes: number;
+}
+
+export interface WipeReport {
+  device_id: string;
+  prefix: string;
+  site: string | null;
+  total_files: number;
+  total_bytes: number;
+  safe: WipeCategory;
+  at_risk: WipeCategory[];
+  files: WipeRiskFile[];
+  verdict: string; // "safe" | "caution" | "unsafe"
+}
+
+export interface DeviceContentTotals {
+  device_id: string;
+  label: string;
+  device_type: string;
+  files: number;
+  bytes: number;
+  unique_files: number;
+  unique_bytes: number;
+}
+
+export interface DeviceOverlap {
+  device_a: string;
+  device_b: string;
+  shared_files: number;
+  shared_bytes: number;
+}
+
+/** Each pair appears once; mirror it for a symmetric heatmap. */
+export interface OverlapMatrix {
+  devices: DeviceContentTotals[];
+  pairs: DeviceOverlap[];
+}
+
+export interface CompareCategory {
+  files: number;
+  bytes: number;
+}
+
+export interface CompareSummary {
+  identical: CompareCategory;
+  moved: CompareCategory;
+  different: CompareCategory;
+  only_left: CompareCategory;
+  only_right: CompareCategory;
+}
+
+export interface CompareEntry {
+  path: string;
+  blake3_hash: string;
+  file_size: number;
+}
+
+export interface MovedEntry {
+  left_path: string;
+  right_path: string;
+  blake3_hash: string;
+  file_size: number;
+}
+
+export interface PathConflict {
+  path: string;
+  left_hash: string;
+  left_size: number;
+  right_hash: string;
+  right_size: number;
+}
+
+export interface CompareReport {
+  left_device: string;
+  left_prefix: string;
+  right_device: string;
+  right_prefix: string;
+  summary: CompareSummary;
+  moved: MovedEntry[];
+  different: PathConflict[];
+  only_left: CompareEntry[];
+  only_right: CompareEntry[];
+  not_hashed: string[];
+  in_sync: boolean;
+}
+
+export interface DuplicateFolder {
+  device_id: string;
+  path: string;
+  file_count: number;
+  total_bytes: number;
+}
+
+export interface DuplicateFolderGroup {
+  content_hash: string;
+  folders: DuplicateFolder[];
+  file_count: number;
+  total_bytes: number;
+  reclaimable_bytes: number;
+}
+
+export interface SimilarFolderPair {
+  left: DuplicateFolder;
+  right: DuplicateFolder;
+  similarity: number;
+  shared_bytes: number;
+}
+
+export interface DuplicateFolderReport {
+  exact: DuplicateFolderGroup[];
+  similar: SimilarFolderPair[];
+  reclaimable_bytes: number;
+  similar_shared_bytes: number;
+}
+
+export interface FolderStats {
+  path: string;
+  name: string;
+  file_count: number;
+  total_bytes: number;
+  unsafe_count: number;
+  duplicate_bytes: number;
+}
+
+export interface FolderListing {
+  device_id: string;
+  folder: FolderStats | null;
+  children: FolderStats[];
+}
+
+export interface FolderFilePage {
+  files: FileLocation[];
+  total: number;
+  next_cursor: string | null;
+}
+
+export interface ListQuery {
+  cursor?: string;
+  limit?: number;
+  sort?: string;
+  descending?: boolean;
+  extension?: string;
+  min_size?: number;
+  max_size?: number;
+  modified_after?: string;
+  modified_before?: string;
+  name_contains?: string;
+  device_type?: string;
+}
+
+export interface Page<T> {
+  items: T[];
+  total: number;
+  next_cursor: string | null;
+}
+
+export interface SearchLocation {
+  location: FileLocation;
+  device_label: string;
+  device_type: string;
+  connected: boolean;
+}
+
+export interface SearchResult {
+  blake3_hash: string;
+  file_size: number;
+  representative_name: string;
+  total_copies: number;
+  cold_copies: number;
+  is_safe: boolean;
+  locations: SearchLocation[];
+}
+
+export interface SavedQuery {
+  name: string;
+  query: string;
+  created_at: string;
+  updated_at: string;
+}